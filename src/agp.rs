@@ -0,0 +1,320 @@
+//! A parser for AGP v2 files, describing the placement of components
+//! (contigs, scaffolds) into assembled objects (scaffolds, chromosomes):
+//! ```text
+//! object [TAB] object_beg [TAB] object_end [TAB] part_number [TAB] component_type [TAB] ...
+//! ```
+//! All coordinates are 1-based and inclusive, as in the AGP specification.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Lines, Read};
+
+use thiserror::Error;
+
+use crate::Strand;
+
+#[derive(Debug, Error)]
+pub enum AgpError {
+    #[error("AGP entry with missing fields: {0}")]
+    RecordTooShort(String),
+
+    #[error("invalid numeric field in AGP entry: {0}")]
+    InvalidNumber(String),
+
+    #[error("unrecognized orientation: {0}")]
+    UnknownOrientation(String),
+}
+
+#[derive(Debug, Clone)]
+pub enum AgpPart {
+    Component {
+        id: String,
+        beg: usize,
+        end: usize,
+        orientation: Strand,
+    },
+    Gap {
+        length: usize,
+        gap_type: String,
+        linkage: bool,
+    },
+}
+
+#[derive(Debug, Clone)]
+pub struct AgpRecord {
+    object: String,
+    object_beg: usize,
+    object_end: usize,
+    part_number: usize,
+    component_type: char,
+    part: AgpPart,
+}
+impl AgpRecord {
+    pub fn object(&self) -> &str {
+        &self.object
+    }
+
+    pub fn object_beg(&self) -> usize {
+        self.object_beg
+    }
+
+    pub fn object_end(&self) -> usize {
+        self.object_end
+    }
+
+    pub fn part_number(&self) -> usize {
+        self.part_number
+    }
+
+    pub fn component_type(&self) -> char {
+        self.component_type
+    }
+
+    pub fn part(&self) -> &AgpPart {
+        &self.part
+    }
+
+    pub fn is_gap(&self) -> bool {
+        matches!(self.part, AgpPart::Gap { .. })
+    }
+}
+
+pub struct AgpReader<T> {
+    buffer_lines: Lines<BufReader<T>>,
+}
+impl<T: Read> AgpReader<T> {
+    pub fn new(file: T) -> AgpReader<T> {
+        AgpReader {
+            buffer_lines: BufReader::new(file).lines(),
+        }
+    }
+}
+impl<T: Read> Iterator for AgpReader<T> {
+    type Item = Result<AgpRecord, AgpError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        fn make_record(line: &str) -> Result<AgpRecord, AgpError> {
+            let s = line.split('\t').collect::<Vec<_>>();
+            if s.len() < 8 {
+                return Err(AgpError::RecordTooShort(line.to_owned()));
+            }
+            let number = |x: &str| {
+                x.parse::<usize>()
+                    .map_err(|_| AgpError::InvalidNumber(line.to_owned()))
+            };
+            let component_type = s[4]
+                .chars()
+                .next()
+                .ok_or_else(|| AgpError::RecordTooShort(line.to_owned()))?;
+
+            let part = match component_type {
+                'N' | 'U' => AgpPart::Gap {
+                    length: number(s[5])?,
+                    gap_type: s[6].to_owned(),
+                    linkage: s[7] == "yes",
+                },
+                _ => AgpPart::Component {
+                    id: s[5].to_owned(),
+                    beg: number(s[6])?,
+                    end: number(s[7])?,
+                    orientation: match s.get(8).copied() {
+                        Some("+") => Strand::Direct,
+                        Some("-") => Strand::Reverse,
+                        Some("?") | Some("0") | Some("na") | None => Strand::Unknown,
+                        Some(_) => return Err(AgpError::UnknownOrientation(line.to_owned())),
+                    },
+                },
+            };
+
+            Ok(AgpRecord {
+                object: s[0].to_owned(),
+                object_beg: number(s[1])?,
+                object_end: number(s[2])?,
+                part_number: number(s[3])?,
+                component_type,
+                part,
+            })
+        }
+
+        self.buffer_lines
+            .by_ref()
+            .map(|l| l.unwrap())
+            .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+            .map(|l| make_record(&l))
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Placement {
+    object: String,
+    object_beg: usize,
+    beg: usize,
+    end: usize,
+    orientation: Strand,
+}
+
+/// The placement of components into objects, as described by one or more
+/// AGP files.
+#[derive(Debug, Default)]
+pub struct Assembly {
+    placements: HashMap<String, Vec<Placement>>,
+}
+impl Assembly {
+    pub fn new<I: IntoIterator<Item = AgpRecord>>(records: I) -> Assembly {
+        let mut r = Assembly::default();
+        r.extend(records);
+        r
+    }
+
+    pub fn from_reader<T: Read>(file: T) -> Result<Assembly, AgpError> {
+        Ok(Assembly::new(
+            AgpReader::new(file).collect::<Result<Vec<_>, _>>()?,
+        ))
+    }
+
+    pub fn extend<I: IntoIterator<Item = AgpRecord>>(&mut self, records: I) {
+        for record in records {
+            if let AgpPart::Component {
+                id,
+                beg,
+                end,
+                orientation,
+            } = record.part
+            {
+                self.placements.entry(id).or_default().push(Placement {
+                    object: record.object,
+                    object_beg: record.object_beg,
+                    beg,
+                    end,
+                    orientation,
+                });
+            }
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.placements.is_empty()
+    }
+
+    /// Whether `component` is placed in an object by this assembly
+    pub fn contains(&self, component: &str) -> bool {
+        self.placements.contains_key(component)
+    }
+
+    /// Project a 1-based position on `component` onto the object containing
+    /// it; return the object name, the position on it and whether the
+    /// component has been placed reversed.
+    pub fn project(&self, component: &str, pos: usize) -> Option<(&str, usize, bool)> {
+        self.placements
+            .get(component)?
+            .iter()
+            .find(|p| p.beg <= pos && pos <= p.end)
+            .map(|p| match p.orientation {
+                Strand::Reverse => (p.object.as_str(), p.object_beg + (p.end - pos), true),
+                _ => (p.object.as_str(), p.object_beg + (pos - p.beg), false),
+            })
+    }
+
+    /// Project a 1-based closed interval on `component` onto the object
+    /// containing it, adjusting the strand if the component is reversed;
+    /// return `None` unless the interval lies within a single placement.
+    pub fn project_interval(
+        &self,
+        component: &str,
+        start: usize,
+        end: usize,
+        strand: Strand,
+    ) -> Option<(&str, usize, usize, Strand)> {
+        let p = self
+            .placements
+            .get(component)?
+            .iter()
+            .find(|p| p.beg <= start && end <= p.end)?;
+        Some(match p.orientation {
+            Strand::Reverse => {
+                let mut strand = strand;
+                strand.reverse();
+                (
+                    p.object.as_str(),
+                    p.object_beg + (p.end - end),
+                    p.object_beg + (p.end - start),
+                    strand,
+                )
+            }
+            _ => (
+                p.object.as_str(),
+                p.object_beg + (start - p.beg),
+                p.object_beg + (end - p.beg),
+                strand,
+            ),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const AGP: &str = "##agp-version 2.0\n\
+                       # a comment\n\
+                       chr1\t1\t100\t1\tW\tctg1\t1\t100\t+\n\
+                       chr1\t101\t150\t2\tN\t50\tscaffold\tyes\tpaired-ends\n\
+                       chr1\t151\t250\t3\tW\tctg2\t1\t100\t-\n\
+                       chr2\t1\t50\t1\tW\tctg3\t51\t100\t+\n";
+
+    #[test]
+    fn records() {
+        let records = AgpReader::new(AGP.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(records.len(), 4);
+        assert_eq!(records[0].object(), "chr1");
+        assert_eq!(records[2].part_number(), 3);
+        assert!(records[1].is_gap());
+        assert!(matches!(
+            records[2].part(),
+            AgpPart::Component {
+                id,
+                beg: 1,
+                end: 100,
+                orientation: Strand::Reverse,
+            } if id == "ctg2"
+        ));
+    }
+
+    #[test]
+    fn invalid_records() {
+        for line in [
+            "chr1\t1\t100\t1\tW\tctg1\t1",
+            "chr1\t1\tx\t1\tW\tctg1\t1\t100\t+",
+            "chr1\t1\t100\t1\tW\tctg1\t1\t100\t*",
+        ] {
+            assert!(AgpReader::new(line.as_bytes()).next().unwrap().is_err());
+        }
+    }
+
+    #[test]
+    fn projections() {
+        let assembly = Assembly::from_reader(AGP.as_bytes()).unwrap();
+        assert!(assembly.contains("ctg1") && !assembly.contains("chr1"));
+
+        assert_eq!(assembly.project("ctg1", 10), Some(("chr1", 10, false)));
+        assert_eq!(assembly.project("ctg2", 10), Some(("chr1", 241, true)));
+        assert_eq!(assembly.project("ctg3", 60), Some(("chr2", 10, false)));
+        assert_eq!(assembly.project("ctg3", 10), None);
+        assert_eq!(assembly.project("ctg4", 10), None);
+
+        assert_eq!(
+            assembly.project_interval("ctg2", 10, 20, Strand::Direct),
+            Some(("chr1", 231, 241, Strand::Reverse))
+        );
+        assert_eq!(
+            assembly.project_interval("ctg1", 10, 20, Strand::Unknown),
+            Some(("chr1", 10, 20, Strand::Unknown))
+        );
+        // Straddling the start of the placed part of ctg3
+        assert_eq!(
+            assembly.project_interval("ctg3", 40, 60, Strand::Direct),
+            None
+        );
+    }
+}
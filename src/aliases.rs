@@ -0,0 +1,83 @@
+//! Chromosome aliases, as found in UCSC `chromAlias.txt` files:
+//! ```text
+//! # ucsc [TAB] assembly [TAB] genbank [TAB] refseq
+//! chr1 [TAB] 1 [TAB] CM000663.2 [TAB] NC_000001.11
+//! ```
+//! The first column holds the canonical name, all the others its aliases.
+//! As such files describe a single assembly, aliases are scoped by species.
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Read};
+
+#[derive(Debug, Default, Clone)]
+#[cfg_attr(feature = "binary", derive(serde::Serialize, serde::Deserialize))]
+pub struct ChromAliases {
+    /// The canonical name of each alias, by species
+    aliases: HashMap<String, HashMap<String, String>>,
+}
+impl ChromAliases {
+    pub fn from_reader<T: Read>(species: &str, file: T) -> std::io::Result<ChromAliases> {
+        let mut r = ChromAliases::default();
+        r.extend_from_reader(species, file)?;
+        Ok(r)
+    }
+
+    pub fn extend_from_reader<T: Read>(&mut self, species: &str, file: T) -> std::io::Result<()> {
+        for l in BufReader::new(file).lines() {
+            let l = l?;
+            if l.starts_with('#') || l.trim().is_empty() {
+                continue;
+            }
+            let mut s = l.split('\t').map(|x| x.trim()).filter(|x| !x.is_empty());
+            if let Some(canonical) = s.next() {
+                for alias in s {
+                    self.insert(species, alias, canonical);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn insert(&mut self, species: &str, alias: &str, canonical: &str) {
+        if alias != canonical {
+            self.aliases
+                .entry(species.to_owned())
+                .or_default()
+                .insert(alias.to_owned(), canonical.to_owned());
+        }
+    }
+
+    /// Return the canonical name of `chr` in `species`, which is itself if
+    /// it is not a known alias there
+    pub fn resolve<'a>(&'a self, species: &str, chr: &'a str) -> &'a str {
+        self.aliases
+            .get(species)
+            .and_then(|aliases| aliases.get(chr))
+            .map(|c| c.as_str())
+            .unwrap_or(chr)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.aliases.values().all(|aliases| aliases.is_empty())
+    }
+
+    /// Iterate over the (species, alias, canonical name) triplets
+    pub fn iter(&self) -> impl Iterator<Item = (&str, &str, &str)> {
+        self.aliases.iter().flat_map(|(species, aliases)| {
+            aliases
+                .iter()
+                .map(move |(a, c)| (species.as_str(), a.as_str(), c.as_str()))
+        })
+    }
+}
+impl FromIterator<(String, String, String)> for ChromAliases {
+    fn from_iter<I: IntoIterator<Item = (String, String, String)>>(iter: I) -> Self {
+        let mut r = ChromAliases::default();
+        for (species, alias, canonical) in iter {
+            r.aliases
+                .entry(species)
+                .or_default()
+                .insert(alias, canonical);
+        }
+        r
+    }
+}
@@ -0,0 +1,409 @@
+//! Measures of the rearrangements between whole genomes, the genes being
+//! written in the alphabet of their families.
+use anyhow::*;
+use colored::Colorize;
+use std::collections::{HashMap, HashSet};
+
+use crate::genebook::{FamilyID, Gene, GeneBook};
+use crate::{newick, Strand};
+
+/// How the families present in several copies in a genome are turned into
+/// markers appearing once in each genome
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Duplicates {
+    /// Only keep the first copy of each family along each genome
+    #[default]
+    Exemplar,
+    /// Pair the copies of each family in their order along both genomes,
+    /// the copies left unpaired being dropped
+    Matching,
+}
+
+/// A family and the rank of one of its copies, appearing once in a genome
+type Marker = (FamilyID, usize);
+
+/// The markers of `genes`, sorted by chromosome and position, as signed
+/// sequences, one per chromosome
+fn markers(genes: &[Gene], duplicates: Duplicates) -> Vec<Vec<(Marker, bool)>> {
+    let mut copies = HashMap::<FamilyID, usize>::new();
+    let mut chromosomes = Vec::<Vec<(Marker, bool)>>::new();
+    for (i, g) in genes.iter().enumerate() {
+        if i == 0 || genes[i - 1].chr != g.chr {
+            chromosomes.push(Vec::new());
+        }
+        let copy = copies.entry(g.family).or_default();
+        if duplicates == Duplicates::Matching || *copy == 0 {
+            if let Some(chromosome) = chromosomes.last_mut() {
+                chromosome.push(((g.family, *copy), g.strand != Strand::Reverse));
+            }
+        }
+        *copy += 1;
+    }
+    chromosomes
+}
+
+/// The number of adjacencies between the genes of `species_a` that are not
+/// found, in either orientation, between the genes of `species_b`, the
+/// families present in several copies being reduced to their first one
+pub fn breakpoint_distance(book: &GeneBook, species_a: &str, species_b: &str) -> Result<usize> {
+    breakpoint_distance_with(book, species_a, species_b, Duplicates::default())
+}
+
+/// As [`breakpoint_distance`], handling duplicated families following
+/// `duplicates`; only the markers present in both genomes are considered
+pub fn breakpoint_distance_with(
+    book: &GeneBook,
+    species_a: &str,
+    species_b: &str,
+    duplicates: Duplicates,
+) -> Result<usize> {
+    let a = markers(&book.genes_of_species(species_a)?, duplicates);
+    let b = markers(&book.genes_of_species(species_b)?, duplicates);
+
+    let in_b = b.iter().flatten().map(|m| m.0).collect::<HashSet<_>>();
+    let in_a = a.iter().flatten().map(|m| m.0).collect::<HashSet<_>>();
+    let common = |genome: Vec<Vec<(Marker, bool)>>, other: &HashSet<Marker>| {
+        genome
+            .into_iter()
+            .map(|chromosome| {
+                chromosome
+                    .into_iter()
+                    .filter(|m| other.contains(&m.0))
+                    .collect::<Vec<_>>()
+            })
+            .collect::<Vec<_>>()
+    };
+    let a = common(a, &in_b);
+    let b = common(b, &in_a);
+
+    let mut adjacencies = HashSet::new();
+    for pair in b.iter().flat_map(|chromosome| chromosome.windows(2)) {
+        let (x, y) = (pair[0], pair[1]);
+        adjacencies.insert((x, y));
+        adjacencies.insert(((y.0, !y.1), (x.0, !x.1)));
+    }
+    Ok(a.iter()
+        .flat_map(|chromosome| chromosome.windows(2))
+        .filter(|pair| !adjacencies.contains(&(pair[0], pair[1])))
+        .count())
+}
+
+/// The settings of [`dcj_distance_with`]
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct DcjOptions {
+    /// Only consider the families present in all the species of the book,
+    /// rather than in both compared species
+    pub universal: bool,
+}
+
+/// An end of a gene, its head if `true`
+type Extremity = (FamilyID, bool);
+
+/// The extremities of a gene of `family` on `strand`, as met along its
+/// chromosome
+fn ends(family: FamilyID, strand: Strand) -> (Extremity, Extremity) {
+    let forward = strand != Strand::Reverse;
+    ((family, !forward), (family, forward))
+}
+
+/// The extremities adjacent along `genes`, sorted by chromosome and
+/// position, chromosomes being considered linear
+fn adjacencies(genes: &[&Gene]) -> HashMap<Extremity, Extremity> {
+    let ends = |g: &Gene| ends(g.family, g.strand);
+    let mut r = HashMap::new();
+    for pair in genes.windows(2) {
+        if pair[0].chr == pair[1].chr {
+            let (x, y) = (ends(pair[0]).1, ends(pair[1]).0);
+            r.insert(x, y);
+            r.insert(y, x);
+        }
+    }
+    r
+}
+
+/// The double-cut-and-join distance between the genomes of `species_a` and
+/// `species_b`, i.e. the minimal number of DCJ operations turning one into
+/// the other, computed over the families present in a single copy in both
+pub fn dcj_distance(book: &GeneBook, species_a: &str, species_b: &str) -> Result<usize> {
+    dcj_distance_with(book, species_a, species_b, &DcjOptions::default())
+}
+
+/// As [`dcj_distance`], with the given options
+pub fn dcj_distance_with(
+    book: &GeneBook,
+    species_a: &str,
+    species_b: &str,
+    options: &DcjOptions,
+) -> Result<usize> {
+    let genes_a = book.genes_of_species(species_a)?;
+    let genes_b = book.genes_of_species(species_b)?;
+
+    let mut counts = HashMap::<FamilyID, (usize, usize)>::new();
+    for g in genes_a.iter() {
+        counts.entry(g.family).or_default().0 += 1;
+    }
+    for g in genes_b.iter() {
+        counts.entry(g.family).or_default().1 += 1;
+    }
+    let mut markers = counts
+        .into_iter()
+        .filter(|(_, n)| *n == (1, 1))
+        .map(|(family, _)| family)
+        .collect::<HashSet<_>>();
+    if options.universal {
+        for species in book.species() {
+            let present = book
+                .genes_of_species(&species)?
+                .into_iter()
+                .map(|g| g.family)
+                .collect::<HashSet<_>>();
+            markers.retain(|f| present.contains(f));
+        }
+    }
+
+    let keep = |genes: &[Gene]| {
+        adjacencies(
+            &genes
+                .iter()
+                .filter(|g| markers.contains(&g.family))
+                .collect::<Vec<_>>(),
+        )
+    };
+    let a = keep(&genes_a);
+    let b = keep(&genes_b);
+
+    // Paths start on the telomeres of either genome, and are odd if they
+    // end on a telomere of the other one; the extremities left over lie on
+    // cycles
+    let extremities = markers
+        .iter()
+        .flat_map(|&f| [(f, true), (f, false)])
+        .collect::<Vec<_>>();
+    let mut visited = HashSet::new();
+    let (mut cycles, mut odd_paths) = (0, 0);
+    for &x in extremities.iter() {
+        let in_a = a.contains_key(&x);
+        if visited.contains(&x) || (in_a && b.contains_key(&x)) {
+            continue;
+        }
+        if walk(&a, &b, &mut visited, x, in_a) == Some(in_a) {
+            odd_paths += 1;
+        }
+    }
+    for &x in extremities.iter() {
+        if !visited.contains(&x) {
+            walk(&a, &b, &mut visited, x, true);
+            cycles += 1;
+        }
+    }
+
+    Ok(markers.len() - (cycles + odd_paths / 2))
+}
+
+/// Follow the adjacencies of `a` and `b` in turn from `start`, beginning
+/// with `a` if `in_a`, and return whether the walk stopped on an extremity
+/// lacking an adjacency in `a`, or `None` if it came back to `start`
+fn walk(
+    a: &HashMap<Extremity, Extremity>,
+    b: &HashMap<Extremity, Extremity>,
+    visited: &mut HashSet<Extremity>,
+    start: Extremity,
+    mut in_a: bool,
+) -> Option<bool> {
+    let mut current = start;
+    visited.insert(start);
+    loop {
+        match (if in_a { a } else { b }).get(&current) {
+            None => return Some(in_a),
+            Some(&next) if next == start => return None,
+            Some(&next) => current = next,
+        }
+        visited.insert(current);
+        in_a = !in_a;
+    }
+}
+
+/// A contiguous ancestral region, i.e. a run of families whose order in an
+/// ancestor is supported by the adjacencies conserved in its descendants
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AncestralRegion {
+    /// The families of the region in their ancestral order, with their
+    /// orientation relative to it
+    pub genes: Vec<(FamilyID, Strand)>,
+    /// The number of species supporting each adjacency between consecutive
+    /// genes
+    pub support: Vec<usize>,
+}
+
+/// The node named `name` in `tree`
+fn find<'a>(tree: &'a newick::Node, name: &str) -> Option<&'a newick::Node> {
+    if tree.name.as_deref() == Some(name) {
+        return Some(tree);
+    }
+    tree.children.iter().find_map(|c| find(c, name))
+}
+
+/// Propose the contiguous ancestral regions of the genome of the ancestor
+/// `ancestor`, or of the root if `None`, of the rooted species tree `tree`
+/// given in Newick format, whose leaves are named after the species of
+/// `book`.
+///
+/// Only the families present in at most one copy per genome are considered.
+/// An adjacency between two families is deemed ancestral if it is found in
+/// the descendants of two children of the ancestor, or in the descendants of
+/// one of them and outside of the ancestor; conflicting adjacencies are
+/// settled in favor of the most widely supported ones. Families deemed
+/// ancestral, i.e. present in two such groups, but lacking any ancestral
+/// adjacency form regions of their own. Regions are sorted by decreasing
+/// length.
+pub fn ancestral_regions(
+    book: &GeneBook,
+    tree: &str,
+    ancestor: Option<&str>,
+) -> Result<Vec<AncestralRegion>> {
+    let tree = newick::parse(tree)?
+        .into_iter()
+        .next()
+        .ok_or_else(|| anyhow!("no tree found"))?;
+    let node = match ancestor {
+        Some(name) => find(&tree, name)
+            .ok_or_else(|| anyhow!("{} not found in the tree", name.yellow().bold()))?,
+        None => &tree,
+    };
+    let known = book.species().into_iter().collect::<HashSet<_>>();
+    if tree.leaves().iter().filter(|l| known.contains(**l)).count() < 3 {
+        bail!("at least three species of the tree must be present in the book");
+    }
+
+    // The species of each group: the descendants of each child of the
+    // ancestor, then the outgroups
+    let below = node.leaves().into_iter().collect::<HashSet<_>>();
+    let mut groups = node.children.iter().map(|c| c.leaves()).collect::<Vec<_>>();
+    groups.push(
+        tree.leaves()
+            .into_iter()
+            .filter(|l| !below.contains(l))
+            .collect(),
+    );
+    let mut genomes = Vec::new();
+    for (group, species) in groups.iter().enumerate() {
+        for species in species.iter().filter(|s| known.contains(**s)) {
+            genomes.push((group, book.genes_of_species(species)?));
+        }
+    }
+
+    // The groups in which each single-copy family is found
+    let mut copies = HashMap::<FamilyID, usize>::new();
+    let mut found = HashMap::<FamilyID, HashSet<usize>>::new();
+    for (group, genes) in genomes.iter() {
+        let mut counts = HashMap::<FamilyID, usize>::new();
+        for g in genes.iter() {
+            *counts.entry(g.family).or_default() += 1;
+        }
+        for (family, count) in counts {
+            let c = copies.entry(family).or_default();
+            *c = (*c).max(count);
+            found.entry(family).or_default().insert(*group);
+        }
+    }
+    let ancestral = found
+        .iter()
+        .filter(|(family, groups)| copies[family] == 1 && groups.len() >= 2)
+        .map(|(family, _)| *family)
+        .collect::<HashSet<_>>();
+
+    // The adjacencies between ancestral families, with the groups and the
+    // number of species they are found in
+    let mut candidates = HashMap::<(Extremity, Extremity), (HashSet<usize>, usize)>::new();
+    for (group, genes) in genomes.iter() {
+        let genes = genes
+            .iter()
+            .filter(|g| ancestral.contains(&g.family))
+            .collect::<Vec<_>>();
+        for (x, y) in adjacencies(&genes) {
+            if x < y {
+                let (groups, species) = candidates.entry((x, y)).or_default();
+                groups.insert(*group);
+                *species += 1;
+            }
+        }
+    }
+    let mut candidates = candidates
+        .into_iter()
+        .filter(|(_, (groups, _))| groups.len() >= 2)
+        .map(|(adjacency, (_, species))| (adjacency, species))
+        .collect::<Vec<_>>();
+    candidates.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+    // Keep the best supported adjacencies such that each extremity has a
+    // single neighbor and that regions stay linear
+    let mut kept = HashMap::<Extremity, (Extremity, usize)>::new();
+    let mut region = ancestral
+        .iter()
+        .map(|f| (*f, *f))
+        .collect::<HashMap<FamilyID, FamilyID>>();
+    fn root(region: &mut HashMap<FamilyID, FamilyID>, mut f: FamilyID) -> FamilyID {
+        while region[&f] != f {
+            let up = region[&region[&f]];
+            region.insert(f, up);
+            f = up;
+        }
+        f
+    }
+    for ((x, y), support) in candidates {
+        if kept.contains_key(&x) || kept.contains_key(&y) {
+            continue;
+        }
+        let (rx, ry) = (root(&mut region, x.0), root(&mut region, y.0));
+        if rx == ry {
+            continue;
+        }
+        region.insert(rx, ry);
+        kept.insert(x, (y, support));
+        kept.insert(y, (x, support));
+    }
+
+    // Read the regions from the families with a free extremity
+    let mut families = ancestral.into_iter().collect::<Vec<_>>();
+    families.sort();
+    let mut visited = HashSet::new();
+    let mut regions = Vec::new();
+    for family in families {
+        let free = [(family, false), (family, true)]
+            .into_iter()
+            .find(|x| !kept.contains_key(x));
+        let Some(mut entry) = free.filter(|_| !visited.contains(&family)) else {
+            continue;
+        };
+        let mut r = AncestralRegion {
+            genes: Vec::new(),
+            support: Vec::new(),
+        };
+        loop {
+            visited.insert(entry.0);
+            // Entering a family by its tail means reading it forward
+            let strand = if entry.1 {
+                Strand::Reverse
+            } else {
+                Strand::Direct
+            };
+            r.genes.push((entry.0, strand));
+            let exit = ends(entry.0, strand).1;
+            match kept.get(&exit) {
+                Some(&(next, support)) => {
+                    r.support.push(support);
+                    entry = next;
+                }
+                None => break,
+            }
+        }
+        regions.push(r);
+    }
+    regions.sort_by(|a, b| {
+        b.genes
+            .len()
+            .cmp(&a.genes.len())
+            .then(a.genes[0].0.cmp(&b.genes[0].0))
+    });
+    Ok(regions)
+}
@@ -1,8 +1,8 @@
 #![allow(dead_code)]
-use std::io::{BufRead, BufReader, Lines, Read};
+use std::io::{BufRead, BufReader, Lines, Read, Write};
 use thiserror::Error;
 
-use crate::Strand;
+use crate::{output::RecordSink, Coordinates, Record, Strand};
 
 #[derive(Debug, Error)]
 pub enum BedError {
@@ -86,3 +86,34 @@ impl<T: Read> Iterator for BedReader<T> {
             .map(|l| make_record(&l))
     }
 }
+
+/// Writes records as BED6 lines
+pub struct BedWriter<W> {
+    out: W,
+}
+impl<W: Write> BedWriter<W> {
+    pub fn new(out: W) -> BedWriter<W> {
+        BedWriter { out }
+    }
+}
+impl<W: Write> RecordSink for BedWriter<W> {
+    fn write_record(&mut self, record: &Record) -> anyhow::Result<()> {
+        let (start, end) = record.interval().bounds(Coordinates::ZeroBased);
+        let score = match record {
+            Record::Gff(r) => r.score(),
+            Record::Bed(r) => r.score,
+            _ => None,
+        };
+        writeln!(
+            self.out,
+            "{}\t{}\t{}\t{}\t{}\t{}",
+            record.chr(),
+            start,
+            end,
+            record.id().unwrap_or("."),
+            score.unwrap_or_default(),
+            char::from(record.strand())
+        )?;
+        Ok(())
+    }
+}
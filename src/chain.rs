@@ -0,0 +1,323 @@
+//! A parser for the UCSC chain format used by liftOver, and a simple
+//! coordinate lifting primitive built on it:
+//! ```text
+//! chain score tName tSize tStrand tStart tEnd qName qSize qStrand qStart qEnd id
+//! size dt dq
+//! ...
+//! size
+//! ```
+//! All coordinates are 0-based, as in the chain files themselves.
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader, Lines, Read};
+use std::ops::Range;
+
+use thiserror::Error;
+
+use crate::Strand;
+
+#[derive(Debug, Error)]
+pub enum ChainError {
+    #[error("chain header with missing fields: {0}")]
+    HeaderTooShort(String),
+
+    #[error("invalid chain header: {0}")]
+    InvalidHeader(String),
+
+    #[error("invalid alignment data line: {0}")]
+    InvalidBlock(String),
+
+    #[error("alignment data outside of a chain: {0}")]
+    OrphanBlock(String),
+
+    #[error("aligned region past the end of its sequence: {0}")]
+    OutOfBounds(String),
+}
+
+/// An ungapped aligned block, in forward coordinates on the target and in
+/// `q_strand` coordinates on the query.
+#[derive(Debug, Clone, Copy)]
+pub struct ChainBlock {
+    pub t_start: usize,
+    pub q_start: usize,
+    pub size: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Chain {
+    pub score: f64,
+    pub t_name: String,
+    pub t_size: usize,
+    pub t_strand: Strand,
+    pub t_start: usize,
+    pub t_end: usize,
+    pub q_name: String,
+    pub q_size: usize,
+    pub q_strand: Strand,
+    pub q_start: usize,
+    pub q_end: usize,
+    pub id: Option<String>,
+    pub blocks: Vec<ChainBlock>,
+}
+impl Chain {
+    /// Map a 0-based position on the target sequence to the query, if it
+    /// falls within one of the aligned blocks of this chain.
+    pub fn lift(&self, pos: usize) -> Option<usize> {
+        if pos < self.t_start || pos >= self.t_end {
+            return None;
+        }
+        let i = self.blocks.partition_point(|b| b.t_start <= pos);
+        let block = self.blocks.get(i.checked_sub(1)?)?;
+        if pos >= block.t_start + block.size {
+            return None;
+        }
+        let q = block.q_start + (pos - block.t_start);
+        Some(match self.q_strand {
+            Strand::Reverse => self.q_size - 1 - q,
+            _ => q,
+        })
+    }
+
+    /// Map the 0-based, half-open `range` on the target sequence to the
+    /// smallest range of the query covering the parts of it falling within
+    /// the aligned blocks of this chain, if any.
+    pub fn lift_range(&self, range: Range<usize>) -> Option<Range<usize>> {
+        let first = self
+            .blocks
+            .partition_point(|b| b.t_start + b.size <= range.start);
+        let (start, end) = self.blocks[first..]
+            .iter()
+            .take_while(|b| b.t_start < range.end)
+            .map(|b| {
+                let from = range.start.max(b.t_start) - b.t_start + b.q_start;
+                let to = range.end.min(b.t_start + b.size) - b.t_start + b.q_start;
+                match self.q_strand {
+                    Strand::Reverse => (self.q_size - to, self.q_size - from),
+                    _ => (from, to),
+                }
+            })
+            .reduce(|(s1, e1), (s2, e2)| (s1.min(s2), e1.max(e2)))?;
+        Some(start..end)
+    }
+}
+
+pub struct ChainReader<T> {
+    buffer_lines: Lines<BufReader<T>>,
+    pending: Option<String>,
+}
+impl<T: Read> ChainReader<T> {
+    pub fn new(file: T) -> ChainReader<T> {
+        ChainReader {
+            buffer_lines: BufReader::new(file).lines(),
+            pending: None,
+        }
+    }
+}
+impl<T: Read> Iterator for ChainReader<T> {
+    type Item = Result<Chain, ChainError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        fn parse_header(line: &str) -> Result<Chain, ChainError> {
+            let s = line.split_whitespace().collect::<Vec<_>>();
+            if s.len() < 12 {
+                return Err(ChainError::HeaderTooShort(line.to_owned()));
+            }
+            let invalid = || ChainError::InvalidHeader(line.to_owned());
+            let number = |x: &str| x.parse::<usize>().map_err(|_| invalid());
+
+            let chain = Chain {
+                score: s[1].parse().map_err(|_| invalid())?,
+                t_name: s[2].to_owned(),
+                t_size: number(s[3])?,
+                t_strand: s[4].try_into().map_err(|_| invalid())?,
+                t_start: number(s[5])?,
+                t_end: number(s[6])?,
+                q_name: s[7].to_owned(),
+                q_size: number(s[8])?,
+                q_strand: s[9].try_into().map_err(|_| invalid())?,
+                q_start: number(s[10])?,
+                q_end: number(s[11])?,
+                id: s.get(12).map(|x| x.to_string()),
+                blocks: Vec::new(),
+            };
+            if chain.t_start > chain.t_end
+                || chain.t_end > chain.t_size
+                || chain.q_start > chain.q_end
+                || chain.q_end > chain.q_size
+            {
+                return Err(ChainError::OutOfBounds(line.to_owned()));
+            }
+            Ok(chain)
+        }
+
+        let header = self.pending.take().or_else(|| {
+            self.buffer_lines
+                .by_ref()
+                .map(|l| l.unwrap())
+                .find(|line| !line.starts_with('#') && !line.trim().is_empty())
+        })?;
+        if !header.starts_with("chain") {
+            return Some(Err(ChainError::OrphanBlock(header)));
+        }
+        let mut chain = match parse_header(&header) {
+            Ok(chain) => chain,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let (mut t, mut q) = (chain.t_start, chain.q_start);
+        for line in self.buffer_lines.by_ref().map(|l| l.unwrap()) {
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.trim().is_empty() {
+                break;
+            }
+            if line.starts_with("chain") {
+                self.pending = Some(line);
+                break;
+            }
+
+            let s = line
+                .split_whitespace()
+                .map(|x| x.parse::<usize>())
+                .collect::<Result<Vec<_>, _>>();
+            let (size, dt, dq) = match s.as_deref() {
+                Ok([size]) => (*size, 0, 0),
+                Ok([size, dt, dq]) => (*size, *dt, *dq),
+                _ => return Some(Err(ChainError::InvalidBlock(line))),
+            };
+            // Blocks must lie within the chain, hence within both sequences
+            if t.checked_add(size).is_none_or(|end| end > chain.t_end)
+                || q.checked_add(size).is_none_or(|end| end > chain.q_end)
+            {
+                return Some(Err(ChainError::OutOfBounds(line)));
+            }
+            chain.blocks.push(ChainBlock {
+                t_start: t,
+                q_start: q,
+                size,
+            });
+            t = t.saturating_add(size).saturating_add(dt);
+            q = q.saturating_add(size).saturating_add(dq);
+        }
+
+        Some(Ok(chain))
+    }
+}
+
+/// A set of chains indexed by target sequence, used to lift coordinates
+/// from the target assembly to the query assembly.
+#[derive(Debug, Default)]
+pub struct Liftover {
+    // Chains are sorted by decreasing score, so that the best one wins
+    chains: HashMap<String, Vec<Chain>>,
+}
+impl Liftover {
+    pub fn new<I: IntoIterator<Item = Chain>>(chains: I) -> Liftover {
+        let mut r = Liftover::default();
+        for chain in chains {
+            r.chains
+                .entry(chain.t_name.clone())
+                .or_default()
+                .push(chain);
+        }
+        for chains in r.chains.values_mut() {
+            chains.sort_by(|a, b| b.score.total_cmp(&a.score));
+        }
+        r
+    }
+
+    pub fn from_reader<T: Read>(file: T) -> Result<Liftover, ChainError> {
+        Ok(Liftover::new(
+            ChainReader::new(file).collect::<Result<Vec<_>, _>>()?,
+        ))
+    }
+
+    /// Map a 0-based position on `chr` in the target assembly to the query
+    /// assembly, using the best-scoring chain covering it.
+    pub fn lift(&self, chr: &str, pos: usize) -> Option<(&str, usize)> {
+        self.chains
+            .get(chr)?
+            .iter()
+            .find_map(|c| c.lift(pos).map(|p| (c.q_name.as_str(), p)))
+    }
+
+    /// Map a 0-based, half-open range on `chr` in the target assembly to the
+    /// query assembly, using the best-scoring chain covering part of it.
+    pub fn lift_range(&self, chr: &str, range: Range<usize>) -> Option<(&str, Range<usize>)> {
+        self.chains
+            .get(chr)?
+            .iter()
+            .find_map(|c| c.lift_range(range.clone()).map(|r| (c.q_name.as_str(), r)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CHAINS: &str = "chain 1000 chr1 1000 + 100 300 chrA 500 + 0 190 1\n\
+                          50 50 40\n\
+                          100\n\
+                          \n\
+                          chain 500 chr1 1000 + 100 200 chrB 400 - 50 150 2\n\
+                          100\n";
+
+    fn chains() -> Vec<Chain> {
+        ChainReader::new(CHAINS.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap()
+    }
+
+    #[test]
+    fn blocks() {
+        let chains = chains();
+        assert_eq!(chains.len(), 2);
+        let blocks = chains[0]
+            .blocks
+            .iter()
+            .map(|b| (b.t_start, b.q_start, b.size))
+            .collect::<Vec<_>>();
+        assert_eq!(blocks, [(100, 0, 50), (200, 90, 100)]);
+        assert_eq!(chains[1].q_strand, Strand::Reverse);
+    }
+
+    #[test]
+    fn lifts() {
+        let chains = chains();
+        assert_eq!(chains[0].lift(110), Some(10));
+        assert_eq!(chains[0].lift(160), None);
+        assert_eq!(chains[0].lift(250), Some(140));
+        assert_eq!(chains[0].lift(300), None);
+        assert_eq!(chains[0].lift_range(140..210), Some(40..100));
+        // On the reverse strand of a 400-long query
+        assert_eq!(chains[1].lift(100), Some(349));
+        assert_eq!(chains[1].lift_range(100..110), Some(340..350));
+
+        let liftover = Liftover::new(chains);
+        assert_eq!(liftover.lift("chr1", 110), Some(("chrA", 10)));
+        // Falling in a gap of the best chain, but not of the other one
+        assert_eq!(liftover.lift("chr1", 160), Some(("chrB", 289)));
+        assert_eq!(liftover.lift("chr2", 110), None);
+    }
+
+    #[test]
+    fn invalid_chains() {
+        for chain in [
+            "chain 1 chr1 1000 + 100 300\n",
+            "chain x chr1 1000 + 100 300 chrA 500 + 0 200\n",
+            "50 10 10\n",
+            "chain 1 chr1 1000 + 100 300 chrA 500 + 0 200\n50 x 10\n",
+            // Past the end of the sequences or of the chain
+            "chain 1 chr1 100 + 0 300 chrA 500 + 0 300\n300\n",
+            "chain 1 chr1 1000 + 0 300 chrA 200 - 0 300\n300\n",
+            "chain 1 chr1 1000 + 0 100 chrA 500 + 0 100\n50 10 10\n50\n",
+        ] {
+            assert!(
+                ChainReader::new(chain.as_bytes()).any(|c| c.is_err()),
+                "{}",
+                chain
+            );
+        }
+    }
+}
@@ -1,13 +1,13 @@
 //! A parser for the ChromTable format:
-//! ```
+//! ```text
 //! Chrom [TAB] Start [TAB] Stop [TAB] Strand [TAB] geneid
 //! ```
 
-use std::io::{BufRead, BufReader, Lines, Read};
+use std::io::{BufRead, BufReader, Lines, Read, Write};
 
 use thiserror::Error;
 
-use crate::Strand;
+use crate::{output::RecordSink, Coordinates, Record, Strand};
 
 #[derive(Debug, Error)]
 pub enum ChromError {
@@ -99,3 +99,28 @@ impl<T: Read> Iterator for ChromReader<T> {
             .map(|l| make_record(&l))
     }
 }
+
+/// Writes records as ChromTable lines
+pub struct ChromWriter<W> {
+    out: W,
+}
+impl<W: Write> ChromWriter<W> {
+    pub fn new(out: W) -> ChromWriter<W> {
+        ChromWriter { out }
+    }
+}
+impl<W: Write> RecordSink for ChromWriter<W> {
+    fn write_record(&mut self, record: &Record) -> anyhow::Result<()> {
+        let (start, end) = record.interval().bounds(Coordinates::OneBased);
+        writeln!(
+            self.out,
+            "{}\t{}\t{}\t{}\t{}",
+            record.chr(),
+            start,
+            end,
+            char::from(record.strand()),
+            record.id().unwrap_or(".")
+        )?;
+        Ok(())
+    }
+}
@@ -0,0 +1,108 @@
+//! Conversion of a database to DuckDB, whose columnar storage speeds up the
+//! whole-table scans of large databases. The DuckDB file follows the flat
+//! layout, whatever the layout of the original database.
+use anyhow::*;
+use colored::Colorize;
+use duckdb::types::Value;
+use log::*;
+use rusqlite::types::ValueRef;
+
+use super::{meta, migrate, update::connect};
+
+/// The tables copied to DuckDB, with the DuckDB types of their columns
+const TABLES: &[(&str, &[(&str, &str)])] = &[
+    (
+        "genomes",
+        &[
+            ("species", "VARCHAR"),
+            ("chr", "VARCHAR"),
+            ("ancestral_id", "BIGINT"),
+            ("id", "VARCHAR"),
+            ("start", "BIGINT"),
+            ("stop", "BIGINT"),
+            ("direction", "VARCHAR"),
+            ("left_tail_ids", "VARCHAR"),
+            ("right_tail_ids", "VARCHAR"),
+        ],
+    ),
+    ("families", &[("id", "BIGINT"), ("name", "VARCHAR")]),
+    (
+        "chr_aliases",
+        &[
+            ("species", "VARCHAR"),
+            ("alias", "VARCHAR"),
+            ("chr", "VARCHAR"),
+        ],
+    ),
+    ("meta", &[("key", "VARCHAR"), ("value", "VARCHAR")]),
+];
+
+fn value(v: ValueRef) -> Value {
+    match v {
+        ValueRef::Null => Value::Null,
+        ValueRef::Integer(i) => Value::BigInt(i),
+        ValueRef::Real(f) => Value::Double(f),
+        ValueRef::Text(t) => Value::Text(String::from_utf8_lossy(t).into_owned()),
+        ValueRef::Blob(b) => Value::Blob(b.to_vec()),
+    }
+}
+
+/// Copy the database `db_file` to the DuckDB file `target`, replacing the
+/// tables it may already contain
+pub fn to_duckdb(db_file: &str, target: &str) -> Result<()> {
+    let conn = connect(db_file)?;
+    let attributes = meta::get_list(&conn, meta::ATTRIBUTES)?;
+    let duck = duckdb::Connection::open(target)
+        .with_context(|| anyhow!("while opening {}", target.yellow().bold()))?;
+
+    for (table, columns) in TABLES.iter() {
+        let mut columns = columns
+            .iter()
+            .map(|(name, kind)| (format!("\"{name}\""), *kind))
+            .collect::<Vec<_>>();
+        if *table == "genomes" {
+            columns.extend(attributes.iter().map(|a| (format!("\"{a}\""), "VARCHAR")));
+        }
+        duck.execute_batch(&format!(
+            "DROP TABLE IF EXISTS {table}; CREATE TABLE {table} ({});",
+            columns
+                .iter()
+                .map(|(name, kind)| format!("{name} {kind}"))
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))
+        .with_context(|| anyhow!("while creating {table}"))?;
+
+        let present = conn
+            .prepare("SELECT 1 FROM sqlite_master WHERE type IN ('table', 'view') AND name=?")?
+            .exists([table])?;
+        if !present {
+            debug!("{} has no {} table", db_file, table);
+            continue;
+        }
+
+        trace!("Copying {}", table.bold());
+        let names = columns
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>();
+        let source = if *table == "chr_aliases" {
+            migrate::aliases_source(&conn)?
+        } else {
+            table
+        };
+        let mut query = conn.prepare(&format!("SELECT {} FROM {source}", names.join(", ")))?;
+        let mut rows = query.query([])?;
+        let mut appender = duck.appender(table)?;
+        while let Some(row) = rows.next()? {
+            let values = (0..names.len())
+                .map(|i| row.get_ref(i).map(value))
+                .collect::<Result<Vec<_>, _>>()?;
+            appender.append_row(duckdb::appender_params_from_iter(values))?;
+        }
+        appender.flush()?;
+    }
+    info!("{} copied to {}", db_file.bold(), target.bold());
+
+    Ok(())
+}
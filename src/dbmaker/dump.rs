@@ -0,0 +1,272 @@
+//! Plain-text dumps of the genomes table, for inspection without SQLite, and
+//! Parquet ones for dataframe libraries.
+use anyhow::*;
+use rusqlite::{types::Type, Connection};
+use serde_json::json;
+use std::io::Write;
+
+use super::{meta, update::connect};
+use crate::genebook::{GeneBook, TailGene};
+
+/// The formats in which a database can be dumped
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DumpFormat {
+    /// Tab-separated values with a header line; landscapes are split into
+    /// comma-separated family IDs and a string of strands
+    Tsv,
+    /// One JSON object per gene and per line, landscapes being arrays of
+    /// `{"family": ..., "strand": ...}` objects, along with their `id` and
+    /// `start` for databases with rich landscapes, and their `copies` for
+    /// collapsed tandem arrays
+    JsonLines,
+}
+
+struct Row {
+    species: String,
+    chr: String,
+    id: String,
+    family: usize,
+    start: usize,
+    stop: usize,
+    strand: String,
+    left: Vec<TailGene>,
+    right: Vec<TailGene>,
+    attributes: Vec<Option<String>>,
+}
+
+/// Call `f` on each gene of the genomes table in turn, ordered by species,
+/// chromosome and position, without loading them all in memory
+fn for_each_row<F: FnMut(Row) -> Result<()>>(
+    conn: &Connection,
+    attributes: &[String],
+    mut f: F,
+) -> Result<()> {
+    let mut query = conn.prepare(&format!(
+        "SELECT species, chr, id, ancestral_id, start, stop, direction, left_tail_ids, right_tail_ids{} FROM genomes ORDER BY species, chr, start",
+        attributes
+            .iter()
+            .map(|a| format!(", \"{a}\""))
+            .collect::<String>()
+    ))?;
+    let rows = query.query_map([], |r| {
+        std::result::Result::Ok(Row {
+            species: r.get(0)?,
+            chr: r.get(1)?,
+            id: r.get(2)?,
+            family: r.get(3)?,
+            start: r.get(4)?,
+            stop: r.get(5)?,
+            strand: r.get(6)?,
+            left: GeneBook::parse_landscape(&r.get::<_, String>(7)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(7, Type::Text, Box::new(e))
+            })?,
+            right: GeneBook::parse_landscape(&r.get::<_, String>(8)?).map_err(|e| {
+                rusqlite::Error::FromSqlConversionFailure(8, Type::Text, Box::new(e))
+            })?,
+            attributes: (0..attributes.len())
+                .map(|i| r.get(9 + i))
+                .collect::<Result<_, _>>()?,
+        })
+    })?;
+    for row in rows {
+        f(row?)?;
+    }
+    Ok(())
+}
+
+/// Write the content of the genomes table of `db_file` to `out`
+pub fn export<W: Write>(db_file: &str, format: DumpFormat, mut out: W) -> Result<()> {
+    let conn = connect(db_file)?;
+    let attributes = meta::get_list(&conn, meta::ATTRIBUTES)?;
+
+    match format {
+        DumpFormat::Tsv => {
+            let families = |l: &[TailGene]| {
+                l.iter()
+                    .map(|g| g.family.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            };
+            let strands =
+                |l: &[TailGene]| l.iter().map(|g| g.strand.to_string()).collect::<String>();
+            write!(
+                out,
+                "species\tchr\tid\tfamily\tstart\tstop\tstrand\tleft_families\tleft_strands\tright_families\tright_strands"
+            )?;
+            for a in attributes.iter() {
+                write!(out, "\t{}", a)?;
+            }
+            writeln!(out)?;
+            for_each_row(&conn, &attributes, |r| {
+                write!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    r.species,
+                    r.chr,
+                    r.id,
+                    r.family,
+                    r.start,
+                    r.stop,
+                    r.strand,
+                    families(&r.left),
+                    strands(&r.left),
+                    families(&r.right),
+                    strands(&r.right)
+                )?;
+                for a in r.attributes.iter() {
+                    write!(out, "\t{}", a.as_deref().unwrap_or_default())?;
+                }
+                writeln!(out)?;
+                Ok(())
+            })?;
+        }
+        DumpFormat::JsonLines => {
+            let landscape = |l: &[TailGene]| {
+                l.iter()
+                    .map(|g| {
+                        let mut tail = json!({"family": g.family, "strand": g.strand.to_string()});
+                        if let (Some(id), Some(pos)) = (g.id.as_ref(), g.pos) {
+                            tail["id"] = json!(id);
+                            tail["start"] = json!(pos);
+                        }
+                        if g.copies > 1 {
+                            tail["copies"] = json!(g.copies);
+                        }
+                        tail
+                    })
+                    .collect::<Vec<_>>()
+            };
+            for_each_row(&conn, &attributes, |r| {
+                let mut gene = json!({
+                    "species": r.species,
+                    "chr": r.chr,
+                    "id": r.id,
+                    "family": r.family,
+                    "start": r.start,
+                    "stop": r.stop,
+                    "strand": r.strand,
+                    "left_landscape": landscape(&r.left),
+                    "right_landscape": landscape(&r.right),
+                });
+                for (a, value) in attributes.iter().zip(r.attributes.iter()) {
+                    gene[a] = json!(value);
+                }
+                writeln!(out, "{}", gene)?;
+                Ok(())
+            })?;
+        }
+    }
+
+    Ok(())
+}
+
+/// How many genes are written at once to Parquet files
+#[cfg(feature = "parquet")]
+const PARQUET_BATCH: usize = 65_536;
+
+/// Convert `rows`, bearing `attributes` extra columns, into a record batch of `schema`
+#[cfg(feature = "parquet")]
+fn parquet_batch(
+    schema: &std::sync::Arc<arrow::datatypes::Schema>,
+    rows: &[Row],
+    attributes: usize,
+) -> Result<arrow::record_batch::RecordBatch> {
+    use arrow::array::{
+        ArrayRef, ListBuilder, StringArray, StringBuilder, UInt64Array, UInt64Builder,
+    };
+    use arrow::record_batch::RecordBatch;
+    use std::sync::Arc;
+
+    let strings = |f: &dyn Fn(&Row) -> Option<&str>| -> ArrayRef {
+        Arc::new(rows.iter().map(f).collect::<StringArray>())
+    };
+    let integers = |f: &dyn Fn(&Row) -> usize| -> ArrayRef {
+        Arc::new(rows.iter().map(|r| f(r) as u64).collect::<UInt64Array>())
+    };
+    let families = |f: &dyn Fn(&Row) -> &[TailGene]| -> ArrayRef {
+        let mut b = ListBuilder::new(UInt64Builder::new());
+        for r in rows.iter() {
+            b.values()
+                .append_slice(&f(r).iter().map(|g| g.family as u64).collect::<Vec<_>>());
+            b.append(true);
+        }
+        Arc::new(b.finish())
+    };
+    let strands = |f: &dyn Fn(&Row) -> &[TailGene]| -> ArrayRef {
+        let mut b = ListBuilder::new(StringBuilder::new());
+        for r in rows.iter() {
+            for g in f(r).iter() {
+                b.values().append_value(g.strand.to_string());
+            }
+            b.append(true);
+        }
+        Arc::new(b.finish())
+    };
+
+    let mut columns = vec![
+        strings(&|r| Some(&r.species)),
+        strings(&|r| Some(&r.chr)),
+        strings(&|r| Some(&r.id)),
+        integers(&|r| r.family),
+        integers(&|r| r.start),
+        integers(&|r| r.stop),
+        strings(&|r| Some(&r.strand)),
+        families(&|r| &r.left),
+        strands(&|r| &r.left),
+        families(&|r| &r.right),
+        strands(&|r| &r.right),
+    ];
+    for i in 0..attributes {
+        columns.push(strings(&|r| r.attributes[i].as_deref()));
+    }
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+/// Write the content of the genomes table of `db_file` as a Parquet file,
+/// landscapes being split into lists of family IDs and of strands; genes are
+/// written by batches, so that the table never has to fit in memory
+#[cfg(feature = "parquet")]
+pub fn export_parquet(db_file: &str, filename: &str) -> Result<()> {
+    use arrow::datatypes::{DataType, Field, Schema};
+    use std::sync::Arc;
+
+    let conn = connect(db_file)?;
+    let attributes = meta::get_list(&conn, meta::ATTRIBUTES)?;
+
+    let list = |t: DataType| DataType::List(Arc::new(Field::new("item", t, true)));
+    let mut fields = vec![
+        Field::new("species", DataType::Utf8, false),
+        Field::new("chr", DataType::Utf8, false),
+        Field::new("id", DataType::Utf8, false),
+        Field::new("family", DataType::UInt64, false),
+        Field::new("start", DataType::UInt64, false),
+        Field::new("stop", DataType::UInt64, false),
+        Field::new("strand", DataType::Utf8, false),
+        Field::new("left_families", list(DataType::UInt64), false),
+        Field::new("left_strands", list(DataType::Utf8), false),
+        Field::new("right_families", list(DataType::UInt64), false),
+        Field::new("right_strands", list(DataType::Utf8), false),
+    ];
+    for a in attributes.iter() {
+        fields.push(Field::new(a, DataType::Utf8, true));
+    }
+    let schema = Arc::new(Schema::new(fields));
+
+    let out =
+        std::fs::File::create(filename).with_context(|| anyhow!("while creating {}", filename))?;
+    let mut writer = parquet::arrow::ArrowWriter::try_new(out, schema.clone(), None)?;
+    let mut rows = Vec::with_capacity(PARQUET_BATCH);
+    for_each_row(&conn, &attributes, |r| {
+        rows.push(r);
+        if rows.len() == PARQUET_BATCH {
+            writer.write(&parquet_batch(&schema, &rows, attributes.len())?)?;
+            rows.clear();
+        }
+        Ok(())
+    })?;
+    if !rows.is_empty() {
+        writer.write(&parquet_batch(&schema, &rows, attributes.len())?)?;
+    }
+    writer.close()?;
+    Ok(())
+}
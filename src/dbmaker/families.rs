@@ -0,0 +1,497 @@
+use anyhow::*;
+use colored::Colorize;
+use log::*;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::File,
+    io::{BufRead, BufReader},
+};
+
+use crate::errors::FileError;
+use crate::genebook::FamilyID;
+use crate::newick;
+
+/// The formats in which gene families may be provided to the database builder
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub enum FamilyFormat {
+    /// One family per file, made of all the whitespace-separated IDs it contains
+    #[default]
+    Plain,
+    /// OrthoFinder `Orthogroups.tsv` or `Phylogenetic_Hierarchical_Orthogroups/N*.tsv`,
+    /// one family per line named after its orthogroup
+    OrthoFinder,
+    /// MCL clustering output (e.g. `out.mcl`), one tab-separated family per line
+    Mcl,
+    /// eggNOG-mapper `*.emapper.annotations`, grouping genes by their assigned
+    /// OG at the given taxonomic level, either as a taxon ID (`2759`) or name
+    /// (`Eukaryota`); the genes assigned to the same OG in different files,
+    /// e.g. one per species, make up a single family
+    EggNog { level: String },
+    /// Newick or NHX gene trees, one family per tree; if `split_duplications`
+    /// is set, trees rooted in NHX duplication nodes (`D=Y`) are split into
+    /// one family per duplicated subtree
+    Trees { split_duplications: bool },
+}
+
+/// How family IDs are assigned
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FamilyIds {
+    /// Families are numbered from 1 in the order they are read
+    #[default]
+    Sequential,
+    /// The ID of a family is derived from a hash of its sorted members, so
+    /// that the same families get the same IDs across builds and databases
+    ContentHash,
+}
+impl std::fmt::Display for FamilyIds {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FamilyIds::Sequential => write!(f, "sequential"),
+            FamilyIds::ContentHash => write!(f, "content-hash"),
+        }
+    }
+}
+impl TryFrom<&str> for FamilyIds {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "sequential" => Ok(FamilyIds::Sequential),
+            "content-hash" => Ok(FamilyIds::ContentHash),
+            _ => bail!("unknown family ID scheme: {}", s),
+        }
+    }
+}
+
+/// The content-derived ID of a family, i.e. the first 63 bits of the
+/// SHA-256 of its newline-joined sorted members, so that it fits in an
+/// SQLite integer
+pub(crate) fn content_hash<S: AsRef<str>>(members: &[S]) -> FamilyID {
+    let mut members = members.iter().map(|m| m.as_ref()).collect::<Vec<_>>();
+    members.sort();
+    let digest = Sha256::digest(members.join("\n").as_bytes());
+    let mut bytes = [0; 8];
+    bytes.copy_from_slice(&digest[..8]);
+    (u64::from_be_bytes(bytes) >> 1) as FamilyID
+}
+
+/// The mapping from gene IDs to the families they belong to, built from one
+/// or more family files.
+pub(crate) struct Families {
+    current: FamilyID,
+    count: usize,
+    min_size: usize,
+    pub(crate) id2family: HashMap<String, FamilyID>,
+    pub(crate) names: HashMap<FamilyID, String>,
+    /// The families registered with [`Families::extend`], by name
+    by_name: HashMap<String, FamilyID>,
+    /// The members of the families registered with [`Families::extend`]
+    /// that are still smaller than the minimal size
+    undersized: HashMap<String, Vec<String>>,
+}
+impl Families {
+    pub(crate) fn new(min_size: usize) -> Self {
+        Families {
+            current: 1,
+            count: 0,
+            min_size,
+            id2family: HashMap::new(),
+            names: HashMap::new(),
+            by_name: HashMap::new(),
+            undersized: HashMap::new(),
+        }
+    }
+
+    /// Register a new family made of the given members, and return its ID;
+    /// families smaller than the minimal size are dropped.
+    pub(crate) fn add<S: AsRef<str>>(
+        &mut self,
+        name: Option<&str>,
+        members: impl IntoIterator<Item = S>,
+    ) -> Option<FamilyID> {
+        let members = members.into_iter().collect::<Vec<_>>();
+        if members.len() < self.min_size {
+            trace!("Dropping family {:?} of size {}", name, members.len());
+            return None;
+        }
+
+        let id = self.current;
+        for member in members {
+            self.id2family.insert(member.as_ref().to_owned(), id);
+        }
+        if let Some(name) = name {
+            self.names.insert(id, name.to_owned());
+        }
+        self.current += 1;
+        self.count += 1;
+        Some(id)
+    }
+
+    /// Add `members` to the family called `name`, registering it if this
+    /// name was never seen, e.g. for families spread over several files;
+    /// families stay dropped as long as they are smaller than the minimal
+    /// size.
+    pub(crate) fn extend<S: AsRef<str>>(
+        &mut self,
+        name: &str,
+        members: impl IntoIterator<Item = S>,
+    ) -> Option<FamilyID> {
+        if let Some(&id) = self.by_name.get(name) {
+            for member in members {
+                self.id2family.insert(member.as_ref().to_owned(), id);
+            }
+            return Some(id);
+        }
+
+        let mut pending = self.undersized.remove(name).unwrap_or_default();
+        pending.extend(members.into_iter().map(|m| m.as_ref().to_owned()));
+        if pending.len() < self.min_size {
+            self.undersized.insert(name.to_owned(), pending);
+            return None;
+        }
+        let id = self.add(Some(name), pending)?;
+        self.by_name.insert(name.to_owned(), id);
+        Some(id)
+    }
+
+    pub(crate) fn get(&self, id: &str) -> Option<&FamilyID> {
+        self.id2family.get(id)
+    }
+
+    pub(crate) fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Iterate over the families, as (family ID, members) pairs
+    pub(crate) fn families(&self) -> HashMap<FamilyID, Vec<&str>> {
+        let mut r = HashMap::<FamilyID, Vec<&str>>::new();
+        for (member, family) in self.id2family.iter() {
+            r.entry(*family).or_default().push(member);
+        }
+        r
+    }
+
+    /// Renumber the families following `mapping`
+    pub(crate) fn reassign(&mut self, mapping: &HashMap<FamilyID, FamilyID>) {
+        for family in self.id2family.values_mut() {
+            *family = mapping[family];
+        }
+        self.names = self
+            .names
+            .drain()
+            .map(|(id, name)| (mapping[&id], name))
+            .collect();
+        for id in self.by_name.values_mut() {
+            *id = mapping[id];
+        }
+        self.current = mapping.values().max().map(|m| m + 1).unwrap_or(1);
+    }
+
+    /// Replace the sequential family IDs by content-derived ones
+    pub(crate) fn hash_ids(&mut self) -> Result<()> {
+        let mut mapping = HashMap::new();
+        let mut seen = HashMap::new();
+        for (family, members) in self.families() {
+            let id = content_hash(&members);
+            if let Some(other) = seen.insert(id, family) {
+                bail!(
+                    "families {} and {} have the same members or colliding hashes",
+                    self.names.get(&other).cloned().unwrap_or(other.to_string()),
+                    self.names
+                        .get(&family)
+                        .cloned()
+                        .unwrap_or(family.to_string())
+                );
+            }
+            mapping.insert(family, id);
+        }
+        self.reassign(&mapping);
+        Ok(())
+    }
+}
+
+fn open(f: &str) -> Result<BufReader<File>> {
+    Ok(BufReader::new(File::open(f).map_err(|e| {
+        FileError::CannotOpen {
+            source: e,
+            filename: f.to_owned(),
+        }
+    })?))
+}
+
+fn parse_plain(f: &str, families: &mut Families) -> Result<()> {
+    let mut members = Vec::new();
+    for l in open(f)?.lines() {
+        members.extend(l?.split_whitespace().map(|x| x.to_owned()));
+    }
+    let name = std::path::Path::new(f)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .map(|s| s.to_owned());
+    families.add(name.as_deref(), members);
+
+    Ok(())
+}
+
+fn parse_orthofinder(f: &str, families: &mut Families) -> Result<()> {
+    let mut lines = open(f)?.lines();
+    let header = lines
+        .next()
+        .ok_or_else(|| anyhow!("{} is empty", f.yellow().bold()))??;
+    let header = header.split('\t').collect::<Vec<_>>();
+    // N*.tsv files have two additional leading columns before the species
+    let first_species =
+        if header.get(1) == Some(&"OG") && header.get(2) == Some(&"Gene Tree Parent Clade") {
+            3
+        } else {
+            1
+        };
+
+    for l in lines {
+        let l = l?;
+        if l.is_empty() {
+            continue;
+        }
+        let mut s = l.split('\t');
+        let name = s.next().unwrap_or_default().to_owned();
+        let members = s
+            .skip(first_species - 1)
+            .flat_map(|genes| genes.split(','))
+            .map(|g| g.trim())
+            .filter(|g| !g.is_empty())
+            .collect::<Vec<_>>();
+        families.add(Some(&name), members);
+    }
+
+    Ok(())
+}
+
+fn parse_mcl(f: &str, families: &mut Families) -> Result<()> {
+    for l in open(f)?.lines() {
+        let l = l?;
+        let members = l
+            .split('\t')
+            .map(|g| g.trim())
+            .filter(|g| !g.is_empty())
+            .collect::<Vec<_>>();
+        if !members.is_empty() {
+            families.add(None, members);
+        }
+    }
+
+    Ok(())
+}
+
+fn parse_eggnog(f: &str, level: &str, families: &mut Families) -> Result<()> {
+    let mut og_column = 4;
+    let mut ogs = HashMap::<String, Vec<String>>::new();
+    for l in open(f)?.lines() {
+        let l = l?;
+        if let Some(header) = l.strip_prefix("#query") {
+            if let Some(i) = header.split('\t').position(|c| c == "eggNOG_OGs") {
+                og_column = i;
+            }
+            continue;
+        }
+        if l.starts_with('#') || l.is_empty() {
+            continue;
+        }
+
+        let s = l.split('\t').collect::<Vec<_>>();
+        let og = s.get(og_column).and_then(|ogs| {
+            ogs.split(',').find_map(|og| {
+                // OGs are formatted as `NAME@TAXID|TAXNAME`
+                let (name, taxon) = og.split_once('@')?;
+                let (taxid, taxname) = taxon.split_once('|').unwrap_or((taxon, ""));
+                (taxid == level || taxname == level).then_some(name)
+            })
+        });
+        if let Some(og) = og {
+            ogs.entry(og.to_owned()).or_default().push(s[0].to_owned());
+        }
+    }
+
+    let mut ogs = ogs.into_iter().collect::<Vec<_>>();
+    ogs.sort_by(|a, b| a.0.cmp(&b.0));
+    // The same OGs appear in the annotations of every species
+    for (og, members) in ogs.into_iter() {
+        families.extend(&og, members);
+    }
+
+    Ok(())
+}
+
+fn parse_trees(f: &str, split_duplications: bool, families: &mut Families) -> Result<()> {
+    fn split<'a>(node: &'a newick::Node, families: &mut Vec<Vec<&'a str>>) {
+        if node.is_duplication() && !node.is_leaf() {
+            for child in node.children.iter() {
+                split(child, families);
+            }
+        } else {
+            families.push(node.leaves());
+        }
+    }
+
+    let stem = std::path::Path::new(f)
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or(f);
+    let trees = newick::parse(&std::fs::read_to_string(f)?)?;
+    let many = trees.len() > 1;
+    for (i, tree) in trees.iter().enumerate() {
+        let name = if many {
+            format!("{}.{}", stem, i + 1)
+        } else {
+            stem.to_owned()
+        };
+        if split_duplications {
+            let mut subfamilies = Vec::new();
+            split(tree, &mut subfamilies);
+            if subfamilies.len() == 1 {
+                families.add(Some(&name), subfamilies.pop().unwrap());
+            } else {
+                for (j, members) in subfamilies.into_iter().enumerate() {
+                    families.add(Some(&format!("{}.{}", name, j + 1)), members);
+                }
+            }
+        } else {
+            families.add(Some(&name), tree.leaves());
+        }
+    }
+
+    Ok(())
+}
+
+/// Parse a family file in the given format, and register its families
+pub(crate) fn parse_families(
+    f: &str,
+    format: &FamilyFormat,
+    families: &mut Families,
+) -> Result<()> {
+    trace!("Processing {}", f.bright_white().bold());
+    match format {
+        FamilyFormat::Plain => parse_plain(f, families),
+        FamilyFormat::OrthoFinder => parse_orthofinder(f, families),
+        FamilyFormat::Mcl => parse_mcl(f, families),
+        FamilyFormat::EggNog { level } => parse_eggnog(f, level, families),
+        FamilyFormat::Trees { split_duplications } => parse_trees(f, *split_duplications, families),
+    }
+    .with_context(|| anyhow!("while parsing {}", f.yellow().bold()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Write `content` to a scratch file and return its path
+    fn scratch(name: &str, content: &str) -> String {
+        let path = std::env::temp_dir().join(format!(
+            "syntesuite-families-{}-{}",
+            std::process::id(),
+            name
+        ));
+        std::fs::write(&path, content).unwrap();
+        path.to_str().unwrap().to_owned()
+    }
+
+    fn members(families: &Families, name: &str) -> Vec<String> {
+        let (&id, _) = families.names.iter().find(|(_, n)| *n == name).unwrap();
+        let mut members = families
+            .id2family
+            .iter()
+            .filter(|(_, &f)| f == id)
+            .map(|(m, _)| m.to_owned())
+            .collect::<Vec<_>>();
+        members.sort();
+        members
+    }
+
+    #[test]
+    fn orthofinder_orthogroups() {
+        let f = scratch(
+            "Orthogroups.tsv",
+            "Orthogroup\tspA\tspB\n\
+             OG0000000\ta1, a2\tb1\n\
+             OG0000001\ta3\t\n\
+             \n",
+        );
+        let mut families = Families::new(2);
+        parse_families(&f, &FamilyFormat::OrthoFinder, &mut families).unwrap();
+        assert_eq!(families.len(), 1);
+        assert_eq!(members(&families, "OG0000000"), ["a1", "a2", "b1"]);
+        assert!(families.get("a3").is_none());
+    }
+
+    #[test]
+    fn orthofinder_hierarchical_orthogroups() {
+        let f = scratch(
+            "N0.tsv",
+            "HOG\tOG\tGene Tree Parent Clade\tspA\tspB\n\
+             N0.HOG0000000\tOG0000000\tn1\ta1\tb1, b2\n",
+        );
+        let mut families = Families::new(1);
+        parse_families(&f, &FamilyFormat::OrthoFinder, &mut families).unwrap();
+        assert_eq!(members(&families, "N0.HOG0000000"), ["a1", "b1", "b2"]);
+        assert!(families.get("OG0000000").is_none());
+    }
+
+    const EMAPPER_HEADER: &str =
+        "## emapper-2.1.9\n#query\tseed_ortholog\tevalue\tscore\teggNOG_OGs\tCOG_category\n";
+
+    #[test]
+    fn eggnog_levels() {
+        let f = scratch(
+            "levels.emapper.annotations",
+            &format!(
+                "{EMAPPER_HEADER}\
+                 a1\tx\t0\t1\tCOG1@1|root,KOG1@2759|Eukaryota,3A1@33208|Metazoa\tS\n\
+                 a2\tx\t0\t1\tCOG1@1|root,KOG2@2759|Eukaryota\tS\n\
+                 a3\tx\t0\t1\tCOG1@1|root\tS\n"
+            ),
+        );
+        for level in ["2759", "Eukaryota"] {
+            let mut families = Families::new(1);
+            let format = FamilyFormat::EggNog {
+                level: level.to_owned(),
+            };
+            parse_families(&f, &format, &mut families).unwrap();
+            assert_eq!(families.len(), 2);
+            assert_eq!(members(&families, "KOG1"), ["a1"]);
+            assert_eq!(members(&families, "KOG2"), ["a2"]);
+            assert!(families.get("a3").is_none());
+        }
+    }
+
+    #[test]
+    fn eggnog_across_files() {
+        let a = scratch(
+            "spA.emapper.annotations",
+            &format!(
+                "{EMAPPER_HEADER}\
+                 a1\tx\t0\t1\tKOG1@2759|Eukaryota\tS\n\
+                 a2\tx\t0\t1\tKOG2@2759|Eukaryota\tS\n"
+            ),
+        );
+        let b = scratch(
+            "spB.emapper.annotations",
+            &format!(
+                "{EMAPPER_HEADER}\
+                 b1\tx\t0\t1\tKOG1@2759|Eukaryota\tS\n\
+                 b2\tx\t0\t1\tKOG3@2759|Eukaryota\tS\n"
+            ),
+        );
+        let format = FamilyFormat::EggNog {
+            level: "2759".to_owned(),
+        };
+        let mut families = Families::new(2);
+        for f in [&a, &b] {
+            parse_families(f, &format, &mut families).unwrap();
+        }
+        // Only KOG1 reaches the minimal size, once both files are read
+        assert_eq!(families.len(), 1);
+        assert_eq!(members(&families, "KOG1"), ["a1", "b1"]);
+        assert!(families.get("a2").is_none());
+        assert!(families.get("b2").is_none());
+    }
+}
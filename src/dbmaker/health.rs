@@ -0,0 +1,85 @@
+//! Maintenance and sanity checks of a finished database.
+use anyhow::*;
+use log::*;
+use rusqlite::Connection;
+
+use super::{migrate, update::connect};
+
+/// The state of a database, as reported by [`verify`]
+#[derive(Debug, Clone, Default)]
+pub struct HealthReport {
+    /// The problems reported by `PRAGMA integrity_check`, if any
+    pub integrity: Vec<String>,
+    /// The schema version of the database
+    pub schema_version: u32,
+    /// The number of species stored
+    pub species: usize,
+    /// The number of genes stored
+    pub genes: usize,
+    /// The number of genes whose family is absent from the `families` table
+    pub unknown_families: usize,
+    /// The number of gene IDs appearing more than once
+    pub duplicated_ids: usize,
+}
+impl HealthReport {
+    /// Whether SQLite found the database sound and the genes consistent
+    pub fn is_ok(&self) -> bool {
+        self.integrity.is_empty() && self.unknown_families == 0 && self.duplicated_ids == 0
+    }
+
+    /// Whether the database should be upgraded with [`super::migrate`]
+    pub fn is_outdated(&self) -> bool {
+        self.schema_version < migrate::SCHEMA_VERSION
+    }
+}
+
+fn integrity(conn: &Connection) -> Result<Vec<String>> {
+    let messages = conn
+        .prepare("PRAGMA integrity_check")?
+        .query_map([], |r| r.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
+
+fn count(conn: &Connection, query: &str) -> Result<usize> {
+    Ok(conn.query_row(query, [], |r| r.get(0))?)
+}
+
+/// Refresh the statistics of the query planner, compact the database and
+/// check its integrity
+pub(crate) fn finish(conn: &Connection) -> Result<()> {
+    info!("Optimizing database...");
+    conn.execute_batch("ANALYZE; VACUUM;")
+        .with_context(|| "while optimizing database")?;
+    let problems = integrity(conn)?;
+    if !problems.is_empty() {
+        bail!("integrity check failed: {}", problems.join("; "));
+    }
+    Ok(())
+}
+
+/// Check the integrity and the consistency of the database `db_file`
+pub fn verify(db_file: &str) -> Result<HealthReport> {
+    let conn = connect(db_file)?;
+    let has_families = conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE name='families'")?
+        .exists([])?;
+    Ok(HealthReport {
+        integrity: integrity(&conn)?,
+        schema_version: migrate::schema_version(&conn)?,
+        species: count(&conn, "SELECT COUNT(DISTINCT species) FROM genomes")?,
+        genes: count(&conn, "SELECT COUNT(*) FROM genomes")?,
+        unknown_families: if has_families {
+            count(
+                &conn,
+                "SELECT COUNT(*) FROM genomes WHERE ancestral_id NOT IN (SELECT id FROM families)",
+            )?
+        } else {
+            0
+        },
+        duplicated_ids: count(
+            &conn,
+            "SELECT COUNT(*) FROM (SELECT id FROM genomes GROUP BY id HAVING COUNT(*) > 1)",
+        )?,
+    })
+}
@@ -0,0 +1,174 @@
+//! Checksums of the files a database was built from, so that it can later be
+//! checked against a set of inputs.
+use anyhow::*;
+use regex::Regex;
+use rusqlite::Connection;
+use sha2::{Digest, Sha256};
+use std::{collections::HashMap, io::Read};
+
+use super::{expand_paths, meta, migrate, update::connect};
+use crate::errors::{DataError, FileError};
+
+/// The kinds of input files recorded in the `inputs` table
+pub(crate) const FAMILIES: &str = "families";
+pub(crate) const ANNOTATIONS: &str = "annotations";
+pub(crate) const AGP: &str = "agp";
+pub(crate) const ALIASES: &str = "aliases";
+
+pub(crate) fn create(conn: &Connection) -> Result<()> {
+    conn.execute("DROP TABLE IF EXISTS inputs;", [])
+        .with_context(|| "while dropping table")?;
+    conn.execute(
+        "CREATE TABLE inputs (path text, kind text, sha256 text)",
+        [],
+    )
+    .with_context(|| "while creating database")?;
+    Ok(())
+}
+
+/// The hex-encoded SHA-256 checksum of the content of a file
+pub(crate) fn checksum(filename: &str) -> Result<String> {
+    let mut f = std::fs::File::open(filename).map_err(|e| FileError::CannotOpen {
+        source: e,
+        filename: filename.to_owned(),
+    })?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 1 << 16];
+    loop {
+        let n = f.read(&mut buffer)?;
+        if n == 0 {
+            break;
+        }
+        hasher.update(&buffer[..n]);
+    }
+    Ok(hasher
+        .finalize()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect())
+}
+
+/// Record the checksums of `files`, as inputs of the given kind
+pub(crate) fn record(conn: &Connection, kind: &str, files: &[String]) -> Result<()> {
+    let mut insert =
+        conn.prepare_cached("INSERT INTO inputs (path, kind, sha256) VALUES (?, ?, ?)")?;
+    for f in files.iter() {
+        insert.execute([f.as_str(), kind, &checksum(f)?])?;
+    }
+    Ok(())
+}
+
+/// Forget the annotation files of `species`, as identified by the species
+/// pattern the database was built with
+pub(crate) fn forget_species(conn: &Connection, species: &str) -> Result<()> {
+    let Some(pattern) = meta::get(conn, meta::SPECIES_PATTERN)? else {
+        return Ok(());
+    };
+    let species_regex = Regex::new(&pattern)?;
+    let names = meta::species_names(conn)?;
+    let paths = conn
+        .prepare("SELECT path FROM inputs WHERE kind=?")?
+        .query_map([ANNOTATIONS], |r| r.get::<_, String>(0))?
+        .collect::<Result<Vec<_>, _>>()?;
+    for path in paths.iter() {
+        let filename = std::path::Path::new(path)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or(path);
+        if species_regex
+            .captures(filename)
+            .map(|c| {
+                names
+                    .get(&c["species"])
+                    .map(|s| s.as_str())
+                    .unwrap_or(&c["species"])
+                    == species
+            })
+            .unwrap_or(false)
+        {
+            conn.execute(
+                "DELETE FROM inputs WHERE kind=? AND path=?",
+                [ANNOTATIONS, path],
+            )?;
+        }
+    }
+    Ok(())
+}
+
+/// The outcome of comparing a set of files to the inputs of a database;
+/// files are matched by content, then by name to detect modifications.
+#[derive(Debug, Default, Clone)]
+pub struct InputsCheck {
+    /// Files identical to one of the inputs
+    pub matching: Vec<String>,
+    /// Files sharing their name with one of the inputs, but not its content
+    pub modified: Vec<String>,
+    /// Recorded inputs matching none of the files
+    pub missing: Vec<String>,
+    /// Files matching none of the recorded inputs
+    pub unexpected: Vec<String>,
+}
+impl InputsCheck {
+    /// Whether the files are exactly the inputs of the database
+    pub fn is_ok(&self) -> bool {
+        self.modified.is_empty() && self.missing.is_empty() && self.unexpected.is_empty()
+    }
+}
+
+/// Compare `files` (or the content of directories) to the inputs `db_file`
+/// was built from
+pub fn check_inputs(db_file: &str, files: &[String]) -> Result<InputsCheck> {
+    let conn = connect(db_file)?;
+    let found = migrate::schema_version(&conn)?;
+    if found < 2 {
+        return Err(DataError::UnsupportedSchema {
+            filename: db_file.into(),
+            found,
+            min: 2,
+            max: migrate::SCHEMA_VERSION,
+        }
+        .into());
+    }
+    let recorded = conn
+        .prepare("SELECT path, sha256 FROM inputs")?
+        .query_map([], |r| {
+            std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    let file_name = |path: &str| {
+        std::path::Path::new(path)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_else(|| path.to_owned())
+    };
+
+    let mut check = InputsCheck::default();
+    let mut found = vec![false; recorded.len()];
+    let by_checksum = recorded
+        .iter()
+        .enumerate()
+        .map(|(i, (_, sha))| (sha.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    for f in expand_paths(files, &Default::default())?.into_iter() {
+        if let Some(i) = by_checksum.get(checksum(&f)?.as_str()) {
+            found[*i] = true;
+            check.matching.push(f);
+        } else if let Some(i) = recorded
+            .iter()
+            .position(|(path, _)| file_name(path) == file_name(&f))
+        {
+            found[i] = true;
+            check.modified.push(f);
+        } else {
+            check.unexpected.push(f);
+        }
+    }
+    check.missing = recorded
+        .into_iter()
+        .zip(found)
+        .filter(|(_, found)| !found)
+        .map(|((path, _), _)| path)
+        .collect();
+
+    Ok(check)
+}
@@ -0,0 +1,149 @@
+//! The `meta` table, recording how a database was built as key/value pairs.
+use anyhow::*;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+pub(crate) const CRATE_VERSION: &str = "crate_version";
+pub(crate) const SCHEMA_VERSION: &str = "schema_version";
+pub(crate) const WINDOW: &str = "window";
+pub(crate) const ID_TYPE: &str = "id_type";
+pub(crate) const SPECIES_PATTERN: &str = "species_pattern";
+pub(crate) const ID_PATTERN: &str = "id_pattern";
+pub(crate) const COORDINATES: &str = "coordinates";
+pub(crate) const LAYOUT: &str = "layout";
+pub(crate) const FAMILY_IDS: &str = "family_ids";
+pub(crate) const ATTRIBUTES: &str = "attributes";
+pub(crate) const SPECIES_NAMES: &str = "species_names";
+pub(crate) const NEIGHBORS: &str = "neighbors";
+pub(crate) const CIRCULAR: &str = "circular";
+pub(crate) const RICH_LANDSCAPES: &str = "rich_landscapes";
+pub(crate) const COLLAPSED_TANDEMS: &str = "collapsed_tandems";
+pub(crate) const FAMILIES: &str = "families";
+pub(crate) const ANNOTATIONS: &str = "annotations";
+pub(crate) const CREATED: &str = "created";
+pub(crate) const UPDATED: &str = "updated";
+
+pub(crate) fn create(conn: &Connection) -> Result<()> {
+    conn.execute("DROP TABLE IF EXISTS meta;", [])
+        .with_context(|| "while dropping table")?;
+    conn.execute("CREATE TABLE meta (key text PRIMARY KEY, value text)", [])
+        .with_context(|| "while creating database")?;
+    Ok(())
+}
+
+pub(crate) fn set<V: ToString>(conn: &Connection, key: &str, value: V) -> Result<()> {
+    conn.execute(
+        "INSERT OR REPLACE INTO meta (key, value) VALUES (?, ?)",
+        [key, &value.to_string()],
+    )
+    .with_context(|| anyhow!("while setting {}", key))?;
+    Ok(())
+}
+
+/// Append `values` to the newline-separated list stored under `key`
+pub(crate) fn append(conn: &Connection, key: &str, values: &[String]) -> Result<()> {
+    let mut list = get_list(conn, key)?;
+    list.extend(values.iter().cloned());
+    set(conn, key, list.join("\n"))
+}
+
+/// Read the newline-separated list stored under `key`
+pub(crate) fn get_list(conn: &Connection, key: &str) -> Result<Vec<String>> {
+    Ok(get(conn, key)?
+        .map(|l| l.lines().map(|s| s.to_owned()).collect())
+        .unwrap_or_default())
+}
+
+pub(crate) fn get(conn: &Connection, key: &str) -> Result<Option<String>> {
+    if !has_meta(conn)? {
+        return Ok(None);
+    }
+    Ok(conn
+        .prepare("SELECT value FROM meta WHERE key=?")?
+        .query_map([key], |r| r.get::<_, String>(0))?
+        .next()
+        .transpose()?)
+}
+
+/// Read the whole `meta` table; databases predating it yield an empty map
+pub(crate) fn read(conn: &Connection) -> Result<HashMap<String, String>> {
+    if !has_meta(conn)? {
+        return Ok(HashMap::new());
+    }
+    let meta = conn
+        .prepare("SELECT key, value FROM meta")?
+        .query_map([], |r| {
+            std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    Ok(meta)
+}
+
+/// The mapping from the species names captured from filenames to the names
+/// stored in the database
+pub(crate) fn species_names(conn: &Connection) -> Result<HashMap<String, String>> {
+    Ok(get_list(conn, SPECIES_NAMES)?
+        .into_iter()
+        .filter_map(|l| {
+            l.split_once('\t')
+                .map(|(from, to)| (from.to_owned(), to.to_owned()))
+        })
+        .collect())
+}
+
+pub(crate) fn set_species_names(conn: &Connection, names: &HashMap<String, String>) -> Result<()> {
+    let mut names = names
+        .iter()
+        .map(|(from, to)| format!("{from}\t{to}"))
+        .collect::<Vec<_>>();
+    names.sort();
+    set(conn, SPECIES_NAMES, names.join("\n"))
+}
+
+/// The circular chromosomes, stored as `species\tchr` lines, or `*` if all
+/// of them are
+pub(crate) fn circular(conn: &Connection) -> Result<super::Circular> {
+    let mut circular = super::Circular::default();
+    for l in get_list(conn, CIRCULAR)? {
+        if l == "*" {
+            circular.all = true;
+        } else if let Some((species, chr)) = l.split_once('\t') {
+            circular
+                .chromosomes
+                .insert((species.to_owned(), chr.to_owned()));
+        }
+    }
+    Ok(circular)
+}
+
+pub(crate) fn set_circular(conn: &Connection, circular: &super::Circular) -> Result<()> {
+    let mut lines = circular
+        .chromosomes
+        .iter()
+        .map(|(species, chr)| format!("{species}\t{chr}"))
+        .collect::<Vec<_>>();
+    lines.sort();
+    if circular.all {
+        lines.insert(0, "*".to_owned());
+    }
+    set(conn, CIRCULAR, lines.join("\n"))
+}
+
+/// Return the build provenance recorded in the `meta` table of `db_file`
+pub fn metadata(db_file: &str) -> Result<HashMap<String, String>> {
+    read(&super::update::connect(db_file)?)
+}
+
+fn has_meta(conn: &Connection) -> Result<bool> {
+    Ok(conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type='table' AND name='meta'")?
+        .exists([])?)
+}
+
+/// The current time, in seconds since the UNIX epoch
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default()
+}
@@ -0,0 +1,129 @@
+//! Versioning of the database schema, and in-place upgrade of databases
+//! built by older versions.
+//!
+//! Databases predating the `meta` table are considered to be of version 0.
+//! Each schema change comes with a migration, bringing a database from the
+//! previous version to its own; migrations are applied in order, each one
+//! in its own transaction.
+use anyhow::*;
+use colored::Colorize;
+use log::*;
+use rusqlite::{Connection, Transaction};
+
+use super::{meta, update::connect};
+
+/// The version of the schema created by this version of the crate
+pub const SCHEMA_VERSION: u32 = 4;
+
+/// The oldest schema version that can still be read as is by
+/// [`GeneBook`](crate::genebook::GeneBook)
+pub(crate) const READABLE_SINCE: u32 = 0;
+
+type Migration = fn(&Transaction) -> Result<()>;
+
+/// The migrations, the i-th one bringing a database from version i to i+1
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2, v2_to_v3, v3_to_v4];
+
+/// Add the `meta` and `chr_aliases` tables
+fn v0_to_v1(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS meta (key text PRIMARY KEY, value text);
+         CREATE TABLE IF NOT EXISTS chr_aliases (alias text PRIMARY KEY, chr text);",
+    )?;
+    Ok(())
+}
+
+/// Add the `inputs` table; the checksums of the original inputs can not be
+/// recovered, so it is left empty
+fn v1_to_v2(tx: &Transaction) -> Result<()> {
+    tx.execute(
+        "CREATE TABLE IF NOT EXISTS inputs (path text, kind text, sha256 text)",
+        [],
+    )?;
+    Ok(())
+}
+
+/// Add the `families` table; family names can not be recovered, so it only
+/// lists the family IDs
+fn v2_to_v3(tx: &Transaction) -> Result<()> {
+    tx.execute_batch(
+        "CREATE TABLE IF NOT EXISTS families (id integer PRIMARY KEY, name text);
+         INSERT OR IGNORE INTO families (id) SELECT DISTINCT ancestral_id FROM genomes;",
+    )?;
+    Ok(())
+}
+
+/// Scope the chromosome aliases by species, following [`aliases_source`]
+fn v3_to_v4(tx: &Transaction) -> Result<()> {
+    let source = aliases_source(tx)?;
+    if source == "chr_aliases" {
+        return Ok(());
+    }
+    tx.execute_batch(&format!(
+        "CREATE TABLE chr_aliases_v4 (species text, alias text, chr text, PRIMARY KEY (species, alias));
+         INSERT OR IGNORE INTO chr_aliases_v4 (species, alias, chr) SELECT species, alias, chr FROM {source};
+         DROP TABLE chr_aliases;
+         ALTER TABLE chr_aliases_v4 RENAME TO chr_aliases;"
+    ))?;
+    Ok(())
+}
+
+/// The table or subquery of the (species, alias, chr) rows of the
+/// chromosome aliases of a database; aliases stored before they were scoped
+/// by species apply to the species having their canonical chromosome
+pub(crate) fn aliases_source(conn: &Connection) -> Result<&'static str> {
+    let scoped = conn
+        .prepare("SELECT 1 FROM pragma_table_info('chr_aliases') WHERE name='species'")?
+        .exists([])?;
+    Ok(if scoped {
+        "chr_aliases"
+    } else {
+        "(SELECT DISTINCT g.species, a.alias, a.chr FROM chr_aliases a JOIN (SELECT DISTINCT species, chr FROM genomes) g ON g.chr = a.chr)"
+    })
+}
+
+/// The schema version of an open database
+pub(crate) fn schema_version(conn: &Connection) -> Result<u32> {
+    meta::get(conn, meta::SCHEMA_VERSION)?
+        .map(|v| {
+            v.parse::<u32>()
+                .with_context(|| anyhow!("invalid schema version: {}", v))
+        })
+        .unwrap_or(Ok(0))
+}
+
+/// Upgrade `db_file` in place to the current schema version, and return
+/// the version it was previously at
+pub fn migrate(db_file: &str) -> Result<u32> {
+    let mut conn = connect(db_file)?;
+    let found = schema_version(&conn)?;
+    if found > SCHEMA_VERSION {
+        bail!(
+            "{} uses schema version {}, newer than the supported {}",
+            db_file.yellow().bold(),
+            found,
+            SCHEMA_VERSION
+        );
+    }
+
+    for (version, migration) in MIGRATIONS.iter().enumerate().skip(found as usize) {
+        info!(
+            "Migrating {} from schema version {} to {}",
+            db_file.bold(),
+            version,
+            version + 1
+        );
+        let tx = conn.transaction()?;
+        migration(&tx).with_context(|| {
+            anyhow!(
+                "while migrating {} to schema version {}",
+                db_file.yellow().bold(),
+                version + 1
+            )
+        })?;
+        meta::set(&tx, meta::SCHEMA_VERSION, version + 1)?;
+        tx.commit()?;
+    }
+
+    Ok(found)
+}
@@ -1,20 +1,56 @@
 use anyhow::*;
 use colored::Colorize;
-use flate2::bufread::GzDecoder;
 use log::*;
 use regex::Regex;
 use rusqlite::Connection;
 use std::{
     collections::{HashMap, HashSet},
-    fs::File,
-    io::{BufRead, BufReader, Seek},
+    io::BufRead,
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        mpsc,
+    },
 };
 use thiserror::*;
 
+#[cfg(feature = "duckdb")]
+mod duckdb;
+mod dump;
+mod families;
+mod health;
+pub(crate) mod inputs;
+pub(crate) mod meta;
+pub(crate) mod migrate;
+mod progress;
+pub(crate) mod save;
+mod schema;
+mod tuning;
+mod update;
+#[cfg(feature = "duckdb")]
+pub use duckdb::to_duckdb;
+#[cfg(feature = "parquet")]
+pub use dump::export_parquet;
+pub use dump::{export, DumpFormat};
+use families::Families;
+pub use families::{FamilyFormat, FamilyIds};
+pub use health::{verify, HealthReport};
+pub use inputs::{check_inputs, InputsCheck};
+pub use meta::metadata;
+pub use migrate::{migrate, SCHEMA_VERSION};
+pub use progress::{BuildEvent, ProgressCallback};
+pub use schema::Layout;
+pub use tuning::{DbTuning, JournalMode, Synchronous};
+pub use update::{add_species, remove_species, rename_species, replace_species, rewindow};
+
 use crate::{
-    bed, chrom,
+    agp,
+    aliases::ChromAliases,
     errors::{DataError, FileError, ParseError},
-    gff, Record, Strand,
+    genebook::{collapsed_flanks, flanks, TailGene},
+    input,
+    interval::natural_cmp,
+    table::TableLayout,
+    Coordinates, Strand,
 };
 
 #[derive(Error, Debug)]
@@ -33,109 +69,361 @@ pub enum Error {
 
     #[error("record {} has no ID", .0.bold().yellow())]
     RecordWithoutId(String),
+
+    #[error("{} uses the {} layout, which does not support this operation", .0.bold().yellow(), .1)]
+    UnsupportedLayout(String, Layout),
+
+    #[error("{} can not be used as an attribute column", .0.bold().yellow())]
+    InvalidAttribute(String),
+
+    #[error("ID {} is duplicated in {}", .0.bold().yellow(), .1.bold().yellow())]
+    DuplicateId(String, String),
+}
+
+/// Secondary settings for [`db_from_files`]; the default values reproduce
+/// the plain behavior.
+#[derive(Debug, Default, Clone)]
+pub struct BuildOptions {
+    /// AGP files of each species, as named in the database, describing how
+    /// its annotated components are placed on its chromosomes; annotations
+    /// on placed components are projected onto the chromosomes before
+    /// landscapes are computed
+    pub agps: HashMap<String, Vec<String>>,
+    /// The format of the family files
+    pub family_format: FamilyFormat,
+    /// How family IDs are assigned
+    pub family_ids: FamilyIds,
+    /// Families with fewer members than this are discarded, e.g. 2 to drop singletons
+    pub min_family_size: usize,
+    /// The coordinate convention used in the database; annotations are
+    /// converted to it whatever their original format
+    pub coordinates: Coordinates,
+    /// Chromosome alias files (e.g. UCSC `chromAlias.txt`) of each species,
+    /// as named in the database, used to give the same name to all the
+    /// aliases of a chromosome of this species
+    pub chr_aliases: HashMap<String, Vec<String>>,
+    /// If set, annotation files are read as delimited tables with this
+    /// layout rather than through format detection
+    pub table: Option<TableLayout>,
+    /// How many annotation files to parse in parallel; 0 uses all the
+    /// available cores
+    pub threads: usize,
+    /// The SQLite settings used while filling the database
+    pub tuning: DbTuning,
+    /// How the genes are laid out in the database
+    pub layout: Layout,
+    /// Display names for the species, replacing the names captured from the
+    /// annotation filenames; see [`read_species_names`]
+    pub species_names: HashMap<String, String>,
+    /// How to handle IDs appearing several times in an annotation file
+    pub duplicates: DuplicatePolicy,
+    /// GFF attributes (e.g. `biotype`, `Name`) to store in their own
+    /// columns, named after them; genes lacking them get NULL values
+    pub attributes: Vec<String>,
+    /// Called on each step of the build
+    pub progress: Option<ProgressCallback>,
+    /// Only parse the inputs and report what would be stored through the
+    /// returned [`BuildSummary`], leaving the database untouched
+    pub dry_run: bool,
+    /// If set, also store the landscapes as a `neighbors(gene_id, offset,
+    /// family, strand)` table, spanning this many genes on each side
+    /// independently of the landscape window
+    pub neighbors: Option<usize>,
+    /// The chromosomes whose landscapes wrap around their origin
+    pub circular: Circular,
+    /// If set, chromosomes whose name matches this regex are discarded
+    pub exclude_chromosomes: Option<String>,
+    /// Chromosomes with fewer genes belonging to a family than this are
+    /// discarded, e.g. to get rid of small scaffolds
+    pub min_chromosome_genes: usize,
+    /// GFF attributes (e.g. `Name`, `locus_tag`) to try in turn as the ID of
+    /// records lacking an `ID` attribute
+    pub id_fallbacks: Vec<String>,
+    /// Once the database is filled, run `ANALYZE` and `VACUUM` on it, then
+    /// check its integrity
+    pub optimize: bool,
+    /// How family directories are read
+    pub family_files: InputFilter,
+    /// How annotation directories are read
+    pub annotation_files: InputFilter,
+    /// Also store the ID and start of the genes of the landscapes, so that
+    /// [`TailGene::id`](crate::genebook::TailGene::id) and
+    /// [`TailGene::pos`](crate::genebook::TailGene::pos) are available;
+    /// older versions of this crate can not read such databases
+    pub rich_landscapes: bool,
+    /// Store each run of consecutive genes of the same family as a single
+    /// landscape entry, with its number of copies in
+    /// [`TailGene::copies`](crate::genebook::TailGene::copies), so that
+    /// tandem arrays do not fill the whole window; older versions of this
+    /// crate can not read such databases
+    pub collapse_tandems: bool,
+}
+
+/// How the landscapes are stored in a database
+#[derive(Debug, Default, Clone, Copy)]
+pub(crate) struct LandscapeStyle {
+    /// Whether the IDs and starts of the neighbors are stored
+    pub(crate) rich: bool,
+    /// Whether tandem arrays are collapsed
+    pub(crate) collapse: bool,
+}
+impl LandscapeStyle {
+    fn of(options: &BuildOptions) -> Self {
+        LandscapeStyle {
+            rich: options.rich_landscapes,
+            collapse: options.collapse_tandems,
+        }
+    }
+
+    /// The style of the landscapes already stored in `conn`
+    pub(crate) fn stored(conn: &Connection) -> Result<Self> {
+        Ok(LandscapeStyle {
+            rich: meta::get(conn, meta::RICH_LANDSCAPES)?.is_some(),
+            collapse: meta::get(conn, meta::COLLAPSED_TANDEMS)?.is_some(),
+        })
+    }
+
+    pub(crate) fn record(&self, conn: &Connection) -> Result<()> {
+        if self.rich {
+            meta::set(conn, meta::RICH_LANDSCAPES, true)?;
+        }
+        if self.collapse {
+            meta::set(conn, meta::COLLAPSED_TANDEMS, true)?;
+        }
+        Ok(())
+    }
 }
 
+/// How the files of input directories are selected; files given explicitly
+/// are always used
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InputFilter {
+    /// Also read the files of sub-directories
+    pub recursive: bool,
+    /// If not empty, only use the files whose path relative to the input
+    /// directory matches one of these globs (e.g. `*.gff3`)
+    pub include: Vec<String>,
+    /// Skip the files whose path relative to the input directory matches one
+    /// of these globs (e.g. `README*`)
+    pub exclude: Vec<String>,
+}
+
+/// The chromosomes to be treated as circular (e.g. bacterial or organellar
+/// replicons), whose first and last genes are neighbors
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Circular {
+    /// Treat all the chromosomes as circular
+    pub all: bool,
+    /// The circular chromosomes, as (species, chromosome) pairs; species are
+    /// named as in the database
+    pub chromosomes: HashSet<(String, String)>,
+}
+impl Circular {
+    pub fn contains(&self, species: &str, chr: &str) -> bool {
+        self.all
+            || self
+                .chromosomes
+                .contains(&(species.to_owned(), chr.to_owned()))
+    }
+
+    pub(crate) fn extend(&mut self, other: &Circular) {
+        self.all |= other.all;
+        self.chromosomes.extend(other.chromosomes.iter().cloned());
+    }
+}
+
+/// What to do with genes whose ID appears several times in an annotation file
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum DuplicatePolicy {
+    /// Abort the build
+    Error,
+    /// Keep the first occurrence only
+    #[default]
+    KeepFirst,
+    /// Keep the longest occurrence, the first one in case of ties
+    KeepLongest,
+    /// Keep all the occurrences, the n-th one being renamed to `ID.n`
+    KeepAllWithSuffix,
+}
+
+/// A summary of what went into a database
+#[derive(Debug, Default, Clone)]
+pub struct BuildSummary {
+    /// The number of families read from the family files
+    pub families: usize,
+    /// The number of genes stored for each species
+    pub genes: HashMap<String, usize>,
+    /// For each species, the IDs found more than once in its annotations
+    pub duplicates: HashMap<String, Vec<String>>,
+    /// For each species, the chromosomes discarded by the chromosome filters
+    pub dropped_chromosomes: HashMap<String, Vec<String>>,
+}
+
+/// The annotations of a genome, by chromosome
+type Genome = HashMap<String, Vec<Annotation>>;
+
 struct Annotation {
     id: String,
     dir: Strand,
     start: usize,
     stop: usize,
     ancestral_id: usize,
+    /// The values of [`BuildOptions::attributes`], in the same order
+    attributes: Vec<Option<String>>,
 }
 
-fn parse_family(
-    f: &str,
-    current_ancestral_id: &mut usize,
-    id2ancestral: &mut HashMap<String, usize>,
-) -> Result<()> {
-    trace!("Processing {}", f.bright_white().bold());
-    for l in BufReader::new(File::open(f).map_err(|e| FileError::CannotOpen {
-        source: e,
-        filename: f.to_owned(),
-    })?)
-    .lines()
-    {
-        for id in l?.split_whitespace() {
-            id2ancestral.insert(id.into(), *current_ancestral_id);
+/// An annotation, along with the chromosome it lies on
+type Placed = (String, Annotation);
+
+/// The columns of the genomes table, which can not be used as attribute names
+const RESERVED_COLUMNS: &[&str] = &[
+    "species",
+    "chr",
+    "ancestral_id",
+    "id",
+    "start",
+    "stop",
+    "direction",
+    "left_tail_ids",
+    "right_tail_ids",
+];
+/// The additional columns of the genes table in the normalized layout
+const RESERVED_NORMALIZED_COLUMNS: &[&str] = &["name", "chromosome_id", "family_id"];
+
+fn check_attributes(attributes: &[String], layout: Layout) -> Result<()> {
+    let mut seen = HashSet::new();
+    for a in attributes.iter() {
+        let lower = a.to_lowercase();
+        if a.is_empty()
+            || a.contains('"')
+            || RESERVED_COLUMNS.contains(&lower.as_str())
+            || (layout == Layout::Normalized
+                && RESERVED_NORMALIZED_COLUMNS.contains(&lower.as_str()))
+            || !seen.insert(a.to_lowercase())
+        {
+            return Err(Error::InvalidAttribute(a.to_owned()).into());
         }
     }
-    *current_ancestral_id += 1;
-
     Ok(())
 }
 
-fn parse_genome_gff3(f: &str) -> Result<Box<dyn Iterator<Item = Result<Record, ParseError>>>> {
-    let mut f = File::open(f).map_err(|e| FileError::CannotOpen {
-        source: e,
-        filename: f.to_owned(),
-    })?;
-    let gz = GzDecoder::new(BufReader::new(f.try_clone().unwrap()));
+/// The SQL definitions of the attribute columns, each prefixed by a comma
+fn attribute_columns(attributes: &[String], with_type: bool) -> String {
+    attributes
+        .iter()
+        .map(|a| {
+            if with_type {
+                format!(", \"{a}\" text")
+            } else {
+                format!(", \"{a}\"")
+            }
+        })
+        .collect()
+}
 
-    Ok(match gz.header() {
-        Some(_) => Box::new(
-            gff::GffReader::new(gz).map(|r| r.map(|r| r.into()).map_err(ParseError::GffError)),
-        ),
-        None => {
-            f.rewind()?;
-            Box::new(
-                gff::GffReader::new(BufReader::new(f))
-                    .map(|r| r.map(|r| r.into()).map_err(ParseError::GffError)),
-            )
+/// Read a two-column tab-separated file mapping the species names captured
+/// from filenames to the names to use in the database
+pub fn read_species_names(filename: &str) -> Result<HashMap<String, String>> {
+    let mut names = HashMap::new();
+    for (i, l) in input::open(filename)?.lines().enumerate() {
+        let l = l?;
+        if l.starts_with('#') || l.trim().is_empty() {
+            continue;
         }
-    })
+        let (from, to) = l.split_once('\t').ok_or_else(|| {
+            anyhow!(
+                "{}:{}: expected two tab-separated columns",
+                filename.yellow().bold(),
+                i + 1
+            )
+        })?;
+        names.insert(from.trim().to_owned(), to.trim().to_owned());
+    }
+    Ok(names)
 }
 
-fn parse_genome_bed(f: &str) -> Result<Box<dyn Iterator<Item = Result<Record, ParseError>>>> {
-    let mut f = File::open(f).map_err(|e| FileError::CannotOpen {
-        source: e,
-        filename: f.to_owned(),
-    })?;
-    let gz = GzDecoder::new(BufReader::new(f.try_clone().unwrap()));
-
-    Ok(match gz.header() {
-        Some(_) => Box::new(
-            bed::BedReader::new(gz).map(|r| r.map(|r| r.into()).map_err(ParseError::BedError)),
-        ),
-        None => {
-            f.rewind()?;
-            Box::new(
-                bed::BedReader::new(BufReader::new(f))
-                    .map(|r| r.map(|r| r.into()).map_err(ParseError::BedError)),
+/// Read a two-column tab-separated file listing the circular chromosomes, as
+/// species and chromosome names
+pub fn read_circular(filename: &str) -> Result<Circular> {
+    let mut circular = Circular::default();
+    for (i, l) in input::open(filename)?.lines().enumerate() {
+        let l = l?;
+        if l.starts_with('#') || l.trim().is_empty() {
+            continue;
+        }
+        let (species, chr) = l.split_once('\t').ok_or_else(|| {
+            anyhow!(
+                "{}:{}: expected two tab-separated columns",
+                filename.yellow().bold(),
+                i + 1
             )
+        })?;
+        circular
+            .chromosomes
+            .insert((species.trim().to_owned(), chr.trim().to_owned()));
+    }
+    Ok(circular)
+}
+
+fn parse_aliases(files: &HashMap<String, Vec<String>>) -> Result<ChromAliases> {
+    let mut aliases = ChromAliases::default();
+    for (species, files) in files.iter() {
+        for f in files.iter() {
+            info!("Parsing chromosome aliases {}", f.bright_white().bold());
+            aliases
+                .extend_from_reader(species, input::open(f)?)
+                .with_context(|| anyhow!("while parsing {}", f.yellow().bold()))?;
         }
-    })
+    }
+    Ok(aliases)
 }
 
-fn parse_genome_chrom(f: &str) -> Result<Box<dyn Iterator<Item = Result<Record, ParseError>>>> {
-    let mut f = File::open(f).map_err(|e| FileError::CannotOpen {
-        source: e,
-        filename: f.to_owned(),
-    })?;
-    let gz = GzDecoder::new(BufReader::new(f.try_clone().unwrap()));
+/// All the files of a per-species mapping, in a stable order
+fn species_files(files: &HashMap<String, Vec<String>>) -> Vec<String> {
+    let mut species = files.keys().collect::<Vec<_>>();
+    species.sort();
+    species
+        .into_iter()
+        .flat_map(|s| files[s].iter().cloned())
+        .collect()
+}
 
-    Ok(match gz.header() {
-        Some(_) => Box::new(
-            chrom::ChromReader::new(gz)
-                .map(|r| r.map(|r| r.into()).map_err(ParseError::ChromError)),
-        ),
-        None => {
-            f.rewind()?;
-            Box::new(
-                chrom::ChromReader::new(BufReader::new(f))
-                    .map(|r| r.map(|r| r.into()).map_err(ParseError::ChromError)),
-            )
+fn parse_assemblies(agps: &HashMap<String, Vec<String>>) -> Result<HashMap<String, agp::Assembly>> {
+    let mut assemblies = HashMap::<String, agp::Assembly>::new();
+    for (species, files) in agps.iter() {
+        for f in files.iter() {
+            info!("Parsing AGP {}", f.bright_white().bold());
+            assemblies.entry(species.to_owned()).or_default().extend(
+                agp::AgpReader::new(input::open(f)?)
+                    .collect::<Result<Vec<_>, _>>()
+                    .with_context(|| anyhow!("while parsing {}", f.yellow().bold()))?,
+            );
         }
-    })
+    }
+    Ok(assemblies)
 }
 
 fn parse_file(
     filename: &str,
     species_pattern: &str,
+    table: Option<&TableLayout>,
 ) -> Result<(
     String,
     impl Iterator<Item = Result<crate::Record, ParseError>>,
 )> {
     info!("Processing {}", filename.bright_white().bold());
+    let species = species_of(filename, species_pattern)?;
+    info!("Species: {}", species);
+    let records = if let Some(layout) = table {
+        input::table_records(filename, layout)?
+    } else {
+        input::records(filename)?.1
+    };
+    Ok((species, records))
+}
+
+/// The species of an annotation file, as captured from its name
+fn species_of(filename: &str, species_pattern: &str) -> Result<String> {
     let species_regex = Regex::new(species_pattern).map_err(|e| Error::InvalidRegex {
         source: e,
         re: species_pattern.to_string(),
@@ -160,28 +448,15 @@ fn parse_file(
         )
         .ok_or_else(|| Error::SpeciesNotFound(filename.to_string()))?["species"]
         .to_string();
-    info!("Species: {}", species);
-    let records = if filename.ends_with("gff")
-        || filename.ends_with("gff3")
-        || filename.ends_with("gff.gz")
-        || filename.ends_with("gff3.gz")
-    {
-        parse_genome_gff3(filename)?
-    } else if filename.ends_with("bed") || filename.ends_with("bed.gz") {
-        parse_genome_bed(filename)?
-    } else if filename.ends_with("chrom")
-        || filename.ends_with("chrom.gz")
-        || filename.ends_with("tsv")
-        || filename.ends_with("tsv.gz")
-    {
-        parse_genome_chrom(filename)?
-    } else {
-        bail!(
-            "unable to process {}: unknown filetype",
-            filename.yellow().bold()
-        )
-    };
-    Ok((species, records))
+    Ok(species)
+}
+
+/// The auxiliary data required to process the annotation files
+struct BuildContext<'a> {
+    id2ancestral: Families,
+    assemblies: HashMap<String, agp::Assembly>,
+    aliases: ChromAliases,
+    options: &'a BuildOptions,
 }
 
 fn parse_genome(
@@ -189,9 +464,8 @@ fn parse_genome(
     species_pattern: &str,
     id_type: &str,
     id_pattern: &str,
-    genomes: &mut HashMap<String, HashMap<String, Vec<Annotation>>>,
-    id2ancestral: &HashMap<String, usize>,
-) -> Result<()> {
+    ctx: &BuildContext,
+) -> Result<(String, Genome, Vec<String>)> {
     let id_regex = Regex::new(id_pattern).map_err(|e| Error::InvalidRegex {
         source: e,
         re: id_pattern.to_string(),
@@ -207,38 +481,88 @@ fn parse_genome(
         .into());
     }
 
-    let mut seen = HashSet::new();
-    let (species, records) = parse_file(f, species_pattern)?;
-    for record in records {
+    let mut annotations = Vec::<(String, Annotation)>::new();
+    let (species, records) = parse_file(f, species_pattern, ctx.options.table.as_ref())?;
+    let species = ctx
+        .options
+        .species_names
+        .get(&species)
+        .cloned()
+        .unwrap_or(species);
+    for (i, record) in records.enumerate() {
         let record = record?;
+        if (i + 1) % progress::RECORDS_STEP == 0 {
+            progress::report(ctx.options.progress.as_ref(), || BuildEvent::RecordsRead {
+                file: f.to_owned(),
+                records: i + 1,
+            });
+        }
         if record.is_class(id_type) {
-            let id = record.id().ok_or_else(|| {
-                Error::RecordWithoutId(format!(
-                    "{}:{}-{}",
-                    record.chr(),
-                    record.start(),
-                    record.end()
-                ))
-            })?;
+            let id = record
+                .id()
+                .map(|id| id.to_owned())
+                .or_else(|| {
+                    ctx.options
+                        .id_fallbacks
+                        .iter()
+                        .find_map(|key| record.attribute(key))
+                })
+                .ok_or_else(|| {
+                    Error::RecordWithoutId(format!(
+                        "{}:{}-{}",
+                        record.chr(),
+                        record.start(),
+                        record.end()
+                    ))
+                })?;
             let id = id_regex
-                .captures(id)
-                .ok_or_else(|| Error::IdNotFound(id.into()))?["id"]
+                .captures(&id)
+                .ok_or_else(|| Error::IdNotFound(id.clone()))?["id"]
                 .to_string();
-            if let Some(ancestral_id) = id2ancestral.get(&id) {
-                if seen.insert(id.clone()) {
-                    genomes
-                        .entry(species.clone())
-                        .or_default()
-                        .entry(record.chr().into())
-                        .or_default()
-                        .push(Annotation {
-                            id: id.to_string(),
-                            dir: record.strand(),
-                            start: record.start(),
-                            stop: record.end(),
-                            ancestral_id: *ancestral_id,
-                        });
-                }
+            if let Some(ancestral_id) = ctx.id2ancestral.get(&id) {
+                // AGP files are 1-based, so project before the final conversion
+                let (start, stop) = record.coordinates().convert(
+                    record.start(),
+                    record.end(),
+                    Coordinates::OneBased,
+                );
+                let chr = ctx.aliases.resolve(&species, record.chr());
+                let (chr, start, stop, dir) = match ctx.assemblies.get(&species) {
+                    Some(assembly) if assembly.contains(chr) => {
+                        if let Some(projected) =
+                            assembly.project_interval(chr, start, stop, record.strand())
+                        {
+                            projected
+                        } else {
+                            warn!(
+                                "Skipping {}, which does not fit in a placement of {}",
+                                id.bold().yellow(),
+                                chr.bold().yellow()
+                            );
+                            continue;
+                        }
+                    }
+                    _ => (chr, start, stop, record.strand()),
+                };
+                let chr = ctx.aliases.resolve(&species, chr);
+                let (start, stop) =
+                    Coordinates::OneBased.convert(start, stop, ctx.options.coordinates);
+                annotations.push((
+                    chr.to_owned(),
+                    Annotation {
+                        id: id.to_string(),
+                        dir,
+                        start,
+                        stop,
+                        ancestral_id: *ancestral_id,
+                        attributes: ctx
+                            .options
+                            .attributes
+                            .iter()
+                            .map(|a| record.attribute(a))
+                            .collect(),
+                    },
+                ));
             } else {
                 debug!("Skipping ID {} not found in families", id.bold().yellow());
             }
@@ -252,16 +576,318 @@ fn parse_genome(
         }
     }
 
-    if let Some(genome) = genomes.get_mut(&species) {
-        for (_, ids) in genome.iter_mut() {
-            ids.sort_by_key(|a| a.start);
+    let (annotations, duplicates) = deduplicate(f, annotations, ctx.options.duplicates)?;
+    let mut genome = Genome::new();
+    for (chr, annotation) in annotations.into_iter() {
+        genome.entry(chr).or_default().push(annotation);
+    }
+
+    if genome.is_empty() {
+        warn!("{} appears to be empty", f.yellow().bold());
+    }
+    Ok((species, genome, duplicates))
+}
+
+/// Apply the duplicate policy to the annotations of a file, and return the
+/// annotations to keep along with the duplicated IDs
+fn deduplicate(
+    f: &str,
+    annotations: Vec<Placed>,
+    policy: DuplicatePolicy,
+) -> Result<(Vec<Placed>, Vec<String>)> {
+    let mut occurrences = HashMap::<&str, Vec<usize>>::new();
+    for (i, (_, a)) in annotations.iter().enumerate() {
+        occurrences.entry(&a.id).or_default().push(i);
+    }
+    let mut duplicates = occurrences
+        .iter()
+        .filter(|(_, o)| o.len() > 1)
+        .map(|(id, _)| id.to_string())
+        .collect::<Vec<_>>();
+    duplicates.sort();
+    if duplicates.is_empty() {
+        return Ok((annotations, duplicates));
+    }
+    warn!(
+        "{} duplicated IDs in {}, e.g. {}",
+        duplicates.len(),
+        f.yellow().bold(),
+        duplicates[0].bold()
+    );
+
+    // For each annotation, whether to keep it and how many times its ID has
+    // been seen before
+    let mut rank = vec![0; annotations.len()];
+    let mut keep = vec![true; annotations.len()];
+    for o in occurrences.values().filter(|o| o.len() > 1) {
+        match policy {
+            DuplicatePolicy::Error => {
+                return Err(Error::DuplicateId(annotations[o[0]].1.id.clone(), f.into()).into())
+            }
+            DuplicatePolicy::KeepFirst => {
+                for i in o.iter().skip(1) {
+                    keep[*i] = false;
+                }
+            }
+            DuplicatePolicy::KeepLongest => {
+                let length = |i: usize| annotations[i].1.stop - annotations[i].1.start;
+                // Ties go to the first occurrence
+                let longest = *o
+                    .iter()
+                    .rev()
+                    .max_by_key(|i| length(**i))
+                    .expect("non-empty occurrences");
+                for i in o.iter().filter(|i| **i != longest) {
+                    keep[*i] = false;
+                }
+            }
+            DuplicatePolicy::KeepAllWithSuffix => {
+                for (n, i) in o.iter().enumerate() {
+                    rank[*i] = n;
+                }
+            }
         }
-    } else {
-        warn!("{} appears to be empty", species.yellow().bold());
     }
+
+    let annotations = annotations
+        .into_iter()
+        .zip(keep)
+        .zip(rank)
+        .filter(|((_, keep), _)| *keep)
+        .map(|(((chr, mut a), _), rank)| {
+            if rank > 0 {
+                a.id = format!("{}.{}", a.id, rank + 1);
+            }
+            (chr, a)
+        })
+        .collect();
+    Ok((annotations, duplicates))
+}
+
+fn sort_genome(species: &str, genome: &mut Genome) {
+    for (chr, ids) in genome.iter_mut() {
+        // Ties are broken by end then ID, so that the order never depends
+        // on that of the input
+        let cmp = |a: &Annotation, b: &Annotation| {
+            a.start
+                .cmp(&b.start)
+                .then(a.stop.cmp(&b.stop))
+                .then_with(|| a.id.cmp(&b.id))
+        };
+        if ids.is_sorted_by(|a, b| cmp(a, b).is_le()) {
+            trace!("{}/{} is already sorted", species, chr);
+        } else {
+            ids.sort_by(cmp);
+        }
+    }
+}
+
+/// Parse all the annotation files, spreading them over `threads` workers;
+/// parsed genomes are sent back to the calling thread to be merged, and each
+/// species is handed over to `sink` as soon as all its files are parsed, so
+/// that it is written while the next ones are being parsed.
+#[allow(clippy::too_many_arguments)]
+fn parse_genomes(
+    files: &[String],
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    ctx: &BuildContext,
+    threads: usize,
+    summary: &mut BuildSummary,
+    mut sink: impl FnMut(&HashMap<String, Genome>) -> Result<()>,
+) -> Result<()> {
+    let next = AtomicUsize::new(0);
+    let abort = AtomicBool::new(false);
+    let threads = threads.clamp(1, files.len().max(1));
+
+    // A species is complete once all the files it spans have been parsed
+    let mut pending = HashMap::<String, usize>::new();
+    for f in files.iter() {
+        let species = species_of(f, species_pattern)?;
+        let species = ctx
+            .options
+            .species_names
+            .get(&species)
+            .cloned()
+            .unwrap_or(species);
+        *pending.entry(species).or_default() += 1;
+    }
+    let mut genomes = HashMap::<String, Genome>::new();
+
+    std::thread::scope(|s| -> Result<()> {
+        // Bounded, so that parsers do not outrun the writer
+        let (tx, rx) = mpsc::sync_channel(threads);
+        for _ in 0..threads {
+            let tx = tx.clone();
+            let (next, abort) = (&next, &abort);
+            s.spawn(move || loop {
+                let i = next.fetch_add(1, Ordering::SeqCst);
+                if i >= files.len() || abort.load(Ordering::SeqCst) {
+                    break;
+                }
+                let r = parse_genome(&files[i], species_pattern, id_type, id_pattern, ctx);
+                if tx.send((i, r)).is_err() {
+                    break;
+                }
+            });
+        }
+        drop(tx);
+
+        let mut complete = |species: String, genomes: &mut HashMap<String, Genome>| {
+            let mut genome = genomes.remove(&species).unwrap_or_default();
+            let dropped = filter_chromosomes(&species, &mut genome, ctx.options)?;
+            if !dropped.is_empty() {
+                summary.dropped_chromosomes.insert(species.clone(), dropped);
+            }
+            sort_genome(&species, &mut genome);
+            summary
+                .genes
+                .insert(species.clone(), genome.values().map(|g| g.len()).sum());
+            sink(&HashMap::from([(species, genome)]))
+        };
+
+        for (done, (i, r)) in rx.into_iter().enumerate() {
+            let r = r.and_then(|(species, genome, mut duplicates)| {
+                progress::report(ctx.options.progress.as_ref(), || BuildEvent::FileParsed {
+                    file: files[i].clone(),
+                    species: species.clone(),
+                    genes: genome.values().map(|g| g.len()).sum(),
+                    done: done + 1,
+                    total: files.len(),
+                });
+                if !duplicates.is_empty() {
+                    summary
+                        .duplicates
+                        .entry(species.clone())
+                        .or_default()
+                        .append(&mut duplicates);
+                }
+                let merged = genomes.entry(species.clone()).or_default();
+                for (chr, mut ids) in genome.into_iter() {
+                    merged.entry(chr).or_default().append(&mut ids);
+                }
+                let left = pending.entry(species.clone()).or_default();
+                *left = left.saturating_sub(1);
+                if *left == 0 {
+                    complete(species, &mut genomes)
+                } else {
+                    Ok(())
+                }
+            });
+            if let Err(e) = r {
+                abort.store(true, Ordering::SeqCst);
+                return Err(e);
+            }
+        }
+        Ok(())
+    })?;
+
     Ok(())
 }
 
+/// Discard the chromosomes of `genome` excluded by the chromosome filters of
+/// `options`, and return their sorted names
+fn filter_chromosomes(
+    species: &str,
+    genome: &mut Genome,
+    options: &BuildOptions,
+) -> Result<Vec<String>> {
+    let exclude = options
+        .exclude_chromosomes
+        .as_ref()
+        .map(|re| {
+            Regex::new(re).map_err(|e| Error::InvalidRegex {
+                source: e,
+                re: re.to_owned(),
+            })
+        })
+        .transpose()?;
+    let mut dropped = genome
+        .iter()
+        .filter(|(chr, ids)| {
+            exclude.as_ref().is_some_and(|re| re.is_match(chr))
+                || ids.len() < options.min_chromosome_genes
+        })
+        .map(|(chr, _)| chr.to_owned())
+        .collect::<Vec<_>>();
+    dropped.sort();
+    for chr in dropped.iter() {
+        genome.remove(chr);
+    }
+    if !dropped.is_empty() {
+        info!(
+            "{}: dropped {} chromosomes, {} left",
+            species.bold(),
+            dropped.len(),
+            genome.len()
+        );
+    }
+    Ok(dropped)
+}
+
+/// Expand the given paths into the list of files they designate, reading
+/// the content of directories as specified by `filter`
+fn expand_paths(names: &[String], filter: &InputFilter) -> Result<Vec<String>> {
+    fn walk(
+        dir: &std::path::Path,
+        recursive: bool,
+        files: &mut Vec<std::path::PathBuf>,
+    ) -> Result<()> {
+        for e in dir
+            .read_dir()
+            .with_context(|| anyhow!("while reading {}", dir.display()))?
+        {
+            let path = e
+                .with_context(|| anyhow!("while reading {}", dir.display()))?
+                .path();
+            if path.is_dir() {
+                if recursive {
+                    walk(&path, recursive, files)?;
+                }
+            } else {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let globs = |patterns: &[String]| {
+        patterns
+            .iter()
+            .map(|p| {
+                glob::Pattern::new(p)
+                    .with_context(|| anyhow!("{} is not a valid glob", p.yellow().bold()))
+            })
+            .collect::<Result<Vec<_>>>()
+    };
+    let (include, exclude) = (globs(&filter.include)?, globs(&filter.exclude)?);
+
+    let mut r = Vec::new();
+    for name in names.iter() {
+        let path = std::path::Path::new(name);
+        if path.is_dir() {
+            let mut found = Vec::new();
+            walk(path, filter.recursive, &mut found)?;
+            let mut files = found
+                .into_iter()
+                .filter(|f| {
+                    let relative = f.strip_prefix(path).unwrap_or(f);
+                    (include.is_empty() || include.iter().any(|g| g.matches_path(relative)))
+                        && !exclude.iter().any(|g| g.matches_path(relative))
+                })
+                .map(|f| f.to_string_lossy().to_string())
+                .collect::<Vec<_>>();
+            files.sort();
+            r.extend(files);
+        } else {
+            r.push(name.to_owned());
+        }
+    }
+    Ok(r)
+}
+
+#[allow(clippy::too_many_arguments)]
 pub fn db_from_files(
     families: &[String],
     gffs: &[String],
@@ -270,134 +896,429 @@ pub fn db_from_files(
     id_type: &str,
     id_pattern: &str,
     window: isize,
-) -> Result<()> {
-    let mut current_ancestral_id = 1;
-    let mut id2ancestral = HashMap::new();
+    options: &BuildOptions,
+) -> Result<BuildSummary> {
+    build(
+        families,
+        gffs,
+        db_file,
+        species_pattern,
+        id_type,
+        id_pattern,
+        window,
+        options,
+    )
+    .map(|(_, summary)| summary)
+}
+
+/// Build a database as [`db_from_files`] would, but in memory rather than in
+/// a file; the returned connection can be handed to
+/// [`GeneBook::from_connection`](crate::genebook::GeneBook::from_connection).
+#[allow(clippy::too_many_arguments)]
+pub fn db_in_memory(
+    families: &[String],
+    gffs: &[String],
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    window: isize,
+    options: &BuildOptions,
+) -> Result<(Connection, BuildSummary)> {
+    let (conn, summary) = build(
+        families,
+        gffs,
+        ":memory:",
+        species_pattern,
+        id_type,
+        id_pattern,
+        window,
+        options,
+    )?;
+    let conn = match conn {
+        Some(conn) => conn,
+        None => Connection::open_in_memory()?,
+    };
+    Ok((conn, summary))
+}
+
+/// Build the database in `db_file`, and return the connection to it unless
+/// this is a dry run
+#[allow(clippy::too_many_arguments)]
+fn build(
+    families: &[String],
+    gffs: &[String],
+    db_file: &str,
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    window: isize,
+    options: &BuildOptions,
+) -> Result<(Option<Connection>, BuildSummary)> {
+    check_attributes(&options.attributes, options.layout)?;
+    let families = &expand_paths(families, &options.family_files)?;
+    let gffs = &expand_paths(gffs, &options.annotation_files)?;
+    let mut id2ancestral = Families::new(options.min_family_size);
     info!("Parsing families...");
-    for name in families.iter() {
-        let path = std::path::Path::new(name);
-        if path.is_dir() {
-            for f in path
-                .read_dir()
-                .with_context(|| anyhow!("while reading {}", name))?
-                .map(|e| {
-                    e.map(|e| e.path().to_str().unwrap().to_owned())
-                        .map_err(|_| todo!())
-                })
-            {
-                parse_family(
-                    f.unwrap().as_str(),
-                    &mut current_ancestral_id,
-                    &mut id2ancestral,
-                )?;
-            }
-        } else {
-            parse_family(name, &mut current_ancestral_id, &mut id2ancestral)?;
-        }
+    for f in families.iter() {
+        families::parse_families(f, &options.family_format, &mut id2ancestral)?;
+    }
+
+    info!("{} families found", id2ancestral.len());
+    if options.family_ids == FamilyIds::ContentHash {
+        id2ancestral.hash_ids()?;
     }
+    let mut summary = BuildSummary {
+        families: id2ancestral.len(),
+        ..Default::default()
+    };
+
+    let ctx = BuildContext {
+        id2ancestral,
+        assemblies: parse_assemblies(&options.agps)?,
+        aliases: parse_aliases(&options.chr_aliases)?,
+        options,
+    };
 
     info!("Parsing GFF3s...");
-    let mut genomes = HashMap::new();
-    for name in gffs.iter() {
-        let path = std::path::Path::new(name);
-        if path.is_dir() {
-            for f in path
-                .read_dir()
-                .with_context(|| anyhow!("while reading {}", name))?
-                .map(|e| {
-                    e.map(|e| e.path().to_str().unwrap().to_owned())
-                        .map_err(|_| todo!())
-                })
-            {
-                parse_genome(
-                    f.unwrap().as_str(),
-                    species_pattern,
-                    id_type,
-                    id_pattern,
-                    &mut genomes,
-                    &id2ancestral,
-                )?;
+    let threads = if options.threads == 0 {
+        std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+    } else {
+        options.threads
+    };
+    let mut conn = if options.dry_run {
+        None
+    } else {
+        info!("Creating database...");
+        let mut conn = Connection::open(db_file).map_err(|e| DataError::FailedToConnect {
+            source: e,
+            filename: db_file.into(),
+        })?;
+        options.tuning.apply(&conn)?;
+        match options.layout {
+            Layout::Flat => create_tables(&conn, &options.attributes)?,
+            Layout::Normalized => schema::create_tables(&conn, &options.attributes)?,
+        }
+        create_aliases(&conn)?;
+        let tx = conn.transaction()?;
+        insert_aliases(&tx, &ctx.aliases)?;
+        tx.commit()?;
+
+        inputs::create(&conn)?;
+        inputs::record(&conn, inputs::FAMILIES, families)?;
+        inputs::record(&conn, inputs::ANNOTATIONS, gffs)?;
+        inputs::record(&conn, inputs::AGP, &species_files(&options.agps))?;
+        inputs::record(&conn, inputs::ALIASES, &species_files(&options.chr_aliases))?;
+
+        conn.execute("pragma temp_store = memory;", [])
+            .with_context(|| "while setting temp_store")?;
+        let tx = conn.transaction()?;
+        schema::insert_families(&tx, &ctx.id2ancestral)?;
+        tx.commit()?;
+        if options.neighbors.is_some() {
+            create_neighbors(&conn)?;
+        }
+        Some(conn)
+    };
+
+    info!("Parsing GFF3s and filling database...");
+    parse_genomes(
+        gffs,
+        species_pattern,
+        id_type,
+        id_pattern,
+        &ctx,
+        threads,
+        &mut summary,
+        |genomes| {
+            let Some(conn) = conn.as_mut() else {
+                return Ok(());
+            };
+            let tx = conn.transaction()?;
+            match options.layout {
+                Layout::Flat => insert_genomes(
+                    &tx,
+                    genomes,
+                    window,
+                    &options.circular,
+                    &options.attributes,
+                    LandscapeStyle::of(options),
+                    options.progress.as_ref(),
+                )?,
+                Layout::Normalized => schema::insert_genomes(
+                    &tx,
+                    genomes,
+                    window,
+                    &options.circular,
+                    &options.attributes,
+                    LandscapeStyle::of(options),
+                    options.progress.as_ref(),
+                )?,
+            }
+            if let Some(extent) = options.neighbors {
+                insert_neighbors(&tx, genomes, extent, &options.circular)?;
+            }
+            tx.commit()?;
+            Ok(())
+        },
+    )?;
+
+    let Some(conn) = conn else {
+        let mut species = summary.genes.iter().collect::<Vec<_>>();
+        species.sort();
+        for (species, genes) in species.into_iter() {
+            info!("{}: {} genes", species.bold(), genes);
+            if let Some(dropped) = summary.dropped_chromosomes.get(species) {
+                info!("{}: dropped {}", species.bold(), dropped.join(", "));
             }
-        } else {
-            parse_genome(
-                name,
-                species_pattern,
-                id_type,
-                id_pattern,
-                &mut genomes,
-                &id2ancestral,
-            )?;
         }
+        info!("Dry run: {} left untouched", db_file.bold());
+        return Ok((None, summary));
+    };
+
+    info!("Creating DB indices...");
+    match options.layout {
+        Layout::Flat => create_indices(&conn)?,
+        Layout::Normalized => schema::create_indices(&conn)?,
     }
 
-    info!("Creating database...");
-    let mut conn = Connection::open(db_file).map_err(|e| DataError::FailedToConnect {
-        source: e,
-        filename: db_file.into(),
-    })?;
-    conn.execute("DROP TABLE IF EXISTS genomes;", [])
+    meta::create(&conn)?;
+    meta::set(&conn, meta::CRATE_VERSION, env!("CARGO_PKG_VERSION"))?;
+    meta::set(&conn, meta::SCHEMA_VERSION, SCHEMA_VERSION)?;
+    meta::set(&conn, meta::WINDOW, window)?;
+    meta::set(&conn, meta::ID_TYPE, id_type)?;
+    meta::set(&conn, meta::SPECIES_PATTERN, species_pattern)?;
+    meta::set(&conn, meta::ID_PATTERN, id_pattern)?;
+    meta::set(&conn, meta::COORDINATES, options.coordinates)?;
+    meta::set(&conn, meta::LAYOUT, options.layout)?;
+    meta::set(&conn, meta::FAMILY_IDS, options.family_ids)?;
+    meta::set(&conn, meta::ATTRIBUTES, options.attributes.join("\n"))?;
+    LandscapeStyle::of(options).record(&conn)?;
+    meta::set_species_names(&conn, &options.species_names)?;
+    meta::set_circular(&conn, &options.circular)?;
+    if let Some(extent) = options.neighbors {
+        meta::set(&conn, meta::NEIGHBORS, extent)?;
+    }
+    meta::set(&conn, meta::FAMILIES, families.join("\n"))?;
+    meta::set(&conn, meta::ANNOTATIONS, gffs.join("\n"))?;
+    meta::set(&conn, meta::CREATED, meta::now())?;
+
+    if options.optimize {
+        health::finish(&conn)?;
+    }
+    options.tuning.finish(&conn)?;
+
+    Ok((Some(conn), summary))
+}
+
+fn create_tables(conn: &Connection, attributes: &[String]) -> Result<()> {
+    for name in [
+        "genomes",
+        "genes",
+        "chromosomes",
+        "families",
+        "species",
+        "neighbors",
+    ] {
+        schema::drop_existing(conn, name).with_context(|| "while dropping table")?;
+    }
+    conn.execute(
+        &format!(
+            "CREATE TABLE genomes (
+                species text, chr text, ancestral_id integer, id text,
+                start integer, stop integer, direction char,
+                left_tail_ids text, right_tail_ids text{}
+            )",
+            attribute_columns(attributes, true)
+        ),
+        [],
+    )
+    .with_context(|| "while creating database")?;
+    conn.execute(
+        "CREATE TABLE families (id integer PRIMARY KEY, name text)",
+        [],
+    )
+    .with_context(|| "while creating database")?;
+    Ok(())
+}
+
+fn create_aliases(conn: &Connection) -> Result<()> {
+    conn.execute("DROP TABLE IF EXISTS chr_aliases;", [])
         .with_context(|| "while dropping table")?;
     conn.execute(
-        "CREATE TABLE genomes (
-            species text, chr text, ancestral_id integer, id text,
-            start integer, stop integer, direction char,
-            left_tail_ids text, right_tail_ids text
-        )",
+        "CREATE TABLE chr_aliases (species text, alias text, chr text, PRIMARY KEY (species, alias))",
         [],
     )
     .with_context(|| "while creating database")?;
-    info!("Filling database...");
-    conn.execute("pragma temp_store = memory;", [])
-        .with_context(|| "while setting temp_store")?;
-    for (species, genome) in genomes.iter() {
+    Ok(())
+}
+
+/// Store `aliases`; callers are expected to run it in a transaction
+fn insert_aliases(conn: &Connection, aliases: &ChromAliases) -> Result<()> {
+    let mut insert = conn.prepare_cached(
+        "INSERT OR REPLACE INTO chr_aliases (species, alias, chr) VALUES (?, ?, ?)",
+    )?;
+    for (species, alias, chr) in aliases.iter() {
+        insert.execute([species, alias, chr])?;
+    }
+    Ok(())
+}
+
+/// The dot-joined left and right landscapes of the `j`-th gene of `ids`,
+/// stored following `style`
+fn landscapes(
+    ids: &[Annotation],
+    j: usize,
+    window: isize,
+    circular: bool,
+    style: LandscapeStyle,
+) -> (String, String) {
+    let window = window.max(0) as usize;
+    let tail = |i: usize| TailGene {
+        family: ids[i].ancestral_id,
+        strand: ids[i].dir,
+        id: style.rich.then(|| ids[i].id.clone()),
+        pos: style.rich.then_some(ids[i].start),
+        copies: 1,
+    };
+    let join = |tails: Vec<TailGene>| {
+        tails
+            .iter()
+            .map(|t| t.encode())
+            .collect::<Vec<_>>()
+            .join(".")
+    };
+    if style.collapse {
+        let (left, right) =
+            collapsed_flanks(ids.len(), j, window, circular, |i| ids[i].ancestral_id);
+        let tails = |runs: Vec<Vec<usize>>| {
+            TailGene::collapse(runs.into_iter().flatten().map(tail).collect())
+        };
+        (join(tails(left)), join(tails(right)))
+    } else {
+        let (left, right) = flanks(ids.len(), j, window, circular);
+        let tails = |genes: Vec<usize>| genes.into_iter().map(tail).collect();
+        (join(tails(left)), join(tails(right)))
+    }
+}
+
+/// Insert `genomes` in the flat layout; callers are expected to run it in a
+/// transaction
+fn insert_genomes(
+    conn: &Connection,
+    genomes: &HashMap<String, Genome>,
+    window: isize,
+    circular: &Circular,
+    attributes: &[String],
+    style: LandscapeStyle,
+    progress: Option<&ProgressCallback>,
+) -> Result<()> {
+    let total = genomes.values().map(|g| g.len()).sum();
+    let (mut rows, mut done) = (0, 0);
+    let sql = format!(
+        "INSERT INTO genomes (species, chr, ancestral_id, id, start, stop, direction, left_tail_ids, right_tail_ids{}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?{})",
+        attribute_columns(attributes, false),
+        ", ?".repeat(attributes.len())
+    );
+    let mut genomes = genomes.iter().collect::<Vec<_>>();
+    genomes.sort_by(|a, b| a.0.cmp(b.0));
+    for (species, genome) in genomes.into_iter() {
         debug!("Inserting {}", species.bold());
-        for (chr, ids) in genome.iter() {
+        let mut genome = genome.iter().collect::<Vec<_>>();
+        genome.sort_by(|a, b| natural_cmp(a.0, b.0));
+        for (chr, ids) in genome.into_iter() {
             trace!("Inserting {}", chr.bold());
-            let tx = conn.transaction()?;
+            let circular = circular.contains(species, chr);
+            let mut insert = conn.prepare_cached(&sql)?;
             for (j, id) in ids.iter().enumerate() {
-                let j = j as isize;
-                let i = (0.max(j - window)) as usize;
-                let k = ((ids.len() as isize - 1).min(j + window)) as usize;
-                let left_landscape_ids = ids[i..j as usize]
-                    .iter()
-                    .map(|a| format!("{}{}", a.dir, a.ancestral_id))
-                    .collect::<Vec<_>>();
-                let right_landscape_ids = ids[j as usize + 1..=k]
-                    .iter()
-                    .map(|a| format!("{}{}", a.dir, a.ancestral_id))
-                    .collect::<Vec<_>>();
-                let insert = format!(
-                    "INSERT INTO genomes (species, chr, ancestral_id, id, start, stop, direction, left_tail_ids, right_tail_ids) VALUES ('{}','{}','{}','{}','{}','{}','{}','{}','{}')",
+                let (left_landscape_ids, right_landscape_ids) =
+                    landscapes(ids, j, window, circular, style);
+                let dir = String::from(id.dir);
+                let mut values: Vec<&dyn rusqlite::ToSql> = vec![
                     species,
                     chr,
-                    id.ancestral_id,
-                    id.id,
-                    id.start,
-                    id.stop,
-                    String::from(id.dir),
-                    left_landscape_ids
-                        .into_iter()
-                        .collect::<Vec<_>>()
-                        .join("."),
-                    right_landscape_ids
-                        .into_iter()
-                        .collect::<Vec<_>>()
-                        .join("."),
-                );
-                tx.execute(&insert, [])?;
+                    &id.ancestral_id,
+                    &id.id,
+                    &id.start,
+                    &id.stop,
+                    &dir,
+                    &left_landscape_ids,
+                    &right_landscape_ids,
+                ];
+                values.extend(id.attributes.iter().map(|a| a as &dyn rusqlite::ToSql));
+                insert.execute(values.as_slice())?;
             }
-            tx.commit()?;
+            rows += ids.len();
+            done += 1;
+            progress::report(progress, || BuildEvent::RowsInserted { rows });
+            progress::report(progress, || BuildEvent::ChromosomeDone {
+                species: species.to_string(),
+                chr: chr.to_string(),
+                done,
+                total,
+            });
         }
     }
+    Ok(())
+}
 
-    info!("Creating DB indices...");
+fn create_neighbors(conn: &Connection) -> Result<()> {
     conn.execute_batch(
-        "CREATE INDEX genomes_species ON genomes(species);
-         CREATE INDEX genomes_chr     ON genomes(chr);
-         CREATE INDEX genomes_id      ON genomes(id);
-         CREATE INDEX genomes_start   ON genomes(start);",
+        "CREATE TABLE IF NOT EXISTS neighbors (
+            gene_id text, offset integer, family integer, strand char
+         );
+         CREATE INDEX IF NOT EXISTS neighbors_gene ON neighbors(gene_id, offset);",
     )
-    .with_context(|| "while creating indices")?;
+    .with_context(|| "while creating neighbors table")
+}
 
+/// Store, for each gene, its `extent` closest neighbors on each side; left
+/// neighbors have negative offsets, right ones positive offsets. Callers are
+/// expected to run it in a transaction.
+fn insert_neighbors(
+    conn: &Connection,
+    genomes: &HashMap<String, Genome>,
+    extent: usize,
+    circular: &Circular,
+) -> Result<()> {
+    let mut insert = conn.prepare_cached(
+        "INSERT INTO neighbors (gene_id, offset, family, strand) VALUES (?, ?, ?, ?)",
+    )?;
+    for (species, genome) in genomes.iter() {
+        for (chr, ids) in genome.iter() {
+            let circular = circular.contains(species, chr);
+            for (j, id) in ids.iter().enumerate() {
+                let (left, right) = flanks(ids.len(), j, extent, circular);
+                let left = left
+                    .into_iter()
+                    .rev()
+                    .enumerate()
+                    .map(|(o, i)| (-(o as isize + 1), &ids[i]));
+                let right = right
+                    .into_iter()
+                    .enumerate()
+                    .map(|(o, i)| (o as isize + 1, &ids[i]));
+                for (offset, neighbor) in left.chain(right) {
+                    insert.execute(rusqlite::params![
+                        id.id,
+                        offset,
+                        neighbor.ancestral_id,
+                        String::from(neighbor.dir),
+                    ])?;
+                }
+            }
+        }
+    }
     Ok(())
 }
+
+fn create_indices(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS genomes_species ON genomes(species);
+         CREATE INDEX IF NOT EXISTS genomes_chr     ON genomes(chr);
+         CREATE INDEX IF NOT EXISTS genomes_id      ON genomes(id);
+         CREATE INDEX IF NOT EXISTS genomes_start   ON genomes(start);",
+    )
+    .with_context(|| "while creating indices")
+}
@@ -0,0 +1,53 @@
+//! Progress reporting for long-running builds.
+use std::sync::Arc;
+
+/// The steps of a database build reported to a [`ProgressCallback`]
+#[derive(Debug, Clone)]
+pub enum BuildEvent {
+    /// Some more records have been read from an annotation file
+    RecordsRead { file: String, records: usize },
+    /// An annotation file has been fully parsed
+    FileParsed {
+        file: String,
+        species: String,
+        genes: usize,
+        done: usize,
+        total: usize,
+    },
+    /// Rows have been written to the database, `rows` being the running total
+    /// for the species being written
+    RowsInserted { rows: usize },
+    /// All the genes of a chromosome have been written to the database, as
+    /// the `done`-th of the `total` chromosomes of its species
+    ChromosomeDone {
+        species: String,
+        chr: String,
+        done: usize,
+        total: usize,
+    },
+}
+
+/// How many records are read between two [`BuildEvent::RecordsRead`]
+pub(crate) const RECORDS_STEP: usize = 100_000;
+
+/// A function called on each step of a build, e.g. to drive a progress bar;
+/// it may be called from several threads at once.
+#[derive(Clone)]
+pub struct ProgressCallback(Arc<dyn Fn(&BuildEvent) + Send + Sync>);
+impl ProgressCallback {
+    pub fn new<F: Fn(&BuildEvent) + Send + Sync + 'static>(f: F) -> Self {
+        ProgressCallback(Arc::new(f))
+    }
+}
+impl std::fmt::Debug for ProgressCallback {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "ProgressCallback")
+    }
+}
+
+/// Report the event built by `event`, if there is a callback to report it to
+pub(crate) fn report<F: FnOnce() -> BuildEvent>(progress: Option<&ProgressCallback>, event: F) {
+    if let Some(p) = progress {
+        (p.0)(&event());
+    }
+}
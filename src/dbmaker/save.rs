@@ -0,0 +1,131 @@
+//! Writing the genes of an in-memory book back to a database.
+use anyhow::*;
+use log::*;
+use rusqlite::Connection;
+use std::collections::{BTreeMap, HashMap};
+
+use super::{
+    attribute_columns, check_attributes, create_aliases, create_indices, create_tables, inputs,
+    insert_aliases, meta, schema, LandscapeStyle, Layout, SCHEMA_VERSION,
+};
+use crate::{
+    aliases::ChromAliases,
+    genebook::{Gene, TailGene},
+    Coordinates,
+};
+
+/// The dot-joined form of a landscape, as stored in the database
+fn landscape(genes: &[TailGene]) -> String {
+    genes
+        .iter()
+        .map(|g| g.encode())
+        .collect::<Vec<_>>()
+        .join(".")
+}
+
+/// Replace the content of `conn` with `genes`, stored in the flat layout;
+/// the landscapes are stored as they are found in the genes, and the window
+/// recorded is the width of the widest of them. The database is left
+/// untouched if saving fails.
+pub(crate) fn save(
+    conn: &mut Connection,
+    genes: &HashMap<String, Gene>,
+    aliases: &ChromAliases,
+    coordinates: Coordinates,
+) -> Result<()> {
+    let mut attributes = genes
+        .values()
+        .flat_map(|g| g.attributes.keys().cloned())
+        .collect::<Vec<_>>();
+    attributes.sort();
+    attributes.dedup();
+    check_attributes(&attributes, Layout::Flat)?;
+    let window = genes
+        .values()
+        .map(|g| g.left_landscape.len().max(g.right_landscape.len()))
+        .max()
+        .unwrap_or_default();
+    let tails = || {
+        genes
+            .values()
+            .flat_map(|g| g.left_landscape.iter().chain(g.right_landscape.iter()))
+    };
+    let style = LandscapeStyle {
+        rich: tails().any(|t| t.id.is_some()),
+        collapse: tails().any(|t| t.copies > 1),
+    };
+
+    let tx = conn.transaction()?;
+    // How the genes were read still describes them, unlike the inputs
+    let kept = [
+        meta::ID_TYPE,
+        meta::SPECIES_PATTERN,
+        meta::ID_PATTERN,
+        meta::FAMILY_IDS,
+    ]
+    .into_iter()
+    .map(|key| Ok((key, meta::get(&tx, key)?)))
+    .collect::<Result<Vec<_>>>()?;
+    for name in ["inputs", "meta"] {
+        schema::drop_existing(&tx, name).with_context(|| "while dropping table")?;
+    }
+    create_tables(&tx, &attributes)?;
+    create_aliases(&tx)?;
+    insert_aliases(&tx, aliases)?;
+
+    let mut genes = genes.values().collect::<Vec<_>>();
+    genes.sort_by(|a, b| {
+        (&a.species, &a.chr, a.pos, &a.id).cmp(&(&b.species, &b.chr, b.pos, &b.id))
+    });
+    let families = genes
+        .iter()
+        .map(|g| (g.family, g.family_name.as_deref()))
+        .collect::<BTreeMap<_, _>>();
+
+    debug!("Saving {} genes", genes.len());
+    {
+        let mut insert = tx.prepare_cached("INSERT INTO families (id, name) VALUES (?, ?)")?;
+        for (id, name) in families.iter() {
+            insert.execute(rusqlite::params![id, name])?;
+        }
+
+        let mut insert = tx.prepare_cached(&format!(
+            "INSERT INTO genomes (species, chr, ancestral_id, id, start, stop, direction, left_tail_ids, right_tail_ids{}) VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?{})",
+            attribute_columns(&attributes, false),
+            ", ?".repeat(attributes.len())
+        ))?;
+        for g in genes.iter() {
+            let direction = g.strand.to_string();
+            let left = landscape(&g.left_landscape);
+            let right = landscape(&g.right_landscape);
+            let attributes = attributes
+                .iter()
+                .map(|a| g.attributes.get(a))
+                .collect::<Vec<_>>();
+            let mut values: Vec<&dyn rusqlite::ToSql> = vec![
+                &g.species, &g.chr, &g.family, &g.id, &g.pos, &g.end, &direction, &left, &right,
+            ];
+            values.extend(attributes.iter().map(|a| a as &dyn rusqlite::ToSql));
+            insert.execute(values.as_slice())?;
+        }
+    }
+    create_indices(&tx)?;
+
+    meta::create(&tx)?;
+    meta::set(&tx, meta::CRATE_VERSION, env!("CARGO_PKG_VERSION"))?;
+    meta::set(&tx, meta::SCHEMA_VERSION, SCHEMA_VERSION)?;
+    meta::set(&tx, meta::WINDOW, window)?;
+    meta::set(&tx, meta::COORDINATES, coordinates)?;
+    meta::set(&tx, meta::LAYOUT, Layout::Flat)?;
+    meta::set(&tx, meta::ATTRIBUTES, attributes.join("\n"))?;
+    style.record(&tx)?;
+    for (key, value) in kept {
+        if let Some(value) = value {
+            meta::set(&tx, key, value)?;
+        }
+    }
+    meta::set(&tx, meta::CREATED, meta::now())?;
+    inputs::create(&tx)?;
+    tx.commit()?;
+    Ok(())
+}
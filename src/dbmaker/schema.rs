@@ -0,0 +1,194 @@
+//! The normalized layout of the database, where species, chromosomes and
+//! families are stored in their own tables and referred to by integer IDs.
+//! A `genomes` view exposes the same columns as the flat layout, so that
+//! readers do not have to care about which one is in use.
+use anyhow::*;
+use log::*;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+use super::{
+    attribute_columns, landscapes,
+    progress::{self, BuildEvent, ProgressCallback},
+    Circular, Families, Genome, LandscapeStyle,
+};
+use crate::interval::natural_cmp;
+
+/// How the genes are laid out in the database
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Layout {
+    /// A single `genomes` table, as historically produced
+    #[default]
+    Flat,
+    /// Separate `species`, `chromosomes`, `families` and `genes` tables
+    /// linked by foreign keys, behind a `genomes` view
+    Normalized,
+}
+impl std::fmt::Display for Layout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Layout::Flat => write!(f, "flat"),
+            Layout::Normalized => write!(f, "normalized"),
+        }
+    }
+}
+impl TryFrom<&str> for Layout {
+    type Error = anyhow::Error;
+
+    fn try_from(s: &str) -> Result<Self> {
+        match s {
+            "flat" => Ok(Layout::Flat),
+            "normalized" => Ok(Layout::Normalized),
+            _ => bail!("unknown database layout: {}", s),
+        }
+    }
+}
+
+/// Drop the table or view `name`, if it exists
+pub(crate) fn drop_existing(conn: &Connection, name: &str) -> Result<()> {
+    let kind = conn
+        .prepare("SELECT type FROM sqlite_master WHERE name=?")?
+        .query_map([name], |r| r.get::<_, String>(0))?
+        .next()
+        .transpose()?;
+    match kind.as_deref() {
+        Some("table") => conn.execute(&format!("DROP TABLE {name}"), [])?,
+        Some("view") => conn.execute(&format!("DROP VIEW {name}"), [])?,
+        _ => 0,
+    };
+    Ok(())
+}
+
+pub(crate) fn create_tables(conn: &Connection, attributes: &[String]) -> Result<()> {
+    for name in [
+        "genomes",
+        "genes",
+        "chromosomes",
+        "families",
+        "species",
+        "neighbors",
+    ] {
+        drop_existing(conn, name).with_context(|| "while dropping table")?;
+    }
+    conn.execute_batch(&format!(
+        "CREATE TABLE species (id integer PRIMARY KEY, name text UNIQUE NOT NULL);
+         CREATE TABLE chromosomes (
+            id integer PRIMARY KEY,
+            species_id integer NOT NULL REFERENCES species(id),
+            name text NOT NULL,
+            UNIQUE (species_id, name)
+         );
+         CREATE TABLE families (id integer PRIMARY KEY, name text);
+         CREATE TABLE genes (
+            id integer PRIMARY KEY,
+            name text NOT NULL,
+            chromosome_id integer NOT NULL REFERENCES chromosomes(id),
+            family_id integer NOT NULL REFERENCES families(id),
+            start integer, stop integer, direction char,
+            left_tail_ids text, right_tail_ids text{}
+         );
+         CREATE VIEW genomes AS
+            SELECT species.name AS species, chromosomes.name AS chr,
+                   genes.family_id AS ancestral_id, genes.name AS id,
+                   genes.start, genes.stop, genes.direction,
+                   genes.left_tail_ids, genes.right_tail_ids{}
+            FROM genes
+            JOIN chromosomes ON genes.chromosome_id = chromosomes.id
+            JOIN species ON chromosomes.species_id = species.id;",
+        attribute_columns(attributes, true),
+        attribute_columns(attributes, false),
+    ))
+    .with_context(|| "while creating database")?;
+    Ok(())
+}
+
+/// Register the families, those already present being left untouched;
+/// callers are expected to run it in a transaction
+pub(crate) fn insert_families(conn: &Connection, families: &Families) -> Result<()> {
+    let mut ids = families.id2family.values().copied().collect::<Vec<_>>();
+    ids.sort();
+    ids.dedup();
+    let mut insert =
+        conn.prepare_cached("INSERT OR IGNORE INTO families (id, name) VALUES (?, ?)")?;
+    for id in ids {
+        insert.execute(rusqlite::params![id, families.names.get(&id)])?;
+    }
+    Ok(())
+}
+
+/// Insert `genomes` in the normalized layout; callers are expected to run
+/// it in a transaction
+pub(crate) fn insert_genomes(
+    conn: &Connection,
+    genomes: &HashMap<String, Genome>,
+    window: isize,
+    circular: &Circular,
+    attributes: &[String],
+    style: LandscapeStyle,
+    progress: Option<&ProgressCallback>,
+) -> Result<()> {
+    let total = genomes.values().map(|g| g.len()).sum();
+    let (mut rows, mut done) = (0, 0);
+    let sql = format!(
+        "INSERT INTO genes (name, chromosome_id, family_id, start, stop, direction, left_tail_ids, right_tail_ids{}) VALUES (?, ?, ?, ?, ?, ?, ?, ?{})",
+        attribute_columns(attributes, false),
+        ", ?".repeat(attributes.len())
+    );
+    let mut genomes = genomes.iter().collect::<Vec<_>>();
+    genomes.sort_by(|a, b| a.0.cmp(b.0));
+    for (species, genome) in genomes.into_iter() {
+        debug!("Inserting {}", species);
+        conn.execute("INSERT INTO species (name) VALUES (?)", [species])?;
+        let species_id = conn.last_insert_rowid();
+
+        let mut genome = genome.iter().collect::<Vec<_>>();
+        genome.sort_by(|a, b| natural_cmp(a.0, b.0));
+        for (chr, ids) in genome.into_iter() {
+            trace!("Inserting {}", chr);
+            conn.execute(
+                "INSERT INTO chromosomes (species_id, name) VALUES (?, ?)",
+                rusqlite::params![species_id, chr],
+            )?;
+            let chr_id = conn.last_insert_rowid();
+            let circular = circular.contains(species, chr);
+
+            let mut insert = conn.prepare_cached(&sql)?;
+            for (j, id) in ids.iter().enumerate() {
+                let (left, right) = landscapes(ids, j, window, circular, style);
+                let dir = String::from(id.dir);
+                let mut values: Vec<&dyn rusqlite::ToSql> = vec![
+                    &id.id,
+                    &chr_id,
+                    &id.ancestral_id,
+                    &id.start,
+                    &id.stop,
+                    &dir,
+                    &left,
+                    &right,
+                ];
+                values.extend(id.attributes.iter().map(|a| a as &dyn rusqlite::ToSql));
+                insert.execute(values.as_slice())?;
+            }
+            rows += ids.len();
+            done += 1;
+            progress::report(progress, || BuildEvent::RowsInserted { rows });
+            progress::report(progress, || BuildEvent::ChromosomeDone {
+                species: species.to_string(),
+                chr: chr.to_string(),
+                done,
+                total,
+            });
+        }
+    }
+    Ok(())
+}
+
+pub(crate) fn create_indices(conn: &Connection) -> Result<()> {
+    conn.execute_batch(
+        "CREATE INDEX IF NOT EXISTS genes_name       ON genes(name);
+         CREATE INDEX IF NOT EXISTS genes_chromosome ON genes(chromosome_id, start);
+         CREATE INDEX IF NOT EXISTS genes_family     ON genes(family_id);
+         CREATE INDEX IF NOT EXISTS chromosomes_species ON chromosomes(species_id);",
+    )
+    .with_context(|| "while creating indices")
+}
@@ -0,0 +1,108 @@
+use anyhow::*;
+use rusqlite::Connection;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JournalMode {
+    Delete,
+    Truncate,
+    Persist,
+    Memory,
+    Wal,
+    Off,
+}
+impl JournalMode {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            JournalMode::Delete => "DELETE",
+            JournalMode::Truncate => "TRUNCATE",
+            JournalMode::Persist => "PERSIST",
+            JournalMode::Memory => "MEMORY",
+            JournalMode::Wal => "WAL",
+            JournalMode::Off => "OFF",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Synchronous {
+    Off,
+    Normal,
+    Full,
+    Extra,
+}
+impl Synchronous {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            Synchronous::Off => "OFF",
+            Synchronous::Normal => "NORMAL",
+            Synchronous::Full => "FULL",
+            Synchronous::Extra => "EXTRA",
+        }
+    }
+}
+
+/// SQLite settings applied to the database while it is being built; unset
+/// fields keep the SQLite defaults.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DbTuning {
+    pub journal_mode: Option<JournalMode>,
+    pub synchronous: Option<Synchronous>,
+    /// As in `PRAGMA cache_size`, i.e. in pages if positive, in KiB if negative
+    pub cache_size: Option<i64>,
+    /// Only effective when the database file is created
+    pub page_size: Option<usize>,
+}
+impl DbTuning {
+    /// Settings trading durability during the build for insertion speed
+    pub fn fast() -> Self {
+        DbTuning {
+            journal_mode: Some(JournalMode::Wal),
+            synchronous: Some(Synchronous::Normal),
+            cache_size: Some(-256 * 1024),
+            page_size: None,
+        }
+    }
+
+    pub(crate) fn apply(&self, conn: &Connection) -> Result<()> {
+        // The page size must be set before anything is written
+        if let Some(page_size) = self.page_size {
+            conn.pragma_update(None, "page_size", page_size)
+                .with_context(|| "while setting page_size")?;
+        }
+        if let Some(journal_mode) = self.journal_mode {
+            conn.pragma_update_and_check(None, "journal_mode", journal_mode.as_sql(), |_| {
+                rusqlite::Result::Ok(())
+            })
+            .with_context(|| "while setting journal_mode")?;
+        }
+        if let Some(synchronous) = self.synchronous {
+            conn.pragma_update(None, "synchronous", synchronous.as_sql())
+                .with_context(|| "while setting synchronous")?;
+        }
+        if let Some(cache_size) = self.cache_size {
+            conn.pragma_update(None, "cache_size", cache_size)
+                .with_context(|| "while setting cache_size")?;
+        }
+        Ok(())
+    }
+
+    /// Undo the settings persisting in the database file once it is built,
+    /// i.e. checkpoint a WAL journal and go back to the default one, so that
+    /// the database is a single file that can be opened read-only
+    pub(crate) fn finish(&self, conn: &Connection) -> Result<()> {
+        if self.journal_mode == Some(JournalMode::Wal) {
+            conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| {
+                rusqlite::Result::Ok(())
+            })
+            .with_context(|| "while checkpointing the WAL journal")?;
+            conn.pragma_update_and_check(
+                None,
+                "journal_mode",
+                JournalMode::Delete.as_sql(),
+                |_| rusqlite::Result::Ok(()),
+            )
+            .with_context(|| "while resetting journal_mode")?;
+        }
+        Ok(())
+    }
+}
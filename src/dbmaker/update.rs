@@ -0,0 +1,509 @@
+//! Operations updating an existing database in place.
+use anyhow::*;
+use colored::Colorize;
+use log::*;
+use rusqlite::Connection;
+use std::collections::{HashMap, HashSet};
+
+use super::{
+    collapsed_flanks, create_indices, families, filter_chromosomes, flanks, inputs, insert_aliases,
+    insert_genomes, insert_neighbors, meta, migrate, parse_aliases, parse_assemblies, parse_genome,
+    schema, sort_genome, BuildContext, BuildOptions, Circular, Error, Families, FamilyIds, Genome,
+    LandscapeStyle, Layout,
+};
+use crate::{
+    aliases::ChromAliases,
+    errors::DataError,
+    genebook::{FamilyID, TailGene},
+    Coordinates,
+};
+
+pub(crate) fn connect(db_file: &str) -> Result<Connection> {
+    Ok(
+        Connection::open(db_file).map_err(|e| DataError::FailedToConnect {
+            source: e,
+            filename: db_file.into(),
+        })?,
+    )
+}
+
+/// Connect to a database to be modified, which must use the current schema
+fn open_current(db_file: &str) -> Result<Connection> {
+    let conn = connect(db_file)?;
+    let found = migrate::schema_version(&conn)?;
+    if found != migrate::SCHEMA_VERSION {
+        return Err(DataError::UnsupportedSchema {
+            filename: db_file.into(),
+            found,
+            min: migrate::SCHEMA_VERSION,
+            max: migrate::SCHEMA_VERSION,
+        }
+        .into());
+    }
+    Ok(conn)
+}
+
+/// The layout of an open database
+pub(crate) fn layout(conn: &Connection) -> Result<Layout> {
+    Ok(meta::get(conn, meta::LAYOUT)?
+        .map(|l| Layout::try_from(l.as_str()))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Connect to a database to be modified, which must use the current schema
+/// and the flat layout
+fn open_flat(db_file: &str) -> Result<Connection> {
+    let conn = open_current(db_file)?;
+    let layout = layout(&conn)?;
+    if layout != Layout::Flat {
+        return Err(Error::UnsupportedLayout(db_file.into(), layout).into());
+    }
+    Ok(conn)
+}
+
+/// A parsed genome, ready to be inserted in an existing database
+struct NewSpecies {
+    species: String,
+    genome: Genome,
+    aliases: ChromAliases,
+    families: Families,
+}
+
+/// Parse the genome described in `annotations`, with the families in
+/// `families` matched against those of the database: a family sharing genes
+/// with an existing one inherits its ID, the others are given fresh IDs.
+#[allow(clippy::too_many_arguments)]
+fn parse_species(
+    conn: &Connection,
+    annotations: &str,
+    families: &[String],
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    options: &BuildOptions,
+) -> Result<NewSpecies> {
+    let mut id2ancestral = Families::new(options.min_family_size);
+    for f in super::expand_paths(families, &options.family_files)?.iter() {
+        families::parse_families(f, &options.family_format, &mut id2ancestral)?;
+    }
+
+    let existing = conn
+        .prepare("SELECT id, ancestral_id FROM genomes")?
+        .query_map([], |r| {
+            std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, FamilyID>(1)?))
+        })?
+        .collect::<Result<HashMap<_, _>, _>>()?;
+    // Families may outlive their genes, e.g. once a species is removed, so
+    // fresh IDs must follow all the registered ones
+    let mut next_family =
+        conn.query_row("SELECT COALESCE(MAX(id), 0) + 1 FROM families", [], |r| {
+            r.get::<_, FamilyID>(0)
+        })?;
+    let family_ids = meta::get(conn, meta::FAMILY_IDS)?
+        .map(|f| FamilyIds::try_from(f.as_str()))
+        .transpose()?
+        .unwrap_or_default();
+
+    let mut mapping = HashMap::new();
+    for (family, members) in id2ancestral.families() {
+        // Follow the existing family holding most of the members, if any
+        let mut votes = HashMap::<FamilyID, usize>::new();
+        for member in members.iter() {
+            if let Some(f) = existing.get(*member) {
+                *votes.entry(*f).or_default() += 1;
+            }
+        }
+        let target = votes
+            .into_iter()
+            .max_by_key(|(f, n)| (*n, std::cmp::Reverse(*f)))
+            .map(|(f, _)| f)
+            .unwrap_or_else(|| match family_ids {
+                FamilyIds::Sequential => {
+                    next_family += 1;
+                    next_family - 1
+                }
+                FamilyIds::ContentHash => families::content_hash(&members),
+            });
+        mapping.insert(family, target);
+    }
+    id2ancestral.reassign(&mapping);
+
+    // The new genome must fill the attribute columns of the database, and
+    // follow its naming of the species and its coordinate convention
+    let mut species_names = meta::species_names(conn)?;
+    species_names.extend(options.species_names.clone());
+    let coordinates = meta::get(conn, meta::COORDINATES)?
+        .map(|c| Coordinates::try_from(c.as_str()))
+        .transpose()?
+        .unwrap_or(options.coordinates);
+    let options = &BuildOptions {
+        attributes: meta::get_list(conn, meta::ATTRIBUTES)?,
+        species_names,
+        coordinates,
+        ..options.clone()
+    };
+    let ctx = BuildContext {
+        id2ancestral,
+        assemblies: parse_assemblies(&options.agps)?,
+        aliases: parse_aliases(&options.chr_aliases)?,
+        options,
+    };
+    let (species, mut genome, _) =
+        parse_genome(annotations, species_pattern, id_type, id_pattern, &ctx)?;
+    filter_chromosomes(&species, &mut genome, options)?;
+    sort_genome(&species, &mut genome);
+    Ok(NewSpecies {
+        species,
+        genome,
+        aliases: ctx.aliases,
+        families: ctx.id2ancestral,
+    })
+}
+
+fn has_species(conn: &Connection, species: &str) -> Result<bool> {
+    Ok(conn
+        .prepare("SELECT 1 FROM genomes WHERE species=?")?
+        .exists([species])?)
+}
+
+/// Keep the neighbors table, if any, in sync with newly inserted genomes
+fn update_neighbors(
+    conn: &Connection,
+    genomes: &HashMap<String, Genome>,
+    circular: &Circular,
+) -> Result<()> {
+    if let Some(extent) = meta::get(conn, meta::NEIGHBORS)? {
+        insert_neighbors(conn, genomes, extent.parse()?, circular)?;
+    }
+    Ok(())
+}
+
+/// Ensure that `window` is the one the landscapes of the database were
+/// built with, so that all of them span the same number of genes
+fn check_window(conn: &Connection, db_file: &str, window: isize) -> Result<()> {
+    if let Some(stored) = meta::get(conn, meta::WINDOW)? {
+        ensure!(
+            stored.parse::<isize>().ok() == Some(window),
+            "{} was built with a window of {}, not {}; see `rewindow`",
+            db_file.yellow().bold(),
+            stored,
+            window
+        );
+    }
+    Ok(())
+}
+
+fn delete_neighbors(conn: &Connection, species: &str) -> Result<()> {
+    if meta::get(conn, meta::NEIGHBORS)?.is_some() {
+        conn.execute(
+            "DELETE FROM neighbors WHERE gene_id IN (SELECT id FROM genomes WHERE species=?)",
+            [species],
+        )?;
+    }
+    Ok(())
+}
+
+/// Add the genome described in `annotations` to an existing database, its
+/// genes being assigned to the existing families wherever possible. The
+/// coordinate convention of the database is followed whatever
+/// [`BuildOptions::coordinates`], and `window` must be the one it was built
+/// with.
+#[allow(clippy::too_many_arguments)]
+pub fn add_species(
+    db_file: &str,
+    annotations: &str,
+    families: &[String],
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    window: isize,
+    options: &BuildOptions,
+) -> Result<()> {
+    let mut conn = open_flat(db_file)?;
+    check_window(&conn, db_file, window)?;
+    let NewSpecies {
+        species,
+        genome,
+        aliases,
+        families,
+    } = parse_species(
+        &conn,
+        annotations,
+        families,
+        species_pattern,
+        id_type,
+        id_pattern,
+        options,
+    )?;
+    if has_species(&conn, &species)? {
+        bail!(
+            "{} is already present in {}",
+            species.yellow().bold(),
+            db_file.yellow().bold()
+        );
+    }
+
+    info!("Adding {} to {}", species.bold(), db_file.bold());
+    let tx = conn.transaction()?;
+    insert_aliases(&tx, &aliases)?;
+    schema::insert_families(&tx, &families)?;
+    let genomes = HashMap::from([(species, genome)]);
+    let attributes = meta::get_list(&tx, meta::ATTRIBUTES)?;
+    let style = LandscapeStyle::stored(&tx)?;
+    let mut circular = meta::circular(&tx)?;
+    circular.extend(&options.circular);
+    insert_genomes(
+        &tx,
+        &genomes,
+        window,
+        &circular,
+        &attributes,
+        style,
+        options.progress.as_ref(),
+    )?;
+    update_neighbors(&tx, &genomes, &circular)?;
+    meta::set_circular(&tx, &circular)?;
+    create_indices(&tx)?;
+    meta::append(&tx, meta::ANNOTATIONS, &[annotations.to_owned()])?;
+    if !options.species_names.is_empty() {
+        let mut species_names = meta::species_names(&tx)?;
+        species_names.extend(options.species_names.clone());
+        meta::set_species_names(&tx, &species_names)?;
+    }
+    meta::set(&tx, meta::UPDATED, meta::now())?;
+    inputs::record(&tx, inputs::ANNOTATIONS, &[annotations.to_owned()])?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Remove all the genes of `species` from an existing database, within a
+/// single transaction
+pub fn remove_species(db_file: &str, species: &str) -> Result<()> {
+    let mut conn = open_flat(db_file)?;
+    let tx = conn.transaction()?;
+    delete_neighbors(&tx, species)?;
+    let removed = tx.execute("DELETE FROM genomes WHERE species=?", [species])?;
+    if removed == 0 {
+        bail!(
+            "{} is not present in {}",
+            species.yellow().bold(),
+            db_file.yellow().bold()
+        );
+    }
+    inputs::forget_species(&tx, species)?;
+    meta::set(&tx, meta::UPDATED, meta::now())?;
+    tx.commit()?;
+    info!("Removed {} genes of {}", removed, species.bold());
+
+    Ok(())
+}
+
+/// Replace the genome of an existing species by the one described in
+/// `annotations`, e.g. after a re-annotation. The new file is fully parsed
+/// before the database is modified, and the database is left untouched if
+/// the replacement fails. As for [`add_species`], the coordinate convention
+/// and window of the database are kept.
+#[allow(clippy::too_many_arguments)]
+pub fn replace_species(
+    db_file: &str,
+    annotations: &str,
+    families: &[String],
+    species_pattern: &str,
+    id_type: &str,
+    id_pattern: &str,
+    window: isize,
+    options: &BuildOptions,
+) -> Result<()> {
+    let mut conn = open_flat(db_file)?;
+    check_window(&conn, db_file, window)?;
+    let NewSpecies {
+        species,
+        genome,
+        aliases,
+        families,
+    } = parse_species(
+        &conn,
+        annotations,
+        families,
+        species_pattern,
+        id_type,
+        id_pattern,
+        options,
+    )?;
+    if !has_species(&conn, &species)? {
+        bail!(
+            "{} is not present in {}",
+            species.yellow().bold(),
+            db_file.yellow().bold()
+        );
+    }
+
+    info!("Replacing {} in {}", species.bold(), db_file.bold());
+    // The species must not be lost if anything below fails
+    let tx = conn.transaction()?;
+    delete_neighbors(&tx, &species)?;
+    tx.execute("DELETE FROM genomes WHERE species=?", [&species])?;
+    inputs::forget_species(&tx, &species)?;
+    insert_aliases(&tx, &aliases)?;
+    schema::insert_families(&tx, &families)?;
+    let genomes = HashMap::from([(species, genome)]);
+    let attributes = meta::get_list(&tx, meta::ATTRIBUTES)?;
+    let style = LandscapeStyle::stored(&tx)?;
+    let mut circular = meta::circular(&tx)?;
+    circular.extend(&options.circular);
+    insert_genomes(
+        &tx,
+        &genomes,
+        window,
+        &circular,
+        &attributes,
+        style,
+        options.progress.as_ref(),
+    )?;
+    update_neighbors(&tx, &genomes, &circular)?;
+    meta::set_circular(&tx, &circular)?;
+    meta::append(&tx, meta::ANNOTATIONS, &[annotations.to_owned()])?;
+    if !options.species_names.is_empty() {
+        let mut species_names = meta::species_names(&tx)?;
+        species_names.extend(options.species_names.clone());
+        meta::set_species_names(&tx, &species_names)?;
+    }
+    meta::set(&tx, meta::UPDATED, meta::now())?;
+    inputs::record(&tx, inputs::ANNOTATIONS, &[annotations.to_owned()])?;
+    tx.commit()?;
+
+    Ok(())
+}
+
+/// Regenerate the landscapes of all the genes in an existing database for a
+/// new window size, following the order of the genes stored on each
+/// chromosome.
+pub fn rewindow(db_file: &str, window: isize) -> Result<()> {
+    let mut conn = open_flat(db_file)?;
+    let circular = meta::circular(&conn)?;
+    let style = LandscapeStyle::stored(&conn)?;
+    let chrs = conn
+        .prepare("SELECT DISTINCT species, chr FROM genomes ORDER BY species, chr")?
+        .query_map([], |r| {
+            std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (species, chr) in chrs.iter() {
+        trace!("Rewindowing {}/{}", species, chr.bold());
+        let tx = conn.transaction()?;
+        {
+            let genes = tx
+                .prepare(
+                    "SELECT rowid, direction, ancestral_id, id, start FROM genomes
+                     WHERE species=? AND chr=? ORDER BY start, stop, id",
+                )?
+                .query_map([species, chr], |r| {
+                    std::result::Result::Ok((
+                        r.get::<_, i64>(0)?,
+                        TailGene {
+                            family: r.get::<_, FamilyID>(2)?,
+                            strand: r
+                                .get::<_, String>(1)?
+                                .as_str()
+                                .try_into()
+                                .unwrap_or_default(),
+                            id: style.rich.then(|| r.get::<_, String>(3)).transpose()?,
+                            pos: style.rich.then(|| r.get::<_, usize>(4)).transpose()?,
+                            copies: 1,
+                        },
+                    ))
+                })?
+                .collect::<Result<Vec<_>, _>>()?;
+
+            let mut update = tx.prepare_cached(
+                "UPDATE genomes SET left_tail_ids=?, right_tail_ids=? WHERE rowid=?",
+            )?;
+            let circular = circular.contains(species, chr);
+            let landscape = |indices: Vec<usize>| {
+                let tails = indices.into_iter().map(|i| genes[i].1.clone()).collect();
+                let tails = if style.collapse {
+                    TailGene::collapse(tails)
+                } else {
+                    tails
+                };
+                tails
+                    .iter()
+                    .map(|t| t.encode())
+                    .collect::<Vec<_>>()
+                    .join(".")
+            };
+            let window = window.max(0) as usize;
+            for (j, (rowid, _)) in genes.iter().enumerate() {
+                let (left, right) = if style.collapse {
+                    let (left, right) =
+                        collapsed_flanks(genes.len(), j, window, circular, |i| genes[i].1.family);
+                    (
+                        left.into_iter().flatten().collect(),
+                        right.into_iter().flatten().collect(),
+                    )
+                } else {
+                    flanks(genes.len(), j, window, circular)
+                };
+                update.execute(rusqlite::params![landscape(left), landscape(right), rowid])?;
+            }
+        }
+        tx.commit()?;
+    }
+    info!("Rewindowed {} chromosomes to {}", chrs.len(), window);
+    meta::set(&conn, meta::WINDOW, window)?;
+    meta::set(&conn, meta::UPDATED, meta::now())?;
+
+    Ok(())
+}
+
+/// Rename species in an existing database following `names`, mapping their
+/// current names to new ones
+pub fn rename_species(db_file: &str, names: &HashMap<String, String>) -> Result<()> {
+    let mut conn = open_current(db_file)?;
+    let table = match layout(&conn)? {
+        Layout::Flat => "UPDATE genomes SET species=?2 WHERE species=?1",
+        Layout::Normalized => "UPDATE species SET name=?2 WHERE name=?1",
+    };
+
+    let tx = conn.transaction()?;
+    for (from, to) in names.iter() {
+        if from == to {
+            continue;
+        }
+        if has_species(&tx, to)? {
+            bail!(
+                "can not rename {} to {}: {} is already present",
+                from.yellow().bold(),
+                to.yellow().bold(),
+                to.yellow().bold()
+            );
+        }
+        let renamed = tx.execute(table, [from, to])?;
+        if renamed == 0 {
+            warn!("{} is not present in {}", from.yellow().bold(), db_file);
+        } else {
+            info!("Renamed {} to {}", from.bold(), to.bold());
+        }
+    }
+
+    // Keep track of the names, so that annotation files can still be mapped
+    // to their species
+    let mut mapping = meta::species_names(&tx)?;
+    let displayed = mapping.values().cloned().collect::<HashSet<_>>();
+    for to in mapping.values_mut() {
+        if let Some(renamed) = names.get(to) {
+            *to = renamed.to_owned();
+        }
+    }
+    for (from, to) in names.iter().filter(|(from, _)| !displayed.contains(*from)) {
+        mapping.insert(from.to_owned(), to.to_owned());
+    }
+    meta::set_species_names(&tx, &mapping)?;
+    meta::set(&tx, meta::UPDATED, meta::now())?;
+    tx.commit()?;
+
+    Ok(())
+}
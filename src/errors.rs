@@ -18,14 +18,46 @@ pub enum DataError {
     #[error("ID {} not found in the specified database", .0.yellow().bold())]
     UnknownId(String),
 
+    #[cfg(feature = "sqlite")]
     #[error("failed to connect to database {}", .filename.yellow().bold())]
     FailedToConnect {
         source: rusqlite::Error,
         filename: String,
     },
 
+    #[cfg(feature = "sqlite")]
     #[error("inline gene books can not be accessed mutably")]
     ImmutableBook,
+
+    #[error("genes can only be inserted in or removed from in-memory gene books")]
+    UnsupportedEdit,
+
+    #[error("the genes loaded around {} do not match its landscapes", .0.yellow().bold())]
+    MismatchedNeighborhood(String),
+
+    #[error("landscapes of {requested} genes were requested, but only {available} are available")]
+    WindowTooWide { requested: usize, available: usize },
+
+    #[cfg(feature = "sqlite")]
+    #[error("{0} gene books can not be serialized")]
+    UnserializableBook(&'static str),
+
+    #[cfg(feature = "binary")]
+    #[error("{} is not a binary gene book, or was written by an incompatible version", .0.yellow().bold())]
+    NotABinaryBook(String),
+
+    #[cfg(feature = "mmap")]
+    #[error("{} is not a memory-mapped gene book, or was written by an incompatible version", .0.yellow().bold())]
+    NotAMappedBook(String),
+
+    #[cfg(feature = "sqlite")]
+    #[error("{} uses schema version {found}, but only versions {min} to {max} are supported; see `dbmaker::migrate`", .filename.yellow().bold())]
+    UnsupportedSchema {
+        filename: String,
+        found: u32,
+        min: u32,
+        max: u32,
+    },
 }
 
 #[derive(Error, Debug)]
@@ -39,9 +71,18 @@ pub enum ParseError {
     #[error("wrongly formatted ChromTable file: {0}")]
     ChromError(crate::chrom::ChromError),
 
+    #[error("wrongly formatted gene table: {0}")]
+    TableError(crate::table::TableError),
+
     #[error("invalid phase value: {0}")]
     InvalidPhase(String),
 
     #[error("invalid strand value: {0}")]
     InvalidStrand(String),
+
+    #[error("invalid coordinate convention: {0}")]
+    InvalidCoordinates(String),
+
+    #[error("invalid landscape entry: {}", .0.yellow().bold())]
+    InvalidLandscape(String),
 }
@@ -0,0 +1,104 @@
+//! Export to the `karyotype` and `links` files of Circos:
+//!   - `chr - ID LABEL START END COLOR` lines, one per chromosome of the
+//!     chosen species, coloured by species;
+//!   - `ID1 START1 END1 ID2 START2 END2` lines, one per pair of homologous
+//!     genes, or per syntenic block if blocks are requested.
+use anyhow::*;
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::{by_family, connect, rows};
+use crate::genebook::GeneBook;
+use crate::synteny::{syntenic_blocks, BlockOptions};
+
+/// The Circos ID of the chromosome `chr` of `species`
+fn karyotype_id(species: &str, chr: &str) -> String {
+    format!("{species}_{chr}")
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect()
+}
+
+/// Write the Circos karyotype of `species` and the links between them; if
+/// `blocks` is set, only the syntenic blocks detected under these settings
+/// are linked, rather than every pair of homologous genes
+pub fn export<W1: Write, W2: Write>(
+    db_file: &str,
+    species: &[&str],
+    blocks: Option<BlockOptions>,
+    mut karyotype: W1,
+    mut links: W2,
+) -> Result<()> {
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?
+        .into_iter()
+        .filter(|r| species.contains(&r.species.as_str()))
+        .collect::<Vec<_>>();
+
+    let mut extents = HashMap::<(&str, &str), (usize, usize)>::new();
+    for r in rows.iter() {
+        let extent = extents
+            .entry((&r.species, &r.chr))
+            .or_insert((r.start, r.stop));
+        extent.0 = extent.0.min(r.start);
+        extent.1 = extent.1.max(r.stop);
+    }
+    let mut chromosomes = extents.into_iter().collect::<Vec<_>>();
+    chromosomes
+        .sort_by_key(|((sp, chr), _)| (species.iter().position(|s| s == sp), chr.to_owned()));
+    for ((sp, chr), (start, stop)) in chromosomes {
+        let color = species.iter().position(|s| *s == sp).unwrap_or_default() % 24 + 1;
+        writeln!(
+            karyotype,
+            "chr - {} {} {} {} chr{}",
+            karyotype_id(sp, chr),
+            chr,
+            start,
+            stop,
+            color
+        )?;
+    }
+
+    if let Some(options) = blocks {
+        let book = GeneBook::inline(db_file, 0, "id")?;
+        for (i, a) in species.iter().enumerate() {
+            for b in species[i + 1..].iter() {
+                for block in syntenic_blocks(&book, a, b, &options)? {
+                    writeln!(
+                        links,
+                        "{} {} {} {} {} {}",
+                        karyotype_id(a, &block.chr_a),
+                        block.start_a,
+                        block.end_a,
+                        karyotype_id(b, &block.chr_b),
+                        block.start_b,
+                        block.end_b
+                    )?;
+                }
+            }
+        }
+    } else {
+        for (_, members) in by_family(&rows) {
+            for (k, &i) in members.iter().enumerate() {
+                for &j in members[k + 1..].iter() {
+                    let (a, b) = (&rows[i], &rows[j]);
+                    if a.species == b.species {
+                        continue;
+                    }
+                    writeln!(
+                        links,
+                        "{} {} {} {} {} {}",
+                        karyotype_id(&a.species, &a.chr),
+                        a.start,
+                        a.stop,
+                        karyotype_id(&b.species, &b.chr),
+                        b.start,
+                        b.stop
+                    )?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,41 @@
+//! Export to the match list format of DAGchainer:
+//! ```text
+//! chrA [TAB] geneA [TAB] startA [TAB] endA [TAB] chrB [TAB] geneB [TAB] startB [TAB] endB [TAB] e-value
+//! ```
+//! As matches are derived from families rather than alignments, they all
+//! share the same e-value.
+use anyhow::*;
+use std::io::Write;
+
+use super::{by_family, connect, rows};
+
+/// The e-value attributed to all the matches
+const EVALUE: f64 = 1e-50;
+
+/// Write all the pairs of genes from `species_a` and `species_b` sharing a
+/// family, in DAGchainer format
+pub fn export<W: Write>(db_file: &str, species_a: &str, species_b: &str, mut out: W) -> Result<()> {
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?
+        .into_iter()
+        .filter(|r| r.species == species_a || r.species == species_b)
+        .collect::<Vec<_>>();
+
+    for (_, members) in by_family(&rows) {
+        for &i in members.iter().filter(|&&i| rows[i].species == species_a) {
+            for &j in members.iter().filter(|&&j| rows[j].species == species_b) {
+                if i == j {
+                    continue;
+                }
+                let (a, b) = (&rows[i], &rows[j]);
+                writeln!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{:e}",
+                    a.chr, a.id, a.start, a.stop, b.chr, b.id, b.start, b.stop, EVALUE
+                )?;
+            }
+        }
+    }
+
+    Ok(())
+}
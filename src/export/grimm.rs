@@ -0,0 +1,79 @@
+//! Export to the signed permutations read by GRIMM and UniMoG, one genome
+//! per species, e.g. in GRIMM format:
+//! ```text
+//! >species
+//! # chr1
+//! 1 -2 3 $
+//! ```
+//! and in UniMoG format:
+//! ```text
+//! >species
+//! 1 -2 3 |
+//! ```
+//! Only the families found exactly once in each species are written; they
+//! are numbered from 1 following the order of their IDs.
+use anyhow::*;
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::{by_family, connect, rows};
+use crate::Strand;
+
+/// The flavor of the written permutations
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PermutationFormat {
+    /// Chromosomes end with `$`, preceded by their name as a comment
+    Grimm,
+    /// Chromosomes end with `|`, i.e. are linear
+    UniMoG,
+}
+
+/// Write the gene orders of `species` as signed permutations of their
+/// shared single-copy families
+pub fn export<W: Write>(
+    db_file: &str,
+    species: &[&str],
+    format: PermutationFormat,
+    mut out: W,
+) -> Result<()> {
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?
+        .into_iter()
+        .filter(|r| species.contains(&r.species.as_str()))
+        .collect::<Vec<_>>();
+
+    let markers = by_family(&rows)
+        .into_iter()
+        .filter(|(_, members)| {
+            members.len() == species.len()
+                && species
+                    .iter()
+                    .all(|s| members.iter().any(|&i| rows[i].species == *s))
+        })
+        .enumerate()
+        .map(|(k, (family, _))| (family, k + 1))
+        .collect::<HashMap<_, _>>();
+
+    for sp in species {
+        writeln!(out, ">{sp}")?;
+        let genes = rows
+            .iter()
+            .filter(|r| r.species == *sp && markers.contains_key(&r.family))
+            .collect::<Vec<_>>();
+        for chromosome in genes.chunk_by(|a, b| a.chr == b.chr) {
+            if format == PermutationFormat::Grimm {
+                writeln!(out, "# {}", chromosome[0].chr)?;
+            }
+            for r in chromosome {
+                let sign = if r.strand == Strand::Reverse { "-" } else { "" };
+                write!(out, "{}{} ", sign, markers[&r.family])?;
+            }
+            match format {
+                PermutationFormat::Grimm => writeln!(out, "$")?,
+                PermutationFormat::UniMoG => writeln!(out, "|")?,
+            }
+        }
+    }
+
+    Ok(())
+}
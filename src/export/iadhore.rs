@@ -0,0 +1,135 @@
+//! Export to the input files of i-ADHoRe 3:
+//!   - one gene list per chromosome, `lists/<species>/<chr>.lst`, made of
+//!     the oriented gene IDs (`gene+`/`gene-`) in chromosomal order; the
+//!     species and chromosome names are reduced to characters safe in paths,
+//!     and suffixed if that makes them collide;
+//!   - a `families.tsv` table mapping each gene to its family;
+//!   - a `settings.ini` skeleton referencing all of the above.
+use anyhow::*;
+use colored::Colorize;
+use log::*;
+use std::collections::HashSet;
+use std::fs::{create_dir_all, File};
+use std::io::{BufWriter, Write};
+use std::path::Path;
+
+use super::{connect, rows};
+use crate::Strand;
+
+fn create(path: &Path) -> Result<BufWriter<File>> {
+    Ok(BufWriter::new(File::create(path).with_context(|| {
+        anyhow!(
+            "while creating {}",
+            path.display().to_string().yellow().bold()
+        )
+    })?))
+}
+
+/// Turn `name` into a file name distinct from the ones already `used`
+fn file_name(name: &str, used: &mut HashSet<String>) -> String {
+    let safe = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect::<String>();
+    let safe = if safe.is_empty() || safe.starts_with('.') {
+        format!("_{}", safe)
+    } else {
+        safe
+    };
+    let mut candidate = safe.clone();
+    let mut i = 1;
+    while !used.insert(candidate.clone()) {
+        candidate = format!("{}_{}", safe, i);
+        i += 1;
+    }
+    candidate
+}
+
+/// Write the i-ADHoRe input files for the given database in `out_dir`
+pub fn export<P: AsRef<Path>>(db_file: &str, out_dir: P) -> Result<()> {
+    let out_dir = out_dir.as_ref();
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?;
+
+    let mut families = create(&out_dir.join("families.tsv"))?;
+    let mut settings = create(&out_dir.join("settings.ini"))?;
+
+    let mut genome_names = HashSet::new();
+    let mut list_names = HashSet::new();
+    let mut genome = String::new();
+    let mut current: Option<(&str, &str, BufWriter<File>)> = None;
+    for r in rows.iter() {
+        writeln!(families, "{}\t{}", r.id, r.family)?;
+
+        if current
+            .as_ref()
+            .map(|(species, chr, _)| *species != r.species || *chr != r.chr)
+            .unwrap_or(true)
+        {
+            if current.as_ref().is_none_or(|c| c.0 != r.species) {
+                if current.is_some() {
+                    writeln!(settings)?;
+                }
+                genome = file_name(&r.species, &mut genome_names);
+                list_names.clear();
+                writeln!(settings, "genome={}", genome)?;
+            }
+            if let Some((_, _, mut list)) = current.take() {
+                list.flush()?;
+            }
+            let name = file_name(&r.chr, &mut list_names);
+            let dir = out_dir.join("lists").join(&genome);
+            create_dir_all(&dir)?;
+            let list = dir.join(format!("{}.lst", name));
+            debug!("Writing {}", list.display());
+            writeln!(
+                settings,
+                "{} {}",
+                name,
+                Path::new("lists")
+                    .join(&genome)
+                    .join(format!("{}.lst", name))
+                    .display()
+            )?;
+            current = Some((&r.species, &r.chr, create(&list)?));
+        }
+
+        let orientation = if r.strand == Strand::Reverse {
+            '-'
+        } else {
+            '+'
+        };
+        writeln!(current.as_mut().unwrap().2, "{}{}", r.id, orientation)?;
+    }
+    if let Some((_, _, mut list)) = current {
+        list.flush()?;
+    }
+    families.flush()?;
+
+    writeln!(
+        settings,
+        "
+blast_table=families.tsv
+table_type=family
+output_path=output
+cluster_type=collinear
+alignment_method=gg2
+gap_size=30
+cluster_gap=35
+q_value=0.75
+prob_cutoff=0.01
+anchor_points=3
+level_2_only=false
+multiple_hypothesis_correction=FDR
+number_of_threads=1"
+    )?;
+    settings.flush()?;
+
+    Ok(())
+}
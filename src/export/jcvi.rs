@@ -0,0 +1,54 @@
+//! Export to the `.bed` and `.anchors` files used by the jcvi MCscan
+//! plotting tools (`jcvi.graphics.karyotype`, `jcvi.graphics.dotplot`, ...).
+//!
+//! Anchors are grouped into blocks as chained by [`collinear_chains`].
+use anyhow::*;
+use std::io::Write;
+
+use super::{connect, coordinates, rows};
+use crate::genebook::GeneBook;
+use crate::synteny::{collinear_chains, ChainOptions};
+use crate::{Coordinates, Strand};
+
+/// Write the genes of `species` in BED format, as expected by jcvi
+pub fn write_bed<W: Write>(db_file: &str, species: &str, mut out: W) -> Result<()> {
+    let conn = connect(db_file)?;
+    let coordinates = coordinates(&conn)?;
+    for r in rows(&conn)?.iter().filter(|r| r.species == species) {
+        let (start, stop) = coordinates.convert(r.start, r.stop, Coordinates::ZeroBased);
+        writeln!(
+            out,
+            "{}\t{}\t{}\t{}\t0\t{}",
+            r.chr,
+            start,
+            stop,
+            r.id,
+            if r.strand == Strand::Reverse {
+                '-'
+            } else {
+                '+'
+            }
+        )?;
+    }
+    Ok(())
+}
+
+/// Write the syntenic anchors between `species_a` and `species_b`, one
+/// `###`-separated block per collinear chain found under `options`
+pub fn write_anchors<W: Write>(
+    db_file: &str,
+    species_a: &str,
+    species_b: &str,
+    options: &ChainOptions,
+    mut out: W,
+) -> Result<()> {
+    let book = GeneBook::inline(db_file, 0, "id")?;
+    for block in collinear_chains(&book, species_a, species_b, options)? {
+        writeln!(out, "###")?;
+        for (a, b) in block.anchors.iter() {
+            writeln!(out, "{}\t{}\t100", a, b)?;
+        }
+    }
+
+    Ok(())
+}
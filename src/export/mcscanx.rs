@@ -0,0 +1,148 @@
+//! Export to the input and output files of MCScanX_h:
+//!   - a `.gff` file, `sp<TAB>gene<TAB>start<TAB>end`, where `sp` is a
+//!     two-letter species code followed by the chromosome name;
+//!   - a `.homology` file, listing pairs of homologous genes, i.e. genes
+//!     sharing the same family;
+//!   - a `.collinearity` file, listing the collinear blocks between each
+//!     pair of species as chained by [`collinear_chains`], for use with the
+//!     MCScanX visualization tools.
+use anyhow::*;
+use std::collections::{HashMap, HashSet};
+use std::io::Write;
+
+use super::{by_family, connect, rows, Row};
+use crate::genebook::GeneBook;
+use crate::synteny::{collinear_chains, ChainOptions};
+use crate::Strand;
+
+/// Generate the two-letter code of the `i`-th species
+fn species_code(i: usize) -> String {
+    let letters = b"abcdefghijklmnopqrstuvwxyz";
+    format!(
+        "{}{}",
+        letters[(i / 26) % 26] as char,
+        letters[i % 26] as char
+    )
+}
+
+/// Map each species of `rows` to its two-letter code, in order of appearance
+fn species_codes(rows: &[Row]) -> Result<HashMap<String, String>> {
+    let mut codes = HashMap::new();
+    for r in rows.iter() {
+        let n = codes.len();
+        if n >= 26 * 26 && !codes.contains_key(&r.species) {
+            bail!("MCScanX can not handle more than {} species", 26 * 26);
+        }
+        codes
+            .entry(r.species.clone())
+            .or_insert_with(|| species_code(n));
+    }
+    Ok(codes)
+}
+
+/// Write the MCScanX `.gff` and `.homology` files for the given database,
+/// and return the mapping from species names to the codes that were used to
+/// prefix their chromosomes.
+pub fn export<W1: Write, W2: Write>(
+    db_file: &str,
+    mut gff: W1,
+    mut homology: W2,
+) -> Result<HashMap<String, String>> {
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?;
+
+    let codes = species_codes(&rows)?;
+    for r in rows.iter() {
+        writeln!(
+            gff,
+            "{}{}\t{}\t{}\t{}",
+            codes[&r.species], r.chr, r.id, r.start, r.stop
+        )?;
+    }
+
+    for (_, members) in by_family(&rows) {
+        for (k, &i) in members.iter().enumerate() {
+            for &j in members[k + 1..].iter() {
+                writeln!(homology, "{}\t{}", rows[i].id, rows[j].id)?;
+            }
+        }
+    }
+
+    Ok(codes)
+}
+
+/// Write an MCScanX `.collinearity` file listing the collinear blocks
+/// chained under `options` between all the pairs of species of the
+/// database, their chromosomes being named as in the `.gff` file written by
+/// [`export`].
+///
+/// As there are no alignments behind the homologies, all the E-values are
+/// set to 0.
+pub fn write_collinearity<W: Write>(
+    db_file: &str,
+    options: &ChainOptions,
+    mut out: W,
+) -> Result<()> {
+    let conn = connect(db_file)?;
+    let rows = rows(&conn)?;
+    let codes = species_codes(&rows)?;
+    let mut species = codes.keys().collect::<Vec<_>>();
+    species.sort_by_key(|s| &codes[*s]);
+
+    let book = GeneBook::inline(db_file, 0, "id")?;
+    let mut blocks = Vec::new();
+    for (k, species_a) in species.iter().enumerate() {
+        for species_b in species[k + 1..].iter() {
+            blocks.extend(
+                collinear_chains(&book, species_a, species_b, options)?
+                    .into_iter()
+                    .map(|block| (*species_a, *species_b, block)),
+            );
+        }
+    }
+    let collinear = blocks
+        .iter()
+        .flat_map(|(_, _, block)| block.anchors.iter())
+        .flat_map(|(a, b)| [a, b])
+        .collect::<HashSet<_>>()
+        .len();
+
+    writeln!(out, "############### Parameters ###############")?;
+    writeln!(out, "# MATCH_SCORE: {}", options.match_score)?;
+    writeln!(out, "# MATCH_SIZE: {}", options.min_anchors)?;
+    writeln!(out, "# GAP_PENALTY: {}", options.gap_penalty)?;
+    writeln!(out, "# MAX GAPS: {}", options.max_gap)?;
+    writeln!(out, "# E_VALUE: 0")?;
+    writeln!(out, "############### Statistics ###############")?;
+    writeln!(
+        out,
+        "# Number of collinear genes: {}, Percentage: {:.2}",
+        collinear,
+        100.0 * collinear as f64 / rows.len().max(1) as f64
+    )?;
+    writeln!(out, "# Number of all genes: {}", rows.len())?;
+    writeln!(out, "##########################################")?;
+    for (n, (species_a, species_b, block)) in blocks.iter().enumerate() {
+        writeln!(
+            out,
+            "## Alignment {}: score={:.1} e_value=0 N={} {}{}&{}{} {}",
+            n,
+            block.score as f64,
+            block.anchors.len(),
+            codes[*species_a],
+            block.chr_a,
+            codes[*species_b],
+            block.chr_b,
+            if block.orientation == Strand::Reverse {
+                "minus"
+            } else {
+                "plus"
+            }
+        )?;
+        for (k, (a, b)) in block.anchors.iter().enumerate() {
+            writeln!(out, "{:>3}-{:>3}:\t{}\t{}\t      0", n, k, a, b)?;
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,79 @@
+//! Exporters writing the content of a database in the input formats of
+//! third-party synteny tools.
+use anyhow::*;
+use rusqlite::Connection;
+use std::collections::HashMap;
+
+use crate::{dbmaker::meta, errors::DataError, genebook::FamilyID, Coordinates, Strand};
+
+pub mod circos;
+pub mod dagchainer;
+pub mod grimm;
+pub mod iadhore;
+pub mod jcvi;
+pub mod mcscanx;
+pub mod ribbons;
+
+/// A gene as stored in the `genomes` table
+pub(crate) struct Row {
+    pub species: String,
+    pub chr: String,
+    pub id: String,
+    pub start: usize,
+    pub stop: usize,
+    pub strand: Strand,
+    pub family: FamilyID,
+}
+
+pub(crate) fn connect(db_file: &str) -> Result<Connection> {
+    Ok(
+        Connection::open(db_file).map_err(|e| DataError::FailedToConnect {
+            source: e,
+            filename: db_file.into(),
+        })?,
+    )
+}
+
+/// The coordinate convention of the genes of the database
+pub(crate) fn coordinates(conn: &Connection) -> Result<Coordinates> {
+    Ok(meta::get(conn, meta::COORDINATES)?
+        .map(|c| Coordinates::try_from(c.as_str()))
+        .transpose()?
+        .unwrap_or_default())
+}
+
+/// Load all the genes of the database, ordered by species, chromosome and
+/// position
+pub(crate) fn rows(conn: &Connection) -> Result<Vec<Row>> {
+    conn.prepare(
+        "SELECT species, chr, id, start, stop, direction, ancestral_id FROM genomes ORDER BY species, chr, start",
+    )?
+    .query_map([], |r| {
+        std::result::Result::Ok(Row {
+            species: r.get(0)?,
+            chr: r.get(1)?,
+            id: r.get(2)?,
+            start: r.get(3)?,
+            stop: r.get(4)?,
+            strand: r
+                .get::<_, String>(5)?
+                .as_str()
+                .try_into()
+                .unwrap_or_default(),
+            family: r.get(6)?,
+        })
+    })?
+    .collect::<Result<Vec<_>, _>>()
+    .with_context(|| "while reading genes")
+}
+
+/// Group the indices of `rows` by family
+pub(crate) fn by_family(rows: &[Row]) -> Vec<(FamilyID, Vec<usize>)> {
+    let mut families = HashMap::<FamilyID, Vec<usize>>::new();
+    for (i, r) in rows.iter().enumerate() {
+        families.entry(r.family).or_default().push(i);
+    }
+    let mut families = families.into_iter().collect::<Vec<_>>();
+    families.sort_by_key(|f| f.0);
+    families
+}
@@ -0,0 +1,90 @@
+//! Export of the syntenic blocks between consecutive genomes as a JSON
+//! document, for front-ends drawing ribbon or alluvial plots:
+//! ```text
+//! {
+//!   "genomes": [
+//!     {"species": "a", "chromosomes": [{"name": "chr1", "start": 1, "end": 9000}, ...]},
+//!     ...
+//!   ],
+//!   "ribbons": [
+//!     {
+//!       "source": {"species": "a", "chr": "chr1", "start": 100, "end": 4000},
+//!       "target": {"species": "b", "chr": "chr3", "start": 2000, "end": 7000},
+//!       "orientation": "+",
+//!       "anchors": 12
+//!     },
+//!     ...
+//!   ]
+//! }
+//! ```
+//! Genomes are listed in the order given, chromosomes in natural order and
+//! ribbons link each genome to the next one; `orientation` is `-` for blocks
+//! inverted in the target, and `anchors` the number of gene pairs supporting
+//! the block. Coordinates are those of the database.
+use anyhow::*;
+use serde_json::json;
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::{connect, rows};
+use crate::genebook::GeneBook;
+use crate::interval::natural_cmp;
+use crate::synteny::{syntenic_blocks, BlockOptions};
+
+/// Write the chromosomes of `species` and the syntenic blocks, detected
+/// under `options`, between each of them and the next one
+pub fn export<W: Write>(
+    db_file: &str,
+    species: &[&str],
+    options: &BlockOptions,
+    mut out: W,
+) -> Result<()> {
+    let conn = connect(db_file)?;
+    let mut extents = HashMap::<(String, String), (usize, usize)>::new();
+    for r in rows(&conn)?
+        .into_iter()
+        .filter(|r| species.contains(&r.species.as_str()))
+    {
+        let extent = extents
+            .entry((r.species, r.chr))
+            .or_insert((r.start, r.stop));
+        extent.0 = extent.0.min(r.start);
+        extent.1 = extent.1.max(r.stop);
+    }
+    let genomes = species
+        .iter()
+        .map(|sp| {
+            let mut chromosomes = extents
+                .iter()
+                .filter(|((s, _), _)| s == sp)
+                .map(|((_, chr), extent)| (chr, extent))
+                .collect::<Vec<_>>();
+            chromosomes.sort_by(|a, b| natural_cmp(a.0, b.0));
+            json!({
+                "species": sp,
+                "chromosomes": chromosomes
+                    .into_iter()
+                    .map(|(chr, (start, end))| json!({"name": chr, "start": start, "end": end}))
+                    .collect::<Vec<_>>(),
+            })
+        })
+        .collect::<Vec<_>>();
+
+    let book = GeneBook::inline(db_file, 0, "id")?;
+    let mut ribbons = Vec::new();
+    for pair in species.windows(2) {
+        let (a, b) = (pair[0], pair[1]);
+        for block in syntenic_blocks(&book, a, b, options)? {
+            ribbons.push(json!({
+                "source": {"species": a, "chr": block.chr_a, "start": block.start_a, "end": block.end_a},
+                "target": {"species": b, "chr": block.chr_b, "start": block.start_b, "end": block.end_b},
+                "orientation": block.orientation.to_string(),
+                "anchors": block.anchors.len(),
+            }));
+        }
+    }
+
+    serde_json::to_writer(&mut out, &json!({"genomes": genomes, "ribbons": ribbons}))?;
+    writeln!(out)?;
+    Ok(())
+}
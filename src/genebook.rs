@@ -1,10 +1,27 @@
 use anyhow::*;
+#[cfg(feature = "sqlite")]
 use log::*;
-use rusqlite::Connection;
-use std::collections::HashMap;
-use std::sync::Mutex;
+#[cfg(feature = "sqlite")]
+use rusqlite::{Connection, OpenFlags};
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
+#[cfg(feature = "sqlite")]
+use std::{
+    ops::Deref,
+    sync::{Condvar, Mutex},
+};
 
-use crate::{errors, Strand};
+use crate::{
+    aliases::ChromAliases, chain::Liftover, errors, gff, interval::natural_cmp, Coordinates,
+    GenomicPosition, Interval, Strand,
+};
+#[cfg(feature = "sqlite")]
+use crate::{
+    dbmaker::{meta, migrate, save},
+    errors::ParseError,
+};
 
 pub type FamilyID = usize;
 
@@ -13,22 +30,594 @@ pub enum GeneBook {
     InMemory {
         genes: HashMap<String, Gene>,
         species: Vec<String>,
+        aliases: ChromAliases,
+        regions: Regions,
+        normalizer: Normalizer,
+        /// The coordinate convention of [`Gene::pos`] and [`Gene::end`]
+        coordinates: Coordinates,
+        /// The number of genes in the landscapes on each side
+        window: usize,
+        /// The keys of the genes of each family, if indexed
+        families: Option<HashMap<FamilyID, Vec<String>>>,
+        /// The chains lifting the regions queried to the assembly of the
+        /// database, by species
+        liftovers: HashMap<String, Arc<Liftover>>,
     },
     Cached {
         genes: HashMap<String, Gene>,
         species: Vec<String>,
+        aliases: ChromAliases,
+        regions: Regions,
+        normalizer: Normalizer,
+        /// The coordinate convention of [`Gene::pos`] and [`Gene::end`]
+        coordinates: Coordinates,
+        /// The number of genes in the landscapes on each side
+        window: usize,
+        /// The keys of the genes of each family, if indexed
+        families: Option<HashMap<FamilyID, Vec<String>>>,
+        /// The chains lifting the regions queried to the assembly of the
+        /// database, by species
+        liftovers: HashMap<String, Arc<Liftover>>,
     },
+    #[cfg(feature = "sqlite")]
     Inline {
-        conn: Mutex<Connection>,
+        conn: Pool,
         window: usize,
         id_column: String,
+        schema: Schema,
+        normalizer: Normalizer,
+        /// The stored IDs, by their normalized form; empty if the IDs are
+        /// not normalized
+        raw_ids: HashMap<String, String>,
+        cache: Mutex<GeneCache>,
+        /// Loads the chromosomes queried often into memory, if enabled
+        promoter: Option<Box<Promoter>>,
+        /// The chains lifting the regions queried to the assembly of the
+        /// database, by species
+        liftovers: HashMap<String, Arc<Liftover>>,
     },
 }
 
-#[derive(Clone, Copy)]
+/// How gene IDs are normalized before being compared, both when a book is
+/// loaded and when it is queried, so that e.g. `ENSG0000012345.7` may be found
+/// as `ensg0000012345`
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct IdNormalization {
+    /// Compare IDs regardless of their case
+    pub lowercase: bool,
+    /// Strip the trailing version of IDs, e.g. `.7` in `ENSG0000012345.7`
+    pub strip_version: bool,
+    /// Strip all the matches of this regex from IDs
+    pub strip: Option<String>,
+}
+impl IdNormalization {
+    #[cfg(feature = "sqlite")]
+    fn compile(&self) -> Result<Normalizer> {
+        Ok(Normalizer {
+            lowercase: self.lowercase,
+            strip_version: self.strip_version,
+            strip: self
+                .strip
+                .as_ref()
+                .map(|re| {
+                    regex::Regex::new(re).with_context(|| anyhow!("{} is not a valid regex", re))
+                })
+                .transpose()?,
+        })
+    }
+}
+
+/// A compiled [`IdNormalization`]
+#[derive(Debug, Clone, Default)]
+pub struct Normalizer {
+    lowercase: bool,
+    strip_version: bool,
+    strip: Option<regex::Regex>,
+}
+impl Normalizer {
+    /// Whether IDs are left as they are
+    #[cfg(feature = "sqlite")]
+    fn is_identity(&self) -> bool {
+        !self.lowercase && !self.strip_version && self.strip.is_none()
+    }
+
+    fn normalize<'a>(&self, id: &'a str) -> Cow<'a, str> {
+        let mut id = Cow::Borrowed(id);
+        if let Some(strip) = self.strip.as_ref() {
+            if let Cow::Owned(stripped) = strip.replace_all(&id, "") {
+                id = Cow::Owned(stripped);
+            }
+        }
+        if self.strip_version {
+            if let Some((stem, version)) = id.rsplit_once('.') {
+                if !version.is_empty() && version.chars().all(|c| c.is_ascii_digit()) {
+                    id = Cow::Owned(stem.to_owned());
+                }
+            }
+        }
+        if self.lowercase && id.chars().any(|c| c.is_uppercase()) {
+            id = Cow::Owned(id.to_lowercase());
+        }
+        id
+    }
+
+    /// Key `genes` by their normalized ID; of the IDs colliding once
+    /// normalized, only the smallest is kept
+    #[cfg(feature = "sqlite")]
+    fn rekey(&self, genes: HashMap<String, Gene>) -> HashMap<String, Gene> {
+        if self.is_identity() {
+            return genes;
+        }
+        let mut r = HashMap::<String, Gene>::with_capacity(genes.len());
+        for g in genes.into_values() {
+            let key = self.normalize(&g.id).into_owned();
+            if let Some(other) = r.get(&key) {
+                let keep_other = other.id < g.id;
+                warn!(
+                    "{} and {} are the same ID once normalized; only {} is kept",
+                    other.id,
+                    g.id,
+                    if keep_other { &other.id } else { &g.id }
+                );
+                if keep_other {
+                    continue;
+                }
+            }
+            r.insert(key, g);
+        }
+        r
+    }
+}
+
+/// The options of a [`GeneBook`]
+#[derive(Debug, Clone)]
+pub struct BookOptions {
+    /// How gene IDs are normalized
+    pub normalization: IdNormalization,
+    /// How many of the genes recently fetched by inline books are kept in
+    /// memory; 0 disables the cache
+    pub cache_size: usize,
+    /// How many connections inline books may open to serve concurrent
+    /// reads; books built on an existing connection only ever use it
+    pub connections: usize,
+    /// Whether to open the database read-only, e.g. on a read-only
+    /// filesystem; by default, in-memory and cached books do, and inline
+    /// books do not
+    pub read_only: Option<bool>,
+    /// Whether in-memory and cached books index their genes by family,
+    /// speeding up [`GeneBook::genes_of_family`]
+    pub family_index: bool,
+    /// If set, in-memory books read their genes from this file rather than
+    /// from the database when it was written for the same database, window
+    /// and ID column, and (re)write it otherwise
+    #[cfg(feature = "binary")]
+    pub sidecar: Option<String>,
+    /// If set, inline books load into memory, in the background, the
+    /// chromosomes queried this many times; books built on an existing
+    /// connection never do
+    pub promote_after: Option<usize>,
+    /// Chains lifting coordinates from other assemblies to the ones the
+    /// database was built on, by species; the regions given to
+    /// [`GeneBook::genes_in_region`] for these species are then expressed in
+    /// the target assemblies of the chains
+    pub liftovers: HashMap<String, Arc<Liftover>>,
+}
+impl Default for BookOptions {
+    fn default() -> Self {
+        BookOptions {
+            normalization: IdNormalization::default(),
+            cache_size: 10_000,
+            connections: 4,
+            read_only: None,
+            family_index: false,
+            #[cfg(feature = "binary")]
+            sidecar: None,
+            promote_after: None,
+            liftovers: HashMap::new(),
+        }
+    }
+}
+
+/// The connections of an inline book, opened read-only on demand up to a
+/// limit, so that several threads may query the book at once
+#[cfg(feature = "sqlite")]
+pub struct Pool {
+    /// The database to open new connections to; `None` if the pool is
+    /// bound to its initial connection
+    filename: Option<String>,
+    capacity: usize,
+    state: Mutex<PoolState>,
+    released: Condvar,
+}
+#[cfg(feature = "sqlite")]
+struct PoolState {
+    idle: Vec<Connection>,
+    open: usize,
+}
+#[cfg(feature = "sqlite")]
+impl Pool {
+    fn new(conn: Connection, filename: Option<&str>, capacity: usize) -> Self {
+        Pool {
+            filename: filename.map(|f| f.to_owned()),
+            capacity: capacity.max(1),
+            state: Mutex::new(PoolState {
+                idle: vec![conn],
+                open: 1,
+            }),
+            released: Condvar::new(),
+        }
+    }
+
+    /// An idle connection, or a new one if the pool is not full; wait for
+    /// one to be released otherwise
+    fn get(&self) -> Result<PooledConnection<'_>> {
+        let mut state = self.state.lock().expect("MUTEX POISONING");
+        loop {
+            if let Some(conn) = state.idle.pop() {
+                return Ok(PooledConnection {
+                    pool: self,
+                    conn: Some(conn),
+                });
+            }
+            if let Some(filename) = self
+                .filename
+                .as_ref()
+                .filter(|_| state.open < self.capacity)
+            {
+                state.open += 1;
+                drop(state);
+                return match open(filename, true) {
+                    std::result::Result::Ok(conn) => Ok(PooledConnection {
+                        pool: self,
+                        conn: Some(conn),
+                    }),
+                    Err(e) => {
+                        self.state.lock().expect("MUTEX POISONING").open -= 1;
+                        self.released.notify_one();
+                        Err(e)
+                    }
+                };
+            }
+            state = self.released.wait(state).expect("MUTEX POISONING");
+        }
+    }
+}
+
+/// A connection borrowed from a [`Pool`], returned to it when dropped
+#[cfg(feature = "sqlite")]
+struct PooledConnection<'a> {
+    pool: &'a Pool,
+    conn: Option<Connection>,
+}
+#[cfg(feature = "sqlite")]
+impl Deref for PooledConnection<'_> {
+    type Target = Connection;
+
+    fn deref(&self) -> &Connection {
+        self.conn.as_ref().unwrap()
+    }
+}
+#[cfg(feature = "sqlite")]
+impl Drop for PooledConnection<'_> {
+    fn drop(&mut self) {
+        if let Some(conn) = self.conn.take() {
+            self.pool
+                .state
+                .lock()
+                .expect("MUTEX POISONING")
+                .idle
+                .push(conn);
+            self.pool.released.notify_one();
+        }
+    }
+}
+
+/// The genes recently fetched by an inline book, by raw ID, evicting the
+/// least recently used ones beyond its capacity
+#[cfg(feature = "sqlite")]
+#[derive(Default)]
+pub struct GeneCache {
+    capacity: usize,
+    tick: u64,
+    genes: HashMap<String, (u64, Gene)>,
+    recency: BTreeMap<u64, String>,
+}
+#[cfg(feature = "sqlite")]
+impl GeneCache {
+    fn new(capacity: usize) -> Self {
+        GeneCache {
+            capacity,
+            ..Default::default()
+        }
+    }
+
+    fn get(&mut self, id: &str) -> Option<Gene> {
+        let (last, gene) = self.genes.get_mut(id)?;
+        self.recency.remove(last);
+        self.tick += 1;
+        *last = self.tick;
+        self.recency.insert(self.tick, id.to_owned());
+        Some(gene.clone())
+    }
+
+    fn insert(&mut self, gene: Gene) {
+        if self.capacity == 0 {
+            return;
+        }
+        self.tick += 1;
+        if let Some((last, _)) = self.genes.remove(&gene.id) {
+            self.recency.remove(&last);
+        }
+        self.recency.insert(self.tick, gene.id.clone());
+        self.genes.insert(gene.id.clone(), (self.tick, gene));
+        while self.genes.len() > self.capacity {
+            let Some((_, oldest)) = self.recency.pop_first() else {
+                break;
+            };
+            self.genes.remove(&oldest);
+        }
+    }
+}
+
+/// A chromosome, as a (species, chromosome) pair
+#[cfg(feature = "sqlite")]
+type ChrKey = (String, String);
+
+/// Loads the chromosomes that an inline book queries often into memory, in
+/// the background, and serves their genes once they are loaded
+#[cfg(feature = "sqlite")]
+pub struct Promoter {
+    filename: String,
+    /// How many queries a chromosome takes before being loaded
+    threshold: usize,
+    window: usize,
+    id_column: String,
+    schema: Schema,
+    state: Arc<Mutex<PromoterState>>,
+}
+#[cfg(feature = "sqlite")]
+#[derive(Default)]
+struct PromoterState {
+    /// How many times each chromosome not yet promoted has been queried
+    accesses: HashMap<ChrKey, usize>,
+    /// The chromosomes being loaded or already loaded
+    promoted: HashSet<ChrKey>,
+    /// The genes of the loaded chromosomes, by raw ID
+    genes: HashMap<String, Gene>,
+    /// The raw IDs of the genes of each loaded chromosome
+    chromosomes: HashMap<ChrKey, Vec<String>>,
+}
+#[cfg(feature = "sqlite")]
+impl Promoter {
+    fn new(
+        filename: &str,
+        threshold: usize,
+        window: usize,
+        id_column: &str,
+        schema: &Schema,
+    ) -> Self {
+        Promoter {
+            filename: filename.to_owned(),
+            threshold: threshold.max(1),
+            window,
+            id_column: id_column.to_owned(),
+            schema: schema.clone(),
+            state: Default::default(),
+        }
+    }
+
+    /// The gene `id`, if its chromosome is loaded
+    fn get(&self, id: &str) -> Option<Gene> {
+        self.state
+            .lock()
+            .expect("MUTEX POISONING")
+            .genes
+            .get(id)
+            .cloned()
+    }
+
+    /// The genes of `chr` in `species`, if it is loaded
+    fn chromosome(&self, species: &str, chr: &str) -> Option<Vec<Gene>> {
+        let state = self.state.lock().expect("MUTEX POISONING");
+        let ids = state
+            .chromosomes
+            .get(&(species.to_owned(), chr.to_owned()))?;
+        Some(
+            ids.iter()
+                .filter_map(|id| state.genes.get(id))
+                .cloned()
+                .collect(),
+        )
+    }
+
+    /// Record a query of `chr` in `species`, and start loading it if it
+    /// reaches the threshold
+    fn touch(&self, species: &str, chr: &str) {
+        let key = (species.to_owned(), chr.to_owned());
+        {
+            let mut state = self.state.lock().expect("MUTEX POISONING");
+            if state.promoted.contains(&key) {
+                return;
+            }
+            let accesses = state.accesses.entry(key.clone()).or_default();
+            *accesses += 1;
+            if *accesses < self.threshold {
+                return;
+            }
+            state.accesses.remove(&key);
+            state.promoted.insert(key.clone());
+        }
+
+        let filename = self.filename.clone();
+        let window = self.window;
+        let id_column = self.id_column.clone();
+        let schema = self.schema.clone();
+        let state = Arc::clone(&self.state);
+        std::thread::spawn(move || {
+            debug!("Promoting {}/{} to memory", key.0, key.1);
+            let loaded = open(&filename, true).and_then(|conn| {
+                let query = conn.prepare(&format!(
+                    "SELECT {} FROM {} WHERE genomes.species=? AND genomes.chr=?",
+                    GeneBook::columns(&id_column, &schema),
+                    GeneBook::source(&schema)
+                ))?;
+                GeneBook::get_rows(query, [&key.0, &key.1], window, &schema)
+            });
+            let mut state = state.lock().expect("MUTEX POISONING");
+            match loaded {
+                std::result::Result::Ok(genes) => {
+                    state
+                        .chromosomes
+                        .insert(key, genes.keys().cloned().collect());
+                    state.genes.extend(genes);
+                }
+                Err(e) => {
+                    warn!("failed to promote {}/{}: {}", key.0, key.1, e);
+                    state.promoted.remove(&key);
+                }
+            }
+        });
+    }
+}
+
+/// How gene IDs are matched by [`GeneBook::find`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdPattern {
+    /// IDs starting with this prefix, e.g. `ENSG0000012345` for
+    /// `ENSG0000012345.7`
+    Prefix(String),
+    /// IDs matching this glob, e.g. `ENSG00000123*.?`
+    Glob(String),
+    /// IDs matching this regex anywhere
+    Regex(String),
+}
+impl IdPattern {
+    fn compile(&self) -> Result<Matcher<'_>> {
+        Ok(match self {
+            IdPattern::Prefix(prefix) => Matcher::Prefix(prefix),
+            IdPattern::Glob(glob) => Matcher::Glob(
+                glob::Pattern::new(glob)
+                    .with_context(|| anyhow!("{} is not a valid glob", glob))?,
+            ),
+            IdPattern::Regex(re) => Matcher::Regex(
+                regex::Regex::new(re).with_context(|| anyhow!("{} is not a valid regex", re))?,
+            ),
+        })
+    }
+
+    /// A prefix or a glob, in the syntax of the SQLite `GLOB` operator
+    #[cfg(feature = "sqlite")]
+    fn as_sql_glob(&self) -> Option<String> {
+        match self {
+            IdPattern::Prefix(prefix) => Some(
+                prefix
+                    .chars()
+                    .map(|c| match c {
+                        '*' | '?' | '[' => format!("[{c}]"),
+                        _ => c.to_string(),
+                    })
+                    .chain(std::iter::once("*".to_owned()))
+                    .collect(),
+            ),
+            IdPattern::Glob(glob) => Some(glob.clone()),
+            IdPattern::Regex(_) => None,
+        }
+    }
+}
+
+/// A compiled [`IdPattern`]
+enum Matcher<'a> {
+    Prefix(&'a str),
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
+}
+impl Matcher<'_> {
+    fn matches(&self, id: &str) -> bool {
+        match self {
+            Matcher::Prefix(prefix) => id.starts_with(prefix),
+            Matcher::Glob(glob) => glob.matches(id),
+            Matcher::Regex(re) => re.is_match(id),
+        }
+    }
+}
+
+#[derive(Clone)]
+#[cfg_attr(feature = "binary", derive(serde::Serialize, serde::Deserialize))]
 pub struct TailGene {
     pub family: FamilyID,
     pub strand: Strand,
+    /// The ID of the gene, if stored in the landscapes; see
+    /// [`crate::dbmaker::BuildOptions::rich_landscapes`]
+    pub id: Option<String>,
+    /// The start of the gene, if stored in the landscapes
+    pub pos: Option<usize>,
+    /// How many consecutive genes of the family this entry stands for, more
+    /// than one for collapsed tandem arrays; see
+    /// [`crate::dbmaker::BuildOptions::collapse_tandems`]
+    pub copies: usize,
+}
+impl Default for TailGene {
+    fn default() -> Self {
+        TailGene {
+            family: FamilyID::default(),
+            strand: Strand::default(),
+            id: None,
+            pos: None,
+            copies: 1,
+        }
+    }
+}
+impl TailGene {
+    /// The form of this gene in the landscapes stored in the database, i.e.
+    /// `{strand}{family}`, followed by `*{copies}` for tandem arrays and by
+    /// `:{start}:{ID}` if they are known; `.` and `%` are percent-encoded in
+    /// IDs, and unknown strands are written `?` as `.` separates entries
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn encode(&self) -> String {
+        let strand = match self.strand {
+            Strand::Unknown => '?',
+            strand => strand.into(),
+        };
+        let copies = if self.copies > 1 {
+            format!("*{}", self.copies)
+        } else {
+            String::new()
+        };
+        match (self.pos, self.id.as_ref()) {
+            (Some(pos), Some(id)) => format!(
+                "{}{}{}:{}:{}",
+                strand,
+                self.family,
+                copies,
+                pos,
+                id.replace('%', "%25").replace('.', "%2E")
+            ),
+            _ => format!("{}{}{}", strand, self.family, copies),
+        }
+    }
+
+    /// Fold the tandem copies `other`, following this entry, into it; the
+    /// start and ID of the first copy are kept, and the strand if they share
+    /// it
+    pub(crate) fn absorb(&mut self, other: &TailGene) {
+        self.copies += other.copies;
+        if self.strand != other.strand {
+            self.strand = Strand::Unknown;
+        }
+    }
+
+    /// Collapse the runs of consecutive entries of the same family of
+    /// `landscape` into single entries
+    pub fn collapse(landscape: Vec<TailGene>) -> Vec<TailGene> {
+        let mut r = Vec::<TailGene>::with_capacity(landscape.len());
+        for t in landscape {
+            match r.last_mut() {
+                Some(last) if last.family == t.family => last.absorb(&t),
+                _ => r.push(t),
+            }
+        }
+        r
+    }
 }
 impl std::fmt::Debug for TailGene {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> Result<(), std::fmt::Error> {
@@ -42,16 +631,86 @@ impl std::cmp::PartialEq for TailGene {
 }
 impl std::cmp::Eq for TailGene {}
 
+/// The indices of the genes up to `window` away on the left and on the right
+/// of the `j`-th one of `len` genes, in chromosome order; on a circular
+/// chromosome, they wrap around the origin.
+pub(crate) fn flanks(
+    len: usize,
+    j: usize,
+    window: usize,
+    circular: bool,
+) -> (Vec<usize>, Vec<usize>) {
+    if circular {
+        let w = window.min(len.saturating_sub(1));
+        (
+            (1..=w).rev().map(|o| (j + len - o) % len).collect(),
+            (1..=w).map(|o| (j + o) % len).collect(),
+        )
+    } else {
+        (
+            (j.saturating_sub(window)..j).collect(),
+            (j + 1..len.min(j + window + 1)).collect(),
+        )
+    }
+}
+
+/// As [`flanks`], but each run of consecutive genes of the same family, as
+/// given by `family`, counts as a single gene, so that the flanks span
+/// `window` runs
+pub(crate) fn collapsed_flanks(
+    len: usize,
+    j: usize,
+    window: usize,
+    circular: bool,
+    family: impl Fn(usize) -> FamilyID,
+) -> (Vec<Vec<usize>>, Vec<Vec<usize>>) {
+    let reach = if circular { len.saturating_sub(1) } else { len };
+    let walk = |step: isize| {
+        let mut runs = Vec::<Vec<usize>>::new();
+        for o in 1..=reach {
+            let i = if circular {
+                (j as isize + step * o as isize).rem_euclid(len as isize) as usize
+            } else {
+                match j.checked_add_signed(step * o as isize) {
+                    Some(i) if i < len => i,
+                    _ => break,
+                }
+            };
+            if let Some(run) = runs.last_mut().filter(|run| family(run[0]) == family(i)) {
+                run.push(i);
+            } else if runs.len() == window {
+                break;
+            } else {
+                runs.push(vec![i]);
+            }
+        }
+        runs
+    };
+
+    let mut left = walk(-1);
+    left.reverse();
+    left.iter_mut().for_each(|run| run.reverse());
+    (left, walk(1))
+}
+
 #[derive(Clone, Default)]
+#[cfg_attr(feature = "binary", derive(serde::Serialize, serde::Deserialize))]
 pub struct Gene {
     pub id: String,
     pub species: String,
     pub family: FamilyID,
+    /// The name of the family, e.g. its orthogroup, if known
+    pub family_name: Option<String>,
     pub chr: String,
     pub pos: usize,
+    pub end: usize,
+    /// The length of the gene, in bases
+    pub length: usize,
     pub strand: Strand,
     pub left_landscape: Vec<TailGene>,
     pub right_landscape: Vec<TailGene>,
+    /// The extra attributes stored in the database for this gene
+    pub attributes: HashMap<String, String>,
 }
 impl Gene {
     pub fn landscape(&self) -> impl Iterator<Item = TailGene> + '_ {
@@ -61,217 +720,2140 @@ impl Gene {
             .chain(std::iter::once(TailGene {
                 family: self.family,
                 strand: self.strand,
+                id: Some(self.id.clone()),
+                pos: Some(self.pos),
+                copies: 1,
             }))
             .chain(self.right_landscape.iter().cloned())
     }
-}
 
-impl GeneBook {
-    fn parse_landscape(landscape: &str) -> Vec<TailGene> {
-        fn parse_tailgene(g: &str) -> TailGene {
-            let strand = g
-                .chars()
-                .next()
-                .and_then(|c| c.try_into().ok())
-                .unwrap_or_default();
-            let family_id = g
-                .strip_prefix(['+', '-', '.'])
-                .unwrap_or(g)
-                .parse::<usize>()
-                .unwrap();
-            TailGene {
-                family: family_id,
-                strand,
-            }
-        }
+    /// Collapse the tandem arrays of the landscapes of this gene into
+    /// single entries, e.g. for databases built without
+    /// [`crate::dbmaker::BuildOptions::collapse_tandems`]
+    pub fn collapse_tandems(&mut self) {
+        self.left_landscape = TailGene::collapse(std::mem::take(&mut self.left_landscape));
+        self.right_landscape = TailGene::collapse(std::mem::take(&mut self.right_landscape));
+    }
 
-        if landscape.is_empty() {
-            Vec::new()
-        } else {
-            landscape.split('.').map(parse_tailgene).collect::<Vec<_>>()
+    pub fn length(&self) -> usize {
+        self.length
+    }
+
+    pub fn family_name(&self) -> Option<&str> {
+        self.family_name.as_deref()
+    }
+
+    pub fn position(&self) -> GenomicPosition {
+        GenomicPosition::new(&self.chr, self.pos)
+    }
+
+    /// The stretch of chromosome covered by the gene, `coordinates` being
+    /// the convention of the book it comes from; see [`GeneBook::coordinates`]
+    pub fn interval(&self, coordinates: Coordinates) -> Interval {
+        Interval::new(&self.chr, self.pos, self.end, self.strand, coordinates)
+    }
+
+    /// The number of bases between this gene and `other`, 0 if they
+    /// overlap, or `None` if they do not lie on the same chromosome
+    pub fn distance_to(&self, other: &Gene) -> Option<usize> {
+        if self.species != other.species {
+            return None;
         }
+        // Distances do not depend on the convention, as long as both genes
+        // follow the same one
+        let span = |g: &Gene| Interval {
+            chr: g.chr.clone(),
+            start: g.pos,
+            end: g.pos + g.length,
+            strand: g.strand,
+        };
+        span(self).distance_to(&span(other))
     }
+}
 
-    fn get_rows<P: rusqlite::Params>(
-        mut query: rusqlite::Statement,
-        params: P,
-        window: usize,
-    ) -> Result<HashMap<String, Gene>> {
-        let genes = query
-            .query_map(params, |r| {
-                std::result::Result::Ok((
-                    r.get::<_, String>(0)?, // id
-                    r.get::<_, String>(1)?, // left tail
-                    r.get::<_, String>(2)?, // right tail
-                    r.get::<_, usize>(3)?,  // ancestral id
-                    r.get::<_, String>(4)?, // species
-                    r.get::<_, String>(5)?, // chr
-                    r.get::<_, usize>(6)?,  // position
-                    r.get::<_, String>(7)?, // direction
-                ))
-            })?
-            .collect::<Result<Vec<_>, _>>()?;
+/// How [`synteny_score_with`] compares the landscapes of two genes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum SyntenyMetric {
+    /// The share of the families of the smaller landscape also found in
+    /// the other one
+    #[default]
+    SharedFamilies,
+    /// The Jaccard index of the families of both landscapes
+    Jaccard,
+    /// The longest common subsequence of both landscapes, in either
+    /// orientation, relative to the smaller one
+    Ordered,
+}
 
-        Ok(genes
-            .into_iter()
-            .map(|g| {
-                let id = g.0.to_string();
-                let mut left_landscape = Self::parse_landscape(&g.1);
-                left_landscape.reverse();
-                left_landscape.truncate(window);
-                left_landscape.reverse();
+/// The synteny score of `a` and `b` under the default [`SyntenyMetric`],
+/// between 0 and 1
+pub fn synteny_score(a: &Gene, b: &Gene) -> f64 {
+    synteny_score_with(a, b, SyntenyMetric::default())
+}
 
-                let mut right_landscape = Self::parse_landscape(&g.2);
-                right_landscape.truncate(window);
+/// The synteny score of `a` and `b` under `metric`, between 0 and 1; the
+/// landscapes compared include the genes themselves
+pub fn synteny_score_with(a: &Gene, b: &Gene, metric: SyntenyMetric) -> f64 {
+    let a = a.landscape().map(|g| g.family).collect::<Vec<_>>();
+    let b = b.landscape().map(|g| g.family).collect::<Vec<_>>();
+    let smallest = a.len().min(b.len());
+    if smallest == 0 {
+        return 0.;
+    }
 
-                (
-                    g.0.clone(),
-                    Gene {
-                        id,
-                        species: g.4,
-                        family: g.3,
-                        chr: g.5,
-                        pos: g.6,
-                        strand: g.7.as_str().try_into().unwrap(),
-                        left_landscape,
-                        right_landscape,
-                    },
-                )
-            })
-            .collect())
+    match metric {
+        SyntenyMetric::SharedFamilies => {
+            let (small, large) = if a.len() <= b.len() {
+                (&a, &b)
+            } else {
+                (&b, &a)
+            };
+            let large = large.iter().collect::<HashSet<_>>();
+            let small = small.iter().collect::<HashSet<_>>();
+            small.intersection(&large).count() as f64 / small.len() as f64
+        }
+        SyntenyMetric::Jaccard => {
+            let a = a.iter().collect::<HashSet<_>>();
+            let b = b.iter().collect::<HashSet<_>>();
+            a.intersection(&b).count() as f64 / a.union(&b).count() as f64
+        }
+        SyntenyMetric::Ordered => {
+            let reversed = b.iter().rev().cloned().collect::<Vec<_>>();
+            lcs(&a, &b).max(lcs(&a, &reversed)) as f64 / smallest as f64
+        }
     }
+}
 
-    pub fn in_memory(filename: &str, window: usize, id_column: &str) -> Result<Self> {
-        info!("Caching the database...");
+/// The syntenic support required of the pairs of [`GeneBook::homolog_pairs`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct SyntenySupport {
+    pub metric: SyntenyMetric,
+    /// The minimal score of a pair under `metric`
+    pub min_score: f64,
+}
 
-        let conn = Connection::open(filename).map_err(|e| errors::DataError::FailedToConnect {
-            source: e,
-            filename: filename.into(),
-        })?;
-        let query = conn.prepare(&format!(
-            "SELECT {id_column}, left_tail_ids, right_tail_ids, ancestral_id, species, chr, start, direction FROM genomes"
-        ))?;
-        let genes = Self::get_rows(query, [], window)?;
-        let species = conn
-            .prepare("SELECT DISTINCT species FROM genomes")?
-            .query_map([], |row| row.get::<_, String>(0))?
-            .collect::<Result<Vec<_>, _>>()?;
+/// The length of the longest common subsequence of `a` and `b`
+fn lcs(a: &[FamilyID], b: &[FamilyID]) -> usize {
+    let mut previous = vec![0; b.len() + 1];
+    let mut current = vec![0; b.len() + 1];
+    for x in a {
+        for (j, y) in b.iter().enumerate() {
+            current[j + 1] = if x == y {
+                previous[j] + 1
+            } else {
+                previous[j + 1].max(current[j])
+            };
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    previous[b.len()]
+}
 
-        info!("Done.");
-        Ok(GeneBook::InMemory { genes, species })
+/// The differences between two books, as returned by [`diff`]; all the
+/// lists are sorted by gene ID
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BookDiff {
+    /// The genes only found in the new book
+    pub added: Vec<String>,
+    /// The genes only found in the old book
+    pub removed: Vec<String>,
+    /// The genes whose species, chromosome or coordinates changed
+    pub moved: Vec<String>,
+    /// The genes whose family changed, with their old and new families
+    pub families: Vec<(String, FamilyID, FamilyID)>,
+    /// The genes whose landscape changed
+    pub landscapes: Vec<String>,
+}
+impl BookDiff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty()
+            && self.removed.is_empty()
+            && self.moved.is_empty()
+            && self.families.is_empty()
+            && self.landscapes.is_empty()
     }
+}
 
-    pub fn cached<S: AsRef<str>>(
-        filename: &str,
-        window: usize,
-        id_column: &str,
-        ids: &[S],
-    ) -> Result<Self> {
-        info!("Caching the database...");
+/// Compare the genes of `old` and `new`, e.g. before and after the
+/// re-annotation of a genome
+pub fn diff(old: &GeneBook, new: &GeneBook) -> Result<BookDiff> {
+    let old = old
+        .all_genes()?
+        .into_iter()
+        .map(|g| (g.id.clone(), g))
+        .collect::<HashMap<_, _>>();
+    let new = new.all_genes()?;
 
-        let conn = Connection::open(filename).map_err(|e| errors::DataError::FailedToConnect {
-            source: e,
-            filename: filename.into(),
-        })?;
+    let mut r = BookDiff::default();
+    let mut seen = HashSet::new();
+    for n in new.iter() {
+        seen.insert(n.id.as_str());
+        let Some(o) = old.get(&n.id) else {
+            r.added.push(n.id.clone());
+            continue;
+        };
+        if (&o.species, &o.chr, o.pos, o.end) != (&n.species, &n.chr, n.pos, n.end) {
+            r.moved.push(n.id.clone());
+        }
+        if o.family != n.family {
+            r.families.push((n.id.clone(), o.family, n.family));
+        }
+        if o.left_landscape != n.left_landscape || o.right_landscape != n.right_landscape {
+            r.landscapes.push(n.id.clone());
+        }
+    }
+    r.removed = old
+        .keys()
+        .filter(|id| !seen.contains(id.as_str()))
+        .cloned()
+        .collect();
 
-        let query = conn.prepare(&format!(
-            "SELECT {id_column}, left_tail_ids, right_tail_ids, ancestral_id, species, chr, start, direction FROM genomes WHERE {id_column} IN ({})",
-            std::iter::repeat("?").take(ids.len()).collect::<Vec<_>>().join(", ")
-        ))?;
-        let genes = Self::get_rows(
-            query,
-            rusqlite::params_from_iter(ids.iter().map(|s| s.as_ref())),
-            window,
-        )?;
-        let species = conn
-            .prepare("SELECT DISTINCT species FROM genomes")?
-            .query_map([], |row| row.get::<_, String>(0))?
-            .collect::<Result<Vec<_>, _>>()?;
+    r.added.sort();
+    r.removed.sort();
+    r.moved.sort();
+    r.families.sort();
+    r.landscapes.sort();
+    Ok(r)
+}
+
+/// The formats of [`GeneBook::export_neighborhood`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum NeighborhoodFormat {
+    /// BED6, each gene being named `ID|family` and the focal gene scored
+    /// 1000, the others 0
+    Bed,
+    /// GFF3, each gene carrying its `family` and its `offset` from the focal
+    /// gene as attributes
+    Gff3,
+}
+
+/// The filters of [`GeneBook::query`]; genes must pass all of the set ones
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct GeneQuery {
+    /// The species of the genes
+    pub species: Option<String>,
+    /// The chromosome of the genes, or one of its aliases
+    pub chr: Option<String>,
+    /// The region the genes must overlap, following the coordinate
+    /// convention of the database; see [`GeneBook::genes_in_region`]
+    pub range: Option<Range<usize>>,
+    /// The family of the genes
+    pub family: Option<FamilyID>,
+}
+
+/// Summary statistics of a [`GeneBook`], as returned by [`GeneBook::stats`]
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct BookStats {
+    /// The number of genes of each species
+    pub genes: HashMap<String, usize>,
+    /// The number of genes of each (species, chromosome) pair
+    pub chromosomes: HashMap<(String, String), usize>,
+    /// The number of distinct families
+    pub families: usize,
+    /// The number of distinct families of each species
+    pub species_families: HashMap<String, usize>,
+    /// The number of families of each size
+    pub family_sizes: BTreeMap<usize, usize>,
+    /// The mean number of genes in the landscapes, both sides included
+    pub mean_landscape: f64,
+    /// The median number of genes in the landscapes, both sides included
+    pub median_landscape: f64,
+}
+impl BookStats {
+    fn new<'a>(genes: impl Iterator<Item = &'a Gene>) -> Self {
+        let mut stats = BookStats::default();
+        let mut families = HashMap::<FamilyID, usize>::new();
+        let mut species_families = HashSet::<(&str, FamilyID)>::new();
+        let mut landscapes = Vec::new();
+        for g in genes {
+            species_families.insert((&g.species, g.family));
+            *stats.genes.entry(g.species.clone()).or_default() += 1;
+            *stats
+                .chromosomes
+                .entry((g.species.clone(), g.chr.clone()))
+                .or_default() += 1;
+            *families.entry(g.family).or_default() += 1;
+            landscapes.push(g.left_landscape.len() + g.right_landscape.len());
+        }
 
-        Ok(GeneBook::Cached { genes, species })
+        stats.families = families.len();
+        for (species, _) in species_families {
+            *stats
+                .species_families
+                .entry(species.to_owned())
+                .or_default() += 1;
+        }
+        for size in families.into_values() {
+            *stats.family_sizes.entry(size).or_default() += 1;
+        }
+        stats.set_landscapes(landscapes);
+        stats
     }
 
-    #[allow(dead_code)]
-    pub fn inline(filename: &str, window: usize, id_column: &str) -> Result<Self> {
-        let conn = Connection::open(filename).map_err(|e| errors::DataError::FailedToConnect {
-            source: e,
-            filename: filename.into(),
-        })?;
-        Ok(GeneBook::Inline {
-            conn: Mutex::new(conn),
-            window,
-            id_column: id_column.to_owned(),
-        })
+    /// Compute the statistics of a database from aggregate queries, only
+    /// streaming the landscapes rather than loading all the genes
+    #[cfg(feature = "sqlite")]
+    fn from_db(conn: &Connection, window: usize) -> Result<Self> {
+        let mut stats = BookStats::default();
+        for r in conn
+            .prepare("SELECT species, chr, COUNT(*) FROM genomes GROUP BY species, chr")?
+            .query_map([], |r| {
+                std::result::Result::Ok((r.get::<_, String>(0)?, r.get(1)?, r.get(2)?))
+            })?
+        {
+            let (species, chr, count) = r?;
+            *stats.genes.entry(species.clone()).or_default() += count;
+            stats.chromosomes.insert((species, chr), count);
+        }
+        for r in conn
+            .prepare("SELECT species, COUNT(DISTINCT ancestral_id) FROM genomes GROUP BY species")?
+            .query_map([], |r| std::result::Result::Ok((r.get(0)?, r.get(1)?)))?
+        {
+            let (species, count) = r?;
+            stats.species_families.insert(species, count);
+        }
+        for r in conn
+            .prepare(
+                "SELECT size, COUNT(*) FROM (SELECT COUNT(*) AS size FROM genomes GROUP BY ancestral_id) GROUP BY size",
+            )?
+            .query_map([], |r| std::result::Result::Ok((r.get(0)?, r.get(1)?)))?
+        {
+            let (size, count) = r?;
+            stats.families += count;
+            stats.family_sizes.insert(size, count);
+        }
+
+        let mut landscapes = Vec::new();
+        let mut query = conn.prepare("SELECT left_tail_ids, right_tail_ids FROM genomes")?;
+        let mut rows = query.query([])?;
+        while let Some(r) = rows.next()? {
+            let left = GeneBook::parse_landscape(&r.get::<_, String>(0)?)?.len();
+            let right = GeneBook::parse_landscape(&r.get::<_, String>(1)?)?.len();
+            landscapes.push(left.min(window) + right.min(window));
+        }
+        stats.set_landscapes(landscapes);
+        Ok(stats)
     }
 
-    pub fn get(&self, g: &str) -> Result<Gene> {
-        match self {
-            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => genes
-                .get(g)
-                .cloned()
-                .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()).into()),
-            GeneBook::Inline {
-                conn: conn_mutex,
-                window,
-                id_column,
-            } => {
-                let conn = conn_mutex.lock().expect("MUTEX POISONING");
-                let mut query = conn.prepare(
-                    &format!("SELECT left_tail_ids, right_tail_ids, ancestral_id, species, chr, start, direction FROM genomes WHERE {id_column}=?"),
-                )?;
-                query
-                    .query_row([g], |r| {
-                        let species = r.get::<_, String>(3)?;
-
-                        let mut left_landscape = Self::parse_landscape(&r.get::<_, String>(0)?);
-                        left_landscape.reverse();
-                        left_landscape.truncate(*window);
-                        left_landscape.reverse();
-
-                        let mut right_landscape = Self::parse_landscape(&r.get::<_, String>(1)?);
-                        right_landscape.truncate(*window);
-
-                        let strand = r
-                            .get::<_, String>(6)?
-                            .chars()
-                            .next()
-                            .and_then(|c| c.try_into().ok())
-                            .unwrap_or_default();
-
-                        rusqlite::Result::Ok(Gene {
-                            id: g.to_string(),
-                            species,
-                            family: r.get::<usize, _>(2)?,
-                            chr: r.get::<_, String>(4)?,
-                            pos: r.get::<usize, _>(5)?,
-                            strand,
-                            left_landscape,
-                            right_landscape,
-                        })
-                    })
-                    .with_context(|| "while accessing DB")
-            }
+    fn set_landscapes(&mut self, mut landscapes: Vec<usize>) {
+        if !landscapes.is_empty() {
+            landscapes.sort_unstable();
+            let n = landscapes.len();
+            self.mean_landscape = landscapes.iter().sum::<usize>() as f64 / n as f64;
+            self.median_landscape = if n.is_multiple_of(2) {
+                (landscapes[n / 2 - 1] + landscapes[n / 2]) as f64 / 2.
+            } else {
+                landscapes[n / 2] as f64
+            };
         }
     }
+}
 
-    pub fn get_mut(&mut self, g: &str) -> Result<&mut Gene> {
-        match self {
-            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => genes
-                .get_mut(g)
-                .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()).into()),
-            GeneBook::Inline { .. } => Err(errors::DataError::ImmutableBook.into()),
+/// How many families [`ChromosomeSummary::families`] lists at most
+const DOMINANT_FAMILIES: usize = 5;
+
+/// A chromosome of a species, as returned by [`GeneBook::karyotype`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChromosomeSummary {
+    pub chr: String,
+    /// The number of genes on the chromosome
+    pub genes: usize,
+    /// The start of its first gene
+    pub start: usize,
+    /// The end of its last gene
+    pub end: usize,
+    /// Its most frequent families and their number of genes, most frequent
+    /// first
+    pub families: Vec<(FamilyID, usize)>,
+}
+impl ChromosomeSummary {
+    fn new(chr: &str, genes: &[&Gene]) -> Self {
+        let mut families = HashMap::<FamilyID, usize>::new();
+        for g in genes {
+            *families.entry(g.family).or_default() += 1;
+        }
+        let mut families = families.into_iter().collect::<Vec<_>>();
+        families.sort_by_key(|(family, count)| (std::cmp::Reverse(*count), *family));
+        families.truncate(DOMINANT_FAMILIES);
+        ChromosomeSummary {
+            chr: chr.to_owned(),
+            genes: genes.len(),
+            start: genes.iter().map(|g| g.pos).min().unwrap_or_default(),
+            end: genes.iter().map(|g| g.end).max().unwrap_or_default(),
+            families,
         }
     }
+}
 
-    pub fn species(&self) -> Vec<String> {
-        match self {
-            GeneBook::InMemory { species, .. } | GeneBook::Cached { species, .. } => {
-                species.to_owned()
-            }
-            GeneBook::Inline {
-                conn: conn_mutex, ..
+/// The genes of each chromosome sorted by position, for the region queries
+/// of in-memory books
+#[derive(Default)]
+pub struct Regions {
+    chrs: HashMap<(String, String), SortedChromosome>,
+}
+#[derive(Default)]
+struct SortedChromosome {
+    /// The position and key of the genes
+    genes: Vec<(usize, String)>,
+    /// The length of the longest gene
+    longest: usize,
+}
+impl Regions {
+    fn new(genes: &HashMap<String, Gene>) -> Self {
+        let mut chrs = HashMap::<(String, String), SortedChromosome>::new();
+        for (id, g) in genes.iter() {
+            let chr = chrs.entry((g.species.clone(), g.chr.clone())).or_default();
+            chr.genes.push((g.pos, id.clone()));
+            chr.longest = chr.longest.max(g.length);
+        }
+        for chr in chrs.values_mut() {
+            chr.genes.sort();
+        }
+        Regions { chrs }
+    }
+
+    /// Register the gene `g` under `key`, and return its rank on its
+    /// chromosome
+    fn insert(&mut self, key: &str, g: &Gene) -> usize {
+        let chr = self
+            .chrs
+            .entry((g.species.clone(), g.chr.clone()))
+            .or_default();
+        let entry = (g.pos, key.to_owned());
+        let rank = chr.genes.partition_point(|x| *x < entry);
+        chr.genes.insert(rank, entry);
+        chr.longest = chr.longest.max(g.length);
+        rank
+    }
+
+    /// Forget the gene `g` registered under `key`, and return the rank it
+    /// had on its chromosome
+    fn remove(&mut self, key: &str, g: &Gene) -> Option<usize> {
+        let chr_key = (g.species.clone(), g.chr.clone());
+        let chr = self.chrs.get_mut(&chr_key)?;
+        let rank = chr.genes.binary_search(&(g.pos, key.to_owned())).ok()?;
+        chr.genes.remove(rank);
+        if chr.genes.is_empty() {
+            self.chrs.remove(&chr_key);
+        }
+        Some(rank)
+    }
+
+    /// The keys of the genes of `chr` in `species`, in order
+    fn keys(&self, species: &str, chr: &str) -> Vec<&str> {
+        self.chrs
+            .get(&(species.to_owned(), chr.to_owned()))
+            .map(|c| c.genes.iter().map(|(_, key)| key.as_str()).collect())
+            .unwrap_or_default()
+    }
+
+    /// The keys of the genes of `species` ordered by chromosome then
+    /// position, skipping the first `offset` ones and keeping `limit` of them
+    fn page(&self, species: &str, offset: usize, limit: usize) -> Vec<&str> {
+        let mut chrs = self
+            .chrs
+            .iter()
+            .filter(|((s, _), _)| s == species)
+            .collect::<Vec<_>>();
+        chrs.sort_by(|((_, a), _), ((_, b), _)| natural_cmp(a, b));
+        chrs.into_iter()
+            .flat_map(|(_, c)| c.genes.iter().map(|(_, key)| key.as_str()))
+            .skip(offset)
+            .take(limit)
+            .collect()
+    }
+
+    fn has_species(&self, species: &str) -> bool {
+        self.chrs.keys().any(|(s, _)| s == species)
+    }
+
+    /// The IDs of the genes possibly overlapping `range`, in order
+    fn candidates(&self, species: &str, chr: &str, range: &Range<usize>) -> Vec<&str> {
+        let Some(SortedChromosome { genes, longest }) =
+            self.chrs.get(&(species.to_owned(), chr.to_owned()))
+        else {
+            return Vec::new();
+        };
+        let from = genes.partition_point(|(pos, _)| pos + longest <= range.start);
+        let to = genes.partition_point(|(pos, _)| *pos < range.end);
+        genes[from..to.max(from)]
+            .iter()
+            .map(|(_, id)| id.as_str())
+            .collect()
+    }
+}
+
+/// Open `filename`, read-only if required
+#[cfg(feature = "sqlite")]
+fn open(filename: &str, read_only: bool) -> Result<Connection> {
+    let flags = if read_only {
+        OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX
+    } else {
+        OpenFlags::default()
+    };
+    Connection::open_with_flags(filename, flags).map_err(|e| {
+        errors::DataError::FailedToConnect {
+            source: e,
+            filename: filename.into(),
+        }
+        .into()
+    })
+}
+
+#[cfg(feature = "sqlite")]
+fn has_table(conn: &Connection, table: &str) -> Result<bool> {
+    Ok(conn
+        .prepare("SELECT 1 FROM sqlite_master WHERE type IN ('table', 'view') AND name=?")?
+        .exists([table])?)
+}
+
+#[cfg(feature = "sqlite")]
+fn load_aliases(conn: &Connection) -> Result<ChromAliases> {
+    if !has_table(conn, "chr_aliases")? {
+        return Ok(ChromAliases::default());
+    }
+    let aliases = conn
+        .prepare(&format!(
+            "SELECT species, alias, chr FROM {}",
+            migrate::aliases_source(conn)?
+        ))?
+        .query_map([], |r| {
+            std::result::Result::Ok((
+                r.get::<_, String>(0)?,
+                r.get::<_, String>(1)?,
+                r.get::<_, String>(2)?,
+            ))
+        })?
+        .collect::<Result<ChromAliases, _>>()?;
+    Ok(aliases)
+}
+
+/// The genes of a database by raw ID, its species, its chromosome aliases
+/// and its coordinate convention
+#[cfg(feature = "sqlite")]
+type Loaded = (
+    HashMap<String, Gene>,
+    Vec<String>,
+    ChromAliases,
+    Coordinates,
+);
+
+/// The settings of a database affecting how genes are read from it
+#[cfg(feature = "sqlite")]
+#[derive(Clone)]
+pub struct Schema {
+    pub(crate) coordinates: Coordinates,
+    pub(crate) attributes: Vec<String>,
+    pub(crate) has_families: bool,
+    /// The width of the stored landscapes, if recorded
+    pub(crate) window: Option<usize>,
+    /// The extent of the `neighbors` table, if any
+    pub(crate) neighbors: Option<usize>,
+}
+#[cfg(feature = "sqlite")]
+impl Schema {
+    fn load(conn: &Connection) -> Result<Self> {
+        Ok(Schema {
+            // Databases predating the meta table are 1-based
+            coordinates: meta::get(conn, meta::COORDINATES)?
+                .map(|c| Coordinates::try_from(c.as_str()))
+                .transpose()?
+                .unwrap_or_default(),
+            attributes: meta::get_list(conn, meta::ATTRIBUTES)?,
+            has_families: has_table(conn, "families")?,
+            window: meta::get(conn, meta::WINDOW)?.and_then(|w| w.parse().ok()),
+            neighbors: meta::get(conn, meta::NEIGHBORS)?.and_then(|n| n.parse().ok()),
+        })
+    }
+}
+
+/// The content of a row selected with [`GeneBook::columns`]
+#[cfg(feature = "sqlite")]
+pub(crate) struct RawGene {
+    pub(crate) id: String,
+    pub(crate) left: String,
+    pub(crate) right: String,
+    pub(crate) family: FamilyID,
+    pub(crate) species: String,
+    pub(crate) chr: String,
+    pub(crate) pos: usize,
+    pub(crate) end: usize,
+    pub(crate) direction: String,
+    pub(crate) family_name: Option<String>,
+    pub(crate) attributes: Vec<Option<String>>,
+}
+#[cfg(feature = "sqlite")]
+impl RawGene {
+    pub(crate) fn into_gene(self, window: usize, schema: &Schema) -> Result<Gene, ParseError> {
+        let mut left_landscape = GeneBook::parse_landscape(&self.left)?;
+        left_landscape.reverse();
+        left_landscape.truncate(window);
+        left_landscape.reverse();
+
+        let mut right_landscape = GeneBook::parse_landscape(&self.right)?;
+        right_landscape.truncate(window);
+
+        let strand = self
+            .direction
+            .chars()
+            .next()
+            .and_then(|c| c.try_into().ok())
+            .unwrap_or_default();
+
+        let (start, stop) = schema
+            .coordinates
+            .convert(self.pos, self.end, Coordinates::ZeroBased);
+
+        let attributes = schema
+            .attributes
+            .iter()
+            .zip(self.attributes)
+            .filter_map(|(a, value)| value.map(|v| (a.to_owned(), v)))
+            .collect();
+
+        std::result::Result::Ok(Gene {
+            id: self.id,
+            species: self.species,
+            family: self.family,
+            family_name: self.family_name,
+            chr: self.chr,
+            pos: self.pos,
+            end: self.end,
+            length: stop.saturating_sub(start),
+            strand,
+            left_landscape,
+            right_landscape,
+            attributes,
+        })
+    }
+}
+
+/// Ensure that the schema of the database can be read, and warn if it could
+/// be upgraded
+#[cfg(feature = "sqlite")]
+fn check_schema(conn: &Connection, filename: &str) -> Result<()> {
+    let found = migrate::schema_version(conn)?;
+    if !(migrate::READABLE_SINCE..=migrate::SCHEMA_VERSION).contains(&found) {
+        return Err(errors::DataError::UnsupportedSchema {
+            filename: filename.into(),
+            found,
+            min: migrate::READABLE_SINCE,
+            max: migrate::SCHEMA_VERSION,
+        }
+        .into());
+    }
+    if found < migrate::SCHEMA_VERSION {
+        warn!(
+            "{} uses the outdated schema version {}; consider upgrading it with `dbmaker::migrate`",
+            filename, found
+        );
+    }
+    Ok(())
+}
+
+/// Warn if the landscapes stored in the database are narrower than `window`
+#[cfg(feature = "sqlite")]
+fn check_window(conn: &Connection, filename: &str, window: usize) -> Result<()> {
+    if let Some(stored) = meta::get(conn, meta::WINDOW)? {
+        let stored = stored.parse::<usize>().unwrap_or(usize::MAX);
+        if window > stored {
+            warn!(
+                "{} was built with a window of {}; landscapes will be truncated to it rather than {}",
+                filename, stored, window
+            );
+        }
+    }
+    Ok(())
+}
+
+impl GeneBook {
+    /// Parse a landscape as written by [`TailGene::encode`]
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn parse_landscape(landscape: &str) -> Result<Vec<TailGene>, ParseError> {
+        fn unescape(id: &str) -> String {
+            let mut r = String::with_capacity(id.len());
+            let mut rest = id;
+            while let Some(i) = rest.find('%') {
+                r.push_str(&rest[..i]);
+                match rest.get(i + 1..i + 3) {
+                    Some("2E") => r.push('.'),
+                    Some("25") => r.push('%'),
+                    _ => {
+                        r.push('%');
+                        rest = &rest[i + 1..];
+                        continue;
+                    }
+                }
+                rest = &rest[i + 3..];
+            }
+            r.push_str(rest);
+            r
+        }
+
+        fn parse_tailgene(g: &str) -> Result<TailGene, ParseError> {
+            let invalid = || ParseError::InvalidLandscape(g.to_owned());
+            let strand = match g.chars().next() {
+                Some('?') => Strand::Unknown,
+                Some(c) => c.try_into().unwrap_or_default(),
+                None => return Err(invalid()),
+            };
+            let mut fields = g.strip_prefix(['+', '-', '?']).unwrap_or(g).splitn(3, ':');
+            let family = fields.next().unwrap_or_default();
+            let (family, copies) = match family.split_once('*') {
+                Some((family, copies)) => (family, copies.parse::<usize>().map_err(|_| invalid())?),
+                None => (family, 1),
+            };
+            let family_id = family.parse::<usize>().map_err(|_| invalid())?;
+            let pos = fields
+                .next()
+                .map(|p| p.parse::<usize>().map_err(|_| invalid()))
+                .transpose()?;
+            let id = fields.next().map(unescape);
+            std::result::Result::Ok(TailGene {
+                family: family_id,
+                strand,
+                id,
+                pos,
+                copies,
+            })
+        }
+
+        if landscape.is_empty() {
+            std::result::Result::Ok(Vec::new())
+        } else {
+            landscape.split('.').map(parse_tailgene).collect()
+        }
+    }
+
+    /// The columns to select to build a [`Gene`] with [`GeneBook::make_gene`]
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn columns(id_column: &str, schema: &Schema) -> String {
+        format!(
+            "genomes.{id_column}, genomes.left_tail_ids, genomes.right_tail_ids, genomes.ancestral_id, genomes.species, genomes.chr, genomes.start, genomes.stop, genomes.direction, {}{}",
+            if schema.has_families {
+                "families.name"
+            } else {
+                "NULL"
+            },
+            schema
+                .attributes
+                .iter()
+                .map(|a| format!(", genomes.\"{a}\""))
+                .collect::<String>()
+        )
+    }
+
+    /// The tables to select the columns of [`GeneBook::columns`] from
+    #[cfg(feature = "sqlite")]
+    pub(crate) fn source(schema: &Schema) -> &'static str {
+        if schema.has_families {
+            "genomes LEFT JOIN families ON genomes.ancestral_id = families.id"
+        } else {
+            "genomes"
+        }
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn make_gene(r: &rusqlite::Row, window: usize, schema: &Schema) -> rusqlite::Result<Gene> {
+        RawGene {
+            id: r.get(0)?,
+            left: r.get(1)?,
+            right: r.get(2)?,
+            family: r.get(3)?,
+            species: r.get(4)?,
+            chr: r.get(5)?,
+            pos: r.get(6)?,
+            end: r.get(7)?,
+            direction: r.get(8)?,
+            family_name: r.get(9)?,
+            attributes: (0..schema.attributes.len())
+                .map(|i| r.get(10 + i))
+                .collect::<rusqlite::Result<_>>()?,
+        }
+        .into_gene(window, schema)
+        .map_err(|e| {
+            rusqlite::Error::FromSqlConversionFailure(1, rusqlite::types::Type::Text, Box::new(e))
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn get_rows<P: rusqlite::Params>(
+        mut query: rusqlite::Statement,
+        params: P,
+        window: usize,
+        schema: &Schema,
+    ) -> Result<HashMap<String, Gene>> {
+        Ok(query
+            .query_map(params, |r| {
+                Self::make_gene(r, window, schema).map(|g| (g.id.clone(), g))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?)
+    }
+
+    /// The keys of the genes of each family
+    fn index_families(genes: &HashMap<String, Gene>) -> HashMap<FamilyID, Vec<String>> {
+        let mut r = HashMap::<FamilyID, Vec<String>>::new();
+        for (key, g) in genes.iter() {
+            r.entry(g.family).or_default().push(key.clone());
+        }
+        r
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn in_memory(filename: &str, window: usize, id_column: &str) -> Result<Self> {
+        Self::in_memory_with(filename, window, id_column, &BookOptions::default())
+    }
+
+    /// As [`GeneBook::in_memory`], with the given options
+    #[cfg(feature = "sqlite")]
+    pub fn in_memory_with(
+        filename: &str,
+        window: usize,
+        id_column: &str,
+        options: &BookOptions,
+    ) -> Result<Self> {
+        info!("Caching the database...");
+        let normalizer = options.normalization.compile()?;
+
+        #[cfg(feature = "binary")]
+        let (genes, species, aliases, coordinates) = match options.sidecar.as_ref() {
+            Some(sidecar) => {
+                Self::load_with_sidecar(filename, window, id_column, options, sidecar)?
+            }
+            None => Self::load_all(filename, window, id_column, options)?,
+        };
+        #[cfg(not(feature = "binary"))]
+        let (genes, species, aliases, coordinates) =
+            Self::load_all(filename, window, id_column, options)?;
+        let genes = normalizer.rekey(genes);
+
+        info!("Done.");
+        Ok(GeneBook::InMemory {
+            regions: Regions::new(&genes),
+            coordinates,
+            window,
+            families: options.family_index.then(|| Self::index_families(&genes)),
+            genes,
+            species,
+            aliases,
+            normalizer,
+            liftovers: options.liftovers.clone(),
+        })
+    }
+
+    /// Read all the genes of `filename`, and what comes with them
+    #[cfg(feature = "sqlite")]
+    fn load_all(
+        filename: &str,
+        window: usize,
+        id_column: &str,
+        options: &BookOptions,
+    ) -> Result<Loaded> {
+        let conn = open(filename, options.read_only.unwrap_or(true))?;
+        check_schema(&conn, filename)?;
+        check_window(&conn, filename, window)?;
+        let schema = Schema::load(&conn)?;
+        let query = conn.prepare(&format!(
+            "SELECT {} FROM {}",
+            Self::columns(id_column, &schema),
+            Self::source(&schema)
+        ))?;
+        let genes = Self::get_rows(query, [], window, &schema)?;
+        let species = conn
+            .prepare("SELECT DISTINCT species FROM genomes")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let aliases = load_aliases(&conn)?;
+        Ok((genes, species, aliases, schema.coordinates))
+    }
+
+    #[cfg(feature = "sqlite")]
+    pub fn cached<S: AsRef<str>>(
+        filename: &str,
+        window: usize,
+        id_column: &str,
+        ids: &[S],
+    ) -> Result<Self> {
+        Self::cached_with(filename, window, id_column, ids, &BookOptions::default())
+    }
+
+    /// As [`GeneBook::cached`], with the given options
+    #[cfg(feature = "sqlite")]
+    pub fn cached_with<S: AsRef<str>>(
+        filename: &str,
+        window: usize,
+        id_column: &str,
+        ids: &[S],
+        options: &BookOptions,
+    ) -> Result<Self> {
+        info!("Caching the database...");
+        let normalizer = options.normalization.compile()?;
+
+        let conn = open(filename, options.read_only.unwrap_or(true))?;
+        check_schema(&conn, filename)?;
+        check_window(&conn, filename, window)?;
+
+        let schema = Schema::load(&conn)?;
+        // The stored IDs may only be found once normalized
+        let ids = if normalizer.is_identity() {
+            ids.iter()
+                .map(|s| s.as_ref().to_owned())
+                .collect::<Vec<_>>()
+        } else {
+            let wanted = ids
+                .iter()
+                .map(|s| normalizer.normalize(s.as_ref()).into_owned())
+                .collect::<HashSet<_>>();
+            Self::raw_ids(&conn, id_column, &normalizer)?
+                .into_iter()
+                .filter_map(|(normalized, raw)| wanted.contains(&normalized).then_some(raw))
+                .collect()
+        };
+        let query = conn.prepare(&format!(
+            "SELECT {} FROM {} WHERE genomes.{id_column} IN ({})",
+            Self::columns(id_column, &schema),
+            Self::source(&schema),
+            std::iter::repeat_n("?", ids.len())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ))?;
+        let genes = normalizer.rekey(Self::get_rows(
+            query,
+            rusqlite::params_from_iter(ids.iter()),
+            window,
+            &schema,
+        )?);
+        let species = conn
+            .prepare("SELECT DISTINCT species FROM genomes")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let aliases = load_aliases(&conn)?;
+
+        Ok(GeneBook::Cached {
+            regions: Regions::new(&genes),
+            coordinates: schema.coordinates,
+            window,
+            families: options.family_index.then(|| Self::index_families(&genes)),
+            genes,
+            species,
+            aliases,
+            normalizer,
+            liftovers: options.liftovers.clone(),
+        })
+    }
+
+    #[cfg(feature = "sqlite")]
+    #[allow(dead_code)]
+    pub fn inline(filename: &str, window: usize, id_column: &str) -> Result<Self> {
+        Self::inline_with(filename, window, id_column, &BookOptions::default())
+    }
+
+    /// As [`GeneBook::inline`], with the given options
+    #[cfg(feature = "sqlite")]
+    pub fn inline_with(
+        filename: &str,
+        window: usize,
+        id_column: &str,
+        options: &BookOptions,
+    ) -> Result<Self> {
+        let conn = open(filename, options.read_only.unwrap_or(false))?;
+        check_schema(&conn, filename)?;
+        check_window(&conn, filename, window)?;
+        Self::make_inline(conn, Some(filename), window, id_column, options)
+    }
+
+    /// An inline book that loads the chromosomes it queries often into
+    /// memory in the background, so that it starts as fast as an inline
+    /// book, yet serves hot regions almost as fast as an in-memory one
+    #[cfg(feature = "sqlite")]
+    pub fn hybrid(filename: &str, window: usize, id_column: &str) -> Result<Self> {
+        Self::inline_with(
+            filename,
+            window,
+            id_column,
+            &BookOptions {
+                promote_after: Some(100),
+                ..Default::default()
+            },
+        )
+    }
+
+    /// Read the genes from an open connection, e.g. one returned by
+    /// [`crate::dbmaker::db_in_memory`]
+    #[cfg(feature = "sqlite")]
+    pub fn from_connection(conn: Connection, window: usize, id_column: &str) -> Result<Self> {
+        Self::from_connection_with(conn, window, id_column, &BookOptions::default())
+    }
+
+    /// As [`GeneBook::from_connection`], with the given options
+    #[cfg(feature = "sqlite")]
+    pub fn from_connection_with(
+        conn: Connection,
+        window: usize,
+        id_column: &str,
+        options: &BookOptions,
+    ) -> Result<Self> {
+        check_schema(&conn, ":memory:")?;
+        check_window(&conn, ":memory:", window)?;
+        Self::make_inline(conn, None, window, id_column, options)
+    }
+
+    #[cfg(feature = "sqlite")]
+    fn make_inline(
+        conn: Connection,
+        filename: Option<&str>,
+        window: usize,
+        id_column: &str,
+        options: &BookOptions,
+    ) -> Result<Self> {
+        let schema = Schema::load(&conn)?;
+        let normalizer = options.normalization.compile()?;
+        let raw_ids = if normalizer.is_identity() {
+            HashMap::new()
+        } else {
+            Self::raw_ids(&conn, id_column, &normalizer)?
+        };
+        let promoter = filename
+            .zip(options.promote_after)
+            .map(|(filename, threshold)| {
+                Box::new(Promoter::new(
+                    filename, threshold, window, id_column, &schema,
+                ))
+            });
+        Ok(GeneBook::Inline {
+            conn: Pool::new(conn, filename, options.connections),
+            window,
+            id_column: id_column.to_owned(),
+            schema,
+            normalizer,
+            raw_ids,
+            cache: Mutex::new(GeneCache::new(options.cache_size)),
+            promoter,
+            liftovers: options.liftovers.clone(),
+        })
+    }
+
+    /// Map the normalized IDs stored in the database to their raw form; as
+    /// in [`Normalizer::rekey`], only the smallest of colliding IDs is kept
+    #[cfg(feature = "sqlite")]
+    fn raw_ids(
+        conn: &Connection,
+        id_column: &str,
+        normalizer: &Normalizer,
+    ) -> Result<HashMap<String, String>> {
+        let mut r = HashMap::new();
+        let mut query = conn.prepare(&format!("SELECT {id_column} FROM genomes"))?;
+        for raw in query.query_map([], |r| r.get::<_, String>(0))? {
+            let raw = raw?;
+            let normalized = normalizer.normalize(&raw).into_owned();
+            if let Some(other) = r.get(&normalized) {
+                let keep_other = *other < raw;
+                warn!(
+                    "{} and {} are the same ID once normalized; only {} is kept",
+                    other,
+                    raw,
+                    if keep_other { other } else { &raw }
+                );
+                if keep_other {
+                    continue;
+                }
+            }
+            r.insert(normalized, raw);
+        }
+        Ok(r)
+    }
+
+    /// The raw ID stored in the database for `id`, if it is known
+    #[cfg(feature = "sqlite")]
+    fn resolve<'a>(
+        normalizer: &Normalizer,
+        raw_ids: &'a HashMap<String, String>,
+        id: &'a str,
+    ) -> Option<&'a str> {
+        if normalizer.is_identity() {
+            Some(id)
+        } else {
+            raw_ids
+                .get(normalizer.normalize(id).as_ref())
+                .map(|s| s.as_str())
+        }
+    }
+
+    pub fn get(&self, g: &str) -> Result<Gene> {
+        match self {
+            GeneBook::InMemory {
+                genes, normalizer, ..
+            }
+            | GeneBook::Cached {
+                genes, normalizer, ..
+            } => genes
+                .get(normalizer.normalize(g).as_ref())
+                .cloned()
+                .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()).into()),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                normalizer,
+                raw_ids,
+                cache,
+                promoter,
+                ..
+            } => {
+                let raw = Self::resolve(normalizer, raw_ids, g)
+                    .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()))?;
+                if let Some(gene) = promoter.as_ref().and_then(|p| p.get(raw)) {
+                    return Ok(gene);
+                }
+                let cached = cache.lock().expect("MUTEX POISONING").get(raw);
+                if let Some(gene) = cached {
+                    if let Some(promoter) = promoter {
+                        promoter.touch(&gene.species, &gene.chr);
+                    }
+                    return Ok(gene);
+                }
+                let conn = pool.get()?;
+                let mut query = conn.prepare_cached(&format!(
+                    "SELECT {} FROM {} WHERE genomes.{id_column}=?",
+                    Self::columns(id_column, schema),
+                    Self::source(schema)
+                ))?;
+                let gene = query
+                    .query_row([raw], |r| Self::make_gene(r, *window, schema))
+                    .with_context(|| "while accessing DB")?;
+                if let Some(promoter) = promoter {
+                    promoter.touch(&gene.species, &gene.chr);
+                }
+                cache.lock().expect("MUTEX POISONING").insert(gene.clone());
+                Ok(gene)
+            }
+        }
+    }
+
+    /// As [`GeneBook::get`], with landscapes of `window` genes on each side
+    /// rather than the window of the book. In-memory and cached books may
+    /// only narrow their landscapes; inline books may widen them up to the
+    /// width stored in the database, then to the extent of its `neighbors`
+    /// table.
+    pub fn get_with_window(&self, g: &str, window: usize) -> Result<Gene> {
+        let too_wide = |available: usize| -> Error {
+            errors::DataError::WindowTooWide {
+                requested: window,
+                available,
+            }
+            .into()
+        };
+        match self {
+            GeneBook::InMemory {
+                window: available, ..
+            }
+            | GeneBook::Cached {
+                window: available, ..
+            } => {
+                if window > *available {
+                    return Err(too_wide(*available));
+                }
+                let mut gene = self.get(g)?;
+                Self::narrow(&mut gene, window);
+                Ok(gene)
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                id_column,
+                schema,
+                normalizer,
+                raw_ids,
+                ..
+            } => {
+                let raw = Self::resolve(normalizer, raw_ids, g)
+                    .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()))?;
+                let stored = schema.window.unwrap_or(usize::MAX);
+                let extent = schema.neighbors.unwrap_or_default();
+                if window > stored && window > extent {
+                    return Err(too_wide(stored.max(extent)));
+                }
+                let conn = pool.get()?;
+                let mut query = conn.prepare_cached(&format!(
+                    "SELECT {} FROM {} WHERE genomes.{id_column}=?",
+                    Self::columns(id_column, schema),
+                    Self::source(schema)
+                ))?;
+                let mut gene = query
+                    .query_row([raw], |r| Self::make_gene(r, window, schema))
+                    .with_context(|| "while accessing DB")?;
+                if window > stored {
+                    let mut query = conn.prepare_cached(&format!(
+                        "SELECT neighbors.offset, neighbors.family, neighbors.strand FROM neighbors
+                         WHERE neighbors.gene_id=(SELECT genomes.id FROM genomes WHERE genomes.{id_column}=?)
+                         AND neighbors.offset BETWEEN ? AND ? ORDER BY neighbors.offset"
+                    ))?;
+                    let neighbors = query
+                        .query_map(rusqlite::params![raw, -(window as isize), window], |r| {
+                            std::result::Result::Ok((
+                                r.get::<_, isize>(0)?,
+                                TailGene {
+                                    family: r.get(1)?,
+                                    strand: r
+                                        .get::<_, String>(2)?
+                                        .chars()
+                                        .next()
+                                        .and_then(|c| c.try_into().ok())
+                                        .unwrap_or_default(),
+                                    ..Default::default()
+                                },
+                            ))
+                        })?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    let (left, right): (Vec<_>, Vec<_>) =
+                        neighbors.into_iter().partition(|(o, _)| *o < 0);
+                    gene.left_landscape = left.into_iter().map(|(_, t)| t).collect();
+                    gene.right_landscape = right.into_iter().map(|(_, t)| t).collect();
+                }
+                Ok(gene)
+            }
+        }
+    }
+
+    /// Truncate the landscapes of `gene` to `window` genes on each side
+    fn narrow(gene: &mut Gene, window: usize) {
+        let left = gene.left_landscape.len();
+        gene.left_landscape.drain(..left.saturating_sub(window));
+        gene.right_landscape.truncate(window);
+    }
+
+    /// The genes whose ID matches `pattern`, ordered by ID
+    pub fn find(&self, pattern: &IdPattern) -> Result<Vec<Gene>> {
+        let matcher = pattern.compile()?;
+        let mut genes = match self {
+            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => genes
+                .values()
+                .filter(|g| matcher.matches(&g.id))
+                .cloned()
+                .collect::<Vec<_>>(),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                id_column,
+                ..
+            } => {
+                let ids = {
+                    let conn = pool.get()?;
+                    // Narrow the candidates down in SQL whenever possible
+                    let glob = pattern.as_sql_glob();
+                    let mut query = conn.prepare(&format!(
+                        "SELECT {id_column} FROM genomes{}",
+                        if glob.is_some() {
+                            format!(" WHERE {id_column} GLOB ?")
+                        } else {
+                            String::new()
+                        }
+                    ))?;
+                    let ids = query
+                        .query_map(rusqlite::params_from_iter(glob.iter()), |r| {
+                            r.get::<_, String>(0)
+                        })?
+                        .collect::<Result<Vec<_>, _>>()?;
+                    ids
+                };
+                let ids = ids
+                    .iter()
+                    .filter(|id| matcher.matches(id))
+                    .map(|id| id.as_str());
+                self.get_many(ids)?
+            }
+        };
+        genes.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(genes)
+    }
+
+    /// Fetch the genes `ids`, in the same order, with a single pass over the
+    /// book or a few queries for inline books
+    pub fn get_many<'a, I: IntoIterator<Item = &'a str>>(&self, ids: I) -> Result<Vec<Gene>> {
+        let ids = ids.into_iter().collect::<Vec<_>>();
+        match self {
+            GeneBook::InMemory {
+                genes, normalizer, ..
+            }
+            | GeneBook::Cached {
+                genes, normalizer, ..
+            } => ids
+                .iter()
+                .map(|g| {
+                    genes
+                        .get(normalizer.normalize(g).as_ref())
+                        .cloned()
+                        .ok_or_else(|| errors::DataError::UnknownId(g.to_string()).into())
+                })
+                .collect(),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                normalizer,
+                raw_ids,
+                cache,
+                promoter,
+                ..
+            } => {
+                let raw = ids
+                    .iter()
+                    .map(|g| {
+                        Self::resolve(normalizer, raw_ids, g)
+                            .ok_or_else(|| errors::DataError::UnknownId(g.to_string()).into())
+                    })
+                    .collect::<Result<Vec<_>>>()?;
+                let mut found = HashMap::new();
+                let mut missing = Vec::new();
+                {
+                    let mut cache = cache.lock().expect("MUTEX POISONING");
+                    for g in raw.iter() {
+                        if let Some(gene) = promoter.as_ref().and_then(|p| p.get(g)) {
+                            found.insert(g.to_string(), gene);
+                            continue;
+                        }
+                        match cache.get(g) {
+                            Some(gene) => {
+                                found.insert(g.to_string(), gene);
+                            }
+                            None => missing.push(*g),
+                        }
+                    }
+                }
+                let conn = pool.get()?;
+                // Stay well below the limit on the number of SQL parameters
+                for chunk in missing.chunks(10_000) {
+                    let query = conn.prepare(&format!(
+                        "SELECT {} FROM {} WHERE genomes.{id_column} IN ({})",
+                        Self::columns(id_column, schema),
+                        Self::source(schema),
+                        std::iter::repeat_n("?", chunk.len())
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ))?;
+                    let fetched = Self::get_rows(
+                        query,
+                        rusqlite::params_from_iter(chunk.iter()),
+                        *window,
+                        schema,
+                    )?;
+                    let mut cache = cache.lock().expect("MUTEX POISONING");
+                    for gene in fetched.values() {
+                        cache.insert(gene.clone());
+                    }
+                    found.extend(fetched);
+                }
+                if let Some(promoter) = promoter {
+                    for gene in found.values() {
+                        promoter.touch(&gene.species, &gene.chr);
+                    }
+                }
+                raw.iter()
+                    .map(|g| {
+                        found
+                            .get(*g)
+                            .cloned()
+                            .ok_or_else(|| errors::DataError::UnknownId(g.to_string()).into())
+                    })
+                    .collect()
+            }
+        }
+    }
+
+    pub fn get_mut(&mut self, g: &str) -> Result<&mut Gene> {
+        match self {
+            GeneBook::InMemory {
+                genes, normalizer, ..
+            }
+            | GeneBook::Cached {
+                genes, normalizer, ..
+            } => genes
+                .get_mut(normalizer.normalize(g).as_ref())
+                .ok_or_else(|| errors::DataError::UnknownId(g.to_owned()).into()),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { .. } => Err(errors::DataError::ImmutableBook.into()),
+        }
+    }
+
+    /// Write the genes of an in-memory book to the database `filename`,
+    /// replacing its content, so that the changes made through
+    /// [`GeneBook::get_mut`] persist
+    #[cfg(feature = "sqlite")]
+    pub fn save(&self, filename: &str) -> Result<()> {
+        let mut conn = open(filename, false)?;
+        self.save_into(&mut conn)
+    }
+
+    /// As [`GeneBook::save`], into an open connection
+    #[cfg(feature = "sqlite")]
+    pub fn save_into(&self, conn: &mut Connection) -> Result<()> {
+        match self {
+            GeneBook::InMemory {
+                genes,
+                aliases,
+                coordinates,
+                ..
+            } => save::save(conn, genes, aliases, *coordinates),
+            // Cached books only hold a subset of the genes of their database
+            GeneBook::Cached { .. } => Err(errors::DataError::UnserializableBook("cached").into()),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { .. } => Err(errors::DataError::UnserializableBook("inline").into()),
+        }
+    }
+
+    /// Add `gene` to an in-memory book, and update the landscapes of its
+    /// neighbors; a gene of the same ID is replaced and returned.
+    /// Chromosomes are considered linear.
+    pub fn insert_gene(&mut self, gene: Gene) -> Result<Option<Gene>> {
+        let GeneBook::InMemory {
+            genes,
+            species,
+            regions,
+            normalizer,
+            families,
+            window,
+            ..
+        } = self
+        else {
+            return Err(errors::DataError::UnsupportedEdit.into());
+        };
+        let key = normalizer.normalize(&gene.id).into_owned();
+        let replaced = Self::take_gene(genes, regions, families, *window, &key);
+
+        if !species.contains(&gene.species) {
+            species.push(gene.species.clone());
+        }
+        if let Some(families) = families.as_mut() {
+            families.entry(gene.family).or_default().push(key.clone());
+        }
+        let rank = regions.insert(&key, &gene);
+        let (sp, chr) = (gene.species.clone(), gene.chr.clone());
+        genes.insert(key, gene);
+        Self::relandscape(genes, regions, *window, &sp, &chr, rank);
+        Ok(replaced)
+    }
+
+    /// Remove the gene `id` from an in-memory book, update the landscapes
+    /// of its neighbors, and return it. Chromosomes are considered linear.
+    pub fn remove_gene(&mut self, id: &str) -> Result<Gene> {
+        let GeneBook::InMemory {
+            genes,
+            species,
+            regions,
+            normalizer,
+            families,
+            window,
+            ..
+        } = self
+        else {
+            return Err(errors::DataError::UnsupportedEdit.into());
+        };
+        let key = normalizer.normalize(id).into_owned();
+        let gene = Self::take_gene(genes, regions, families, *window, &key)
+            .ok_or_else(|| errors::DataError::UnknownId(id.to_owned()))?;
+        if !regions.has_species(&gene.species) {
+            species.retain(|s| *s != gene.species);
+        }
+        Ok(gene)
+    }
+
+    /// Remove the gene stored under `key`, if any, from all the structures
+    /// of an in-memory book, and update the landscapes of its neighbors
+    fn take_gene(
+        genes: &mut HashMap<String, Gene>,
+        regions: &mut Regions,
+        families: &mut Option<HashMap<FamilyID, Vec<String>>>,
+        window: usize,
+        key: &str,
+    ) -> Option<Gene> {
+        let gene = genes.remove(key)?;
+        if let Some(members) = families.as_mut().and_then(|f| f.get_mut(&gene.family)) {
+            members.retain(|m| m != key);
+        }
+        if let Some(rank) = regions.remove(key, &gene) {
+            Self::relandscape(genes, regions, window, &gene.species, &gene.chr, rank);
+        }
+        Some(gene)
+    }
+
+    /// Recompute the landscapes of the genes of `chr` in `species` within
+    /// `window` of the one of rank `rank`
+    fn relandscape(
+        genes: &mut HashMap<String, Gene>,
+        regions: &Regions,
+        window: usize,
+        species: &str,
+        chr: &str,
+        rank: usize,
+    ) {
+        let keys = regions.keys(species, chr);
+        // Only keep track of the neighbors themselves, or collapse tandem
+        // arrays, if the landscapes of the chromosome already do
+        let tails = || {
+            keys.iter()
+                .filter_map(|k| genes.get(*k))
+                .flat_map(|g| g.left_landscape.iter().chain(g.right_landscape.iter()))
+        };
+        let rich = tails().any(|t| t.id.is_some());
+        let collapse = tails().any(|t| t.copies > 1);
+        let tail = |genes: &HashMap<String, Gene>, ranks: Vec<usize>| {
+            ranks
+                .into_iter()
+                .filter_map(|r| genes.get(keys[r]))
+                .map(|g| TailGene {
+                    family: g.family,
+                    strand: g.strand,
+                    id: rich.then(|| g.id.clone()),
+                    pos: rich.then_some(g.pos),
+                    copies: 1,
+                })
+                .collect::<Vec<_>>()
+        };
+        let families = keys
+            .iter()
+            .map(|k| genes.get(*k).map(|g| g.family).unwrap_or_default())
+            .collect::<Vec<_>>();
+        // Collapsed landscapes may reach arbitrarily far
+        let affected = if collapse {
+            0..keys.len()
+        } else {
+            rank.saturating_sub(window)..keys.len().min(rank + window + 1)
+        };
+        for j in affected {
+            let (left, right) = if collapse {
+                let (left, right) = collapsed_flanks(keys.len(), j, window, false, |i| families[i]);
+                (
+                    left.into_iter().flatten().collect(),
+                    right.into_iter().flatten().collect(),
+                )
+            } else {
+                flanks(keys.len(), j, window, false)
+            };
+            let left = tail(genes, left);
+            let right = tail(genes, right);
+            let (left, right) = if collapse {
+                (TailGene::collapse(left), TailGene::collapse(right))
+            } else {
+                (left, right)
+            };
+            if let Some(g) = genes.get_mut(keys[j]) {
+                g.left_landscape = left;
+                g.right_landscape = right;
+            }
+        }
+    }
+
+    /// An in-memory book holding only the genes of `species`; inline books
+    /// only load these genes from the database
+    pub fn restrict_to<S: AsRef<str>>(&self, species: &[S]) -> Result<GeneBook> {
+        let wanted = species.iter().map(|s| s.as_ref()).collect::<HashSet<_>>();
+        match self {
+            GeneBook::InMemory {
+                genes,
+                species,
+                aliases,
+                normalizer,
+                coordinates,
+                window,
+                families,
+                liftovers,
+                ..
+            }
+            | GeneBook::Cached {
+                genes,
+                species,
+                aliases,
+                normalizer,
+                coordinates,
+                window,
+                families,
+                liftovers,
+                ..
+            } => {
+                let genes = genes
+                    .iter()
+                    .filter(|(_, g)| wanted.contains(g.species.as_str()))
+                    .map(|(k, g)| (k.clone(), g.clone()))
+                    .collect::<HashMap<_, _>>();
+                Ok(GeneBook::InMemory {
+                    regions: Regions::new(&genes),
+                    coordinates: *coordinates,
+                    window: *window,
+                    families: families.as_ref().map(|_| Self::index_families(&genes)),
+                    genes,
+                    species: species
+                        .iter()
+                        .filter(|s| wanted.contains(s.as_str()))
+                        .cloned()
+                        .collect(),
+                    aliases: aliases.clone(),
+                    normalizer: normalizer.clone(),
+                    liftovers: liftovers.clone(),
+                })
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                normalizer,
+                liftovers,
+                ..
+            } => {
+                let conn = pool.get()?;
+                let wanted = wanted.into_iter().collect::<Vec<_>>();
+                let query = conn.prepare(&format!(
+                    "SELECT {} FROM {} WHERE genomes.species IN ({})",
+                    Self::columns(id_column, schema),
+                    Self::source(schema),
+                    std::iter::repeat_n("?", wanted.len())
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))?;
+                let genes = normalizer.rekey(Self::get_rows(
+                    query,
+                    rusqlite::params_from_iter(wanted.iter()),
+                    *window,
+                    schema,
+                )?);
+                let mut species = genes
+                    .values()
+                    .map(|g| g.species.clone())
+                    .collect::<HashSet<_>>()
+                    .into_iter()
+                    .collect::<Vec<_>>();
+                species.sort();
+                Ok(GeneBook::InMemory {
+                    regions: Regions::new(&genes),
+                    coordinates: schema.coordinates,
+                    window: *window,
+                    families: None,
+                    genes,
+                    species,
+                    aliases: load_aliases(&conn)?,
+                    normalizer: normalizer.clone(),
+                    liftovers: liftovers.clone(),
+                })
+            }
+        }
+    }
+
+    /// All the genes of the book, ordered by species, chromosome and position
+    pub fn all_genes(&self) -> Result<Vec<Gene>> {
+        let mut species = self.species();
+        species.sort();
+        let mut genes = Vec::new();
+        for s in species.iter() {
+            genes.extend(self.genes_of_species(s)?);
+        }
+        Ok(genes)
+    }
+
+    /// All the genes of `species`, ordered by position; see
+    /// [`GenomicPosition`]
+    pub fn genes_of_species(&self, species: &str) -> Result<Vec<Gene>> {
+        let mut genes = match self {
+            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => genes
+                .values()
+                .filter(|g| g.species == species)
+                .cloned()
+                .collect::<Vec<_>>(),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                ..
+            } => {
+                let conn = pool.get()?;
+                let query = conn.prepare(&format!(
+                    "SELECT {} FROM {} WHERE genomes.species=?",
+                    Self::columns(id_column, schema),
+                    Self::source(schema)
+                ))?;
+                Self::get_rows(query, [species], *window, schema)?
+                    .into_values()
+                    .collect()
+            }
+        };
+        genes.sort_by_cached_key(|g| (g.position(), g.id.clone()));
+        Ok(genes)
+    }
+
+    /// At most `limit` genes of `species`, ordered by position as
+    /// [`GenomicPosition`] is, starting from the `offset`-th one, e.g. to
+    /// browse a species without loading all its genes at once
+    pub fn page(&self, species: &str, offset: usize, limit: usize) -> Result<Vec<Gene>> {
+        match self {
+            GeneBook::InMemory { genes, regions, .. } | GeneBook::Cached { genes, regions, .. } => {
+                Ok(regions
+                    .page(species, offset, limit)
+                    .into_iter()
+                    .filter_map(|key| genes.get(key))
+                    .cloned()
+                    .collect())
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                ..
+            } => {
+                let conn = pool.get()?;
+                let mut chrs = conn
+                    .prepare("SELECT chr, COUNT(*) FROM genomes WHERE species=? GROUP BY chr")?
+                    .query_map([species], |r| {
+                        std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, usize>(1)?))
+                    })?
+                    .collect::<Result<Vec<_>, _>>()?;
+                chrs.sort_by(|a, b| natural_cmp(&a.0, &b.0));
+
+                let (mut offset, mut limit) = (offset, limit);
+                let mut genes = Vec::new();
+                for (chr, count) in chrs {
+                    if limit == 0 {
+                        break;
+                    }
+                    if offset >= count {
+                        offset -= count;
+                        continue;
+                    }
+                    let taken = limit.min(count - offset);
+                    let query = conn.prepare(&format!(
+                        "SELECT {} FROM {} WHERE genomes.species=? AND genomes.chr=? ORDER BY genomes.start, genomes.{id_column} LIMIT ? OFFSET ?",
+                        Self::columns(id_column, schema),
+                        Self::source(schema)
+                    ))?;
+                    genes.extend(
+                        Self::get_rows(
+                            query,
+                            rusqlite::params![species, chr, taken, offset],
+                            *window,
+                            schema,
+                        )?
+                        .into_values(),
+                    );
+                    offset = 0;
+                    limit -= taken;
+                }
+                genes.sort_by_cached_key(|g| (g.position(), g.id.clone()));
+                Ok(genes)
+            }
+        }
+    }
+
+    /// All the genes of `family`, ordered by ID; the family index of
+    /// in-memory and cached books reflects the families at load time
+    pub fn genes_of_family(&self, family: FamilyID) -> Result<Vec<Gene>> {
+        let mut genes = match self {
+            GeneBook::InMemory {
+                genes,
+                families: Some(families),
+                ..
+            }
+            | GeneBook::Cached {
+                genes,
+                families: Some(families),
+                ..
+            } => families
+                .get(&family)
+                .into_iter()
+                .flatten()
+                .filter_map(|key| genes.get(key))
+                .filter(|g| g.family == family)
+                .cloned()
+                .collect::<Vec<_>>(),
+            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => genes
+                .values()
+                .filter(|g| g.family == family)
+                .cloned()
+                .collect::<Vec<_>>(),
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                ..
+            } => {
+                let conn = pool.get()?;
+                let query = conn.prepare(&format!(
+                    "SELECT {} FROM {} WHERE genomes.ancestral_id=?",
+                    Self::columns(id_column, schema),
+                    Self::source(schema)
+                ))?;
+                Self::get_rows(query, [family], *window, schema)?
+                    .into_values()
+                    .collect()
+            }
+        };
+        genes.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(genes)
+    }
+
+    /// All the pairs of genes of `species_a` and `species_b` sharing a
+    /// family, ordered by position in `species_a`, then in `species_b`; if
+    /// `support` is set, only the pairs scoring high enough are kept
+    pub fn homolog_pairs(
+        &self,
+        species_a: &str,
+        species_b: &str,
+        support: Option<SyntenySupport>,
+    ) -> Result<Vec<(Gene, Gene)>> {
+        let genes_b = self.genes_of_species(species_b)?;
+        let mut by_family = HashMap::<FamilyID, Vec<&Gene>>::new();
+        for g in genes_b.iter() {
+            by_family.entry(g.family).or_default().push(g);
+        }
+
+        let mut pairs = Vec::new();
+        for a in self.genes_of_species(species_a)? {
+            for b in by_family.get(&a.family).into_iter().flatten() {
+                if support.is_none_or(|s| synteny_score_with(&a, b, s.metric) >= s.min_score) {
+                    pairs.push((a.clone(), (*b).clone()));
+                }
+            }
+        }
+        Ok(pairs)
+    }
+
+    /// Gene, family and landscape statistics over the whole book; inline
+    /// books compute them in the database without loading the genes
+    pub fn stats(&self) -> Result<BookStats> {
+        match self {
+            GeneBook::InMemory { genes, .. } | GeneBook::Cached { genes, .. } => {
+                Ok(BookStats::new(genes.values()))
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool, window, ..
+            } => BookStats::from_db(&*pool.get()?, *window),
+        }
+    }
+
+    /// The chromosomes of `species`, in natural order, with their extent
+    /// and their dominant families, e.g. to scale the axes of whole-genome
+    /// plots
+    pub fn karyotype(&self, species: &str) -> Result<Vec<ChromosomeSummary>> {
+        let genes = self.genes_of_species(species)?;
+        let mut chrs = HashMap::<&str, Vec<&Gene>>::new();
+        for g in genes.iter() {
+            chrs.entry(&g.chr).or_default().push(g);
+        }
+        let mut chrs = chrs.into_iter().collect::<Vec<_>>();
+        chrs.sort_by(|a, b| natural_cmp(a.0, b.0));
+        Ok(chrs
+            .into_iter()
+            .map(|(chr, genes)| ChromosomeSummary::new(chr, &genes))
+            .collect())
+    }
+
+    /// Write the gene `id` and the genes of its landscape to `out`, with
+    /// their coordinates and families, e.g. to be loaded in a genome browser;
+    /// collapsed tandem arrays are written gene by gene, offsets being ranks
+    /// on the chromosome. Cached books must hold the whole neighborhood, and
+    /// fail otherwise.
+    pub fn export_neighborhood<W: std::io::Write>(
+        &self,
+        id: &str,
+        format: NeighborhoodFormat,
+        mut out: W,
+    ) -> Result<()> {
+        let focal = self.get(id)?;
+        let chromosome = self.chromosome(&focal.species, &focal.chr)?;
+        let j = chromosome
+            .iter()
+            .position(|g| g.id == focal.id)
+            .ok_or_else(|| errors::DataError::UnknownId(id.to_owned()))?;
+        // Collapsed tandem arrays stand for several genes, and landscapes
+        // may wrap around circular chromosomes
+        let extent = |landscape: &[TailGene]| landscape.iter().map(|t| t.copies).sum::<usize>();
+        let n = chromosome.len() as isize;
+        let genes = (-(extent(&focal.left_landscape) as isize)
+            ..=extent(&focal.right_landscape) as isize)
+            .map(|o| (o, &chromosome[(j as isize + o).rem_euclid(n) as usize]))
+            .collect::<Vec<_>>();
+        // Cached books may only hold a part of the chromosome, whose genes
+        // would then not be those of the landscapes
+        let expected = focal
+            .left_landscape
+            .iter()
+            .map(|t| (t.family, t.copies))
+            .chain(std::iter::once((focal.family, 1)))
+            .chain(focal.right_landscape.iter().map(|t| (t.family, t.copies)))
+            .flat_map(|(family, copies)| std::iter::repeat_n(family, copies));
+        if !genes.iter().map(|(_, g)| g.family).eq(expected) {
+            return Err(errors::DataError::MismatchedNeighborhood(id.to_owned()).into());
+        }
+
+        let coordinates = self.coordinates();
+        if format == NeighborhoodFormat::Gff3 {
+            writeln!(out, "##gff-version 3")?;
+        }
+        for (offset, g) in genes {
+            let family = g
+                .family_name()
+                .map(|f| f.to_owned())
+                .unwrap_or_else(|| g.family.to_string());
+            match format {
+                NeighborhoodFormat::Bed => {
+                    let (start, end) = coordinates.convert(g.pos, g.end, Coordinates::ZeroBased);
+                    writeln!(
+                        out,
+                        "{}\t{}\t{}\t{}|{}\t{}\t{}",
+                        g.chr,
+                        start,
+                        end,
+                        g.id,
+                        family,
+                        if offset == 0 { 1000 } else { 0 },
+                        g.strand
+                    )?;
+                }
+                NeighborhoodFormat::Gff3 => {
+                    let (start, end) = coordinates.convert(g.pos, g.end, Coordinates::OneBased);
+                    writeln!(
+                        out,
+                        "{}\tsyntesuite\tgene\t{}\t{}\t.\t{}\t.\tID={};family={};offset={}",
+                        g.chr,
+                        start,
+                        end,
+                        g.strand,
+                        gff::escape(&g.id),
+                        gff::escape(&family),
+                        offset
+                    )?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The coordinate convention of [`Gene::pos`] and [`Gene::end`]
+    pub fn coordinates(&self) -> Coordinates {
+        match self {
+            GeneBook::InMemory { coordinates, .. } | GeneBook::Cached { coordinates, .. } => {
+                *coordinates
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { schema, .. } => schema.coordinates,
+        }
+    }
+
+    /// All the genes of `chr` in `species`, ordered by position
+    pub fn chromosome(&self, species: &str, chr: &str) -> Result<Vec<Gene>> {
+        let mut genes = match self {
+            GeneBook::InMemory { genes, regions, .. } | GeneBook::Cached { genes, regions, .. } => {
+                regions
+                    .keys(species, chr)
+                    .into_iter()
+                    .filter_map(|key| genes.get(key))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                promoter,
+                ..
+            } => {
+                if let Some(genes) = promoter.as_ref().and_then(|p| p.chromosome(species, chr)) {
+                    genes
+                } else {
+                    if let Some(promoter) = promoter {
+                        promoter.touch(species, chr);
+                    }
+                    let conn = pool.get()?;
+                    let query = conn.prepare(&format!(
+                        "SELECT {} FROM {} WHERE genomes.species=? AND genomes.chr=?",
+                        Self::columns(id_column, schema),
+                        Self::source(schema)
+                    ))?;
+                    Self::get_rows(query, [species, chr], *window, schema)?
+                        .into_values()
+                        .collect()
+                }
+            }
+        };
+        genes.sort_by(|a, b| (a.pos, &a.id).cmp(&(b.pos, &b.id)));
+        Ok(genes)
+    }
+
+    /// The genes of `chr` in `species` overlapping `range`, ordered by
+    /// position; `range` follows the coordinate convention of the database,
+    /// as [`Gene::pos`] does, but is expressed in the assembly lifted from if
+    /// a liftover is set for `species`; see [`BookOptions::liftovers`]
+    pub fn genes_in_region(
+        &self,
+        species: &str,
+        chr: &str,
+        range: Range<usize>,
+    ) -> Result<Vec<Gene>> {
+        let liftovers = match self {
+            GeneBook::InMemory { liftovers, .. } | GeneBook::Cached { liftovers, .. } => liftovers,
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { liftovers, .. } => liftovers,
+        };
+        let Some(liftover) = liftovers.get(species) else {
+            return self.genes_in_stored_region(species, chr, range);
+        };
+        // Chains are 0-based
+        let shift = match self.coordinates() {
+            Coordinates::OneBased => 1,
+            Coordinates::ZeroBased => 0,
+        };
+        match liftover.lift_range(
+            chr,
+            range.start.saturating_sub(shift)..range.end.saturating_sub(shift),
+        ) {
+            Some((chr, lifted)) => {
+                self.genes_in_stored_region(species, chr, lifted.start + shift..lifted.end + shift)
+            }
+            None => Ok(Vec::new()),
+        }
+    }
+
+    /// As [`GeneBook::genes_in_region`], `range` being expressed in the
+    /// assembly of the database
+    fn genes_in_stored_region(
+        &self,
+        species: &str,
+        chr: &str,
+        range: Range<usize>,
+    ) -> Result<Vec<Gene>> {
+        let overlaps = |g: &Gene| g.pos < range.end && g.pos + g.length > range.start;
+        let mut genes = match self {
+            GeneBook::InMemory { genes, regions, .. } | GeneBook::Cached { genes, regions, .. } => {
+                regions
+                    .candidates(species, chr, &range)
+                    .into_iter()
+                    .filter_map(|id| genes.get(id))
+                    .filter(|g| overlaps(g))
+                    .cloned()
+                    .collect::<Vec<_>>()
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline {
+                conn: pool,
+                window,
+                id_column,
+                schema,
+                promoter,
+                ..
             } => {
-                let conn = conn_mutex.lock().expect("MUTEX POISONING");
+                if let Some(genes) = promoter.as_ref().and_then(|p| p.chromosome(species, chr)) {
+                    genes.into_iter().filter(|g| overlaps(g)).collect()
+                } else {
+                    if let Some(promoter) = promoter {
+                        promoter.touch(species, chr);
+                    }
+                    // The last base of a gene is `stop` if 1-based, `stop - 1` if
+                    // 0-based
+                    let last = match schema.coordinates {
+                        Coordinates::OneBased => "genomes.stop",
+                        Coordinates::ZeroBased => "genomes.stop - 1",
+                    };
+                    let conn = pool.get()?;
+                    let query = conn.prepare(&format!(
+                        "SELECT {} FROM {} WHERE genomes.species=? AND genomes.chr=? AND genomes.start < ? AND {last} >= ?",
+                        Self::columns(id_column, schema),
+                        Self::source(schema)
+                    ))?;
+                    Self::get_rows(
+                        query,
+                        rusqlite::params![species, chr, range.end, range.start],
+                        *window,
+                        schema,
+                    )?
+                    .into_values()
+                    .filter(|g| overlaps(g))
+                    .collect()
+                }
+            }
+        };
+        genes.sort_by(|a, b| (a.pos, &a.id).cmp(&(b.pos, &b.id)));
+        Ok(genes)
+    }
+
+    /// The genes lying at most `bp` bases away from the gene `id` on its
+    /// chromosome, ordered by position
+    pub fn neighbors_within(&self, id: &str, bp: usize) -> Result<Vec<Gene>> {
+        let gene = self.get(id)?;
+        let range = gene.pos.saturating_sub(bp + 1)..gene.pos + gene.length + bp + 1;
+        Ok(self
+            .genes_in_stored_region(&gene.species, &gene.chr, range)?
+            .into_iter()
+            .filter(|g| g.id != gene.id)
+            .filter(|g| gene.distance_to(g).is_some_and(|d| d <= bp))
+            .collect())
+    }
+
+    /// The genes passing all the filters of `query`, ordered by species,
+    /// chromosome and position; the most selective lookup available is used
+    /// to fetch them, the other filters being applied afterwards
+    pub fn query(&self, query: &GeneQuery) -> Result<Vec<Gene>> {
+        // Chromosome aliases are scoped by species
+        let chrs = match (&query.species, &query.chr) {
+            (_, None) => HashMap::new(),
+            (Some(species), Some(chr)) => {
+                HashMap::from([(species.clone(), self.canonical_chr(species, chr)?)])
+            }
+            (None, Some(chr)) => self
+                .species()
+                .into_iter()
+                .map(|species| {
+                    let canonical = self.canonical_chr(&species, chr)?;
+                    Ok((species, canonical))
+                })
+                .collect::<Result<HashMap<_, _>>>()?,
+        };
+        let chr = query.species.as_ref().and_then(|species| chrs.get(species));
+        // Lifted regions may lie on other chromosomes
+        let mut in_region = false;
+        let mut genes = match (&query.species, &chr, &query.range, query.family) {
+            (Some(species), Some(chr), Some(range), _) => {
+                in_region = true;
+                self.genes_in_region(species, chr, range.clone())?
+            }
+            (Some(species), Some(chr), None, _) => self.chromosome(species, chr)?,
+            (_, _, _, Some(family)) => self.genes_of_family(family)?,
+            (Some(species), None, _, _) => self.genes_of_species(species)?,
+            _ => self.all_genes()?,
+        };
+        genes.retain(|g| {
+            query.species.as_ref().is_none_or(|s| g.species == *s)
+                && query.family.is_none_or(|f| g.family == f)
+                && (in_region
+                    || (query.chr.is_none() || chrs.get(&g.species).is_some_and(|c| g.chr == *c))
+                        && query
+                            .range
+                            .as_ref()
+                            .is_none_or(|r| g.pos < r.end && g.pos + g.length > r.start))
+        });
+        genes.sort_by_cached_key(|g| (g.species.clone(), g.position(), g.id.clone()));
+        Ok(genes)
+    }
+
+    pub fn species(&self) -> Vec<String> {
+        match self {
+            GeneBook::InMemory { species, .. } | GeneBook::Cached { species, .. } => {
+                species.to_owned()
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { conn: pool, .. } => {
+                let conn = pool.get().unwrap();
                 let species = conn
                     .prepare("SELECT DISTINCT species FROM genomes")
                     .unwrap()
@@ -283,4 +2865,776 @@ impl GeneBook {
             }
         }
     }
+
+    /// Return the canonical name of `chr` in `species`, resolving it through
+    /// the chromosome aliases stored in the database for this species
+    pub fn canonical_chr(&self, species: &str, chr: &str) -> Result<String> {
+        match self {
+            GeneBook::InMemory { aliases, .. } | GeneBook::Cached { aliases, .. } => {
+                Ok(aliases.resolve(species, chr).to_owned())
+            }
+            #[cfg(feature = "sqlite")]
+            GeneBook::Inline { conn: pool, .. } => {
+                let conn = pool.get()?;
+                if !has_table(&conn, "chr_aliases")? {
+                    return Ok(chr.to_owned());
+                }
+                let canonical = conn
+                    .prepare(&format!(
+                        "SELECT chr FROM {} WHERE species=? AND alias=?",
+                        migrate::aliases_source(&conn)?
+                    ))?
+                    .query_map([species, chr], |r| r.get::<_, String>(0))?
+                    .next()
+                    .transpose()?;
+                Ok(canonical.unwrap_or_else(|| chr.to_owned()))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "duckdb")]
+impl GeneBook {
+    /// Load in memory a database converted to DuckDB with
+    /// [`crate::dbmaker::to_duckdb`]
+    pub fn in_memory_duckdb(filename: &str, window: usize, id_column: &str) -> Result<Self> {
+        info!("Caching the database...");
+
+        let conn = duckdb::Connection::open(filename)
+            .with_context(|| anyhow!("while opening {}", filename))?;
+        let meta = conn
+            .prepare("SELECT key, value FROM meta")?
+            .query_map([], |r| {
+                std::result::Result::Ok((r.get::<_, String>(0)?, r.get::<_, String>(1)?))
+            })?
+            .collect::<Result<HashMap<_, _>, _>>()?;
+        if let Some(stored) = meta.get(meta::WINDOW).and_then(|w| w.parse::<usize>().ok()) {
+            if window > stored {
+                warn!(
+                    "{} was built with a window of {}; landscapes will be truncated to it rather than {}",
+                    filename, stored, window
+                );
+            }
+        }
+        let schema = Schema {
+            coordinates: meta
+                .get(meta::COORDINATES)
+                .map(|c| Coordinates::try_from(c.as_str()))
+                .transpose()?
+                .unwrap_or_default(),
+            attributes: meta
+                .get(meta::ATTRIBUTES)
+                .map(|a| {
+                    a.lines()
+                        .filter(|l| !l.is_empty())
+                        .map(|l| l.to_owned())
+                        .collect()
+                })
+                .unwrap_or_default(),
+            has_families: true,
+            window: meta.get(meta::WINDOW).and_then(|w| w.parse().ok()),
+            neighbors: None,
+        };
+
+        let genes = conn
+            .prepare(&format!(
+                "SELECT {} FROM {}",
+                Self::columns(id_column, &schema),
+                Self::source(&schema)
+            ))?
+            .query_map([], |r| {
+                std::result::Result::Ok(RawGene {
+                    id: r.get(0)?,
+                    left: r.get(1)?,
+                    right: r.get(2)?,
+                    family: r.get::<_, i64>(3)? as FamilyID,
+                    species: r.get(4)?,
+                    chr: r.get(5)?,
+                    pos: r.get::<_, i64>(6)? as usize,
+                    end: r.get::<_, i64>(7)? as usize,
+                    direction: r.get(8)?,
+                    family_name: r.get(9)?,
+                    attributes: (0..schema.attributes.len())
+                        .map(|i| r.get(10 + i))
+                        .collect::<Result<_, _>>()?,
+                })
+            })?
+            .map(|g| anyhow::Ok(g?.into_gene(window, &schema)?))
+            .map(|g| g.map(|g| (g.id.clone(), g)))
+            .collect::<Result<HashMap<_, _>>>()?;
+        let species = conn
+            .prepare("SELECT DISTINCT species FROM genomes")?
+            .query_map([], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        let aliases = conn
+            .prepare("SELECT species, alias, chr FROM chr_aliases")?
+            .query_map([], |r| {
+                std::result::Result::Ok((
+                    r.get::<_, String>(0)?,
+                    r.get::<_, String>(1)?,
+                    r.get::<_, String>(2)?,
+                ))
+            })?
+            .collect::<Result<ChromAliases, _>>()?;
+
+        info!("Done.");
+        Ok(GeneBook::InMemory {
+            regions: Regions::new(&genes),
+            coordinates: schema.coordinates,
+            window,
+            families: None,
+            genes,
+            species,
+            aliases,
+            normalizer: Normalizer::default(),
+            liftovers: HashMap::new(),
+        })
+    }
+}
+
+#[cfg(feature = "binary")]
+mod binary {
+    use super::*;
+    use std::io::{Read, Write};
+
+    /// Leads binary gene books, followed by the format version
+    const MAGIC: &[u8; 8] = b"SYNTBOOK";
+    const VERSION: u32 = 5;
+
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Book {
+        genes: Vec<Gene>,
+        species: Vec<String>,
+        aliases: ChromAliases,
+        coordinates: Coordinates,
+    }
+
+    /// Leads the sidecar caches of in-memory books, followed by the format
+    /// version
+    #[cfg(feature = "sqlite")]
+    const SIDECAR_MAGIC: &[u8; 8] = b"SYNTSIDE";
+
+    /// The genes of a database, as stored in a sidecar cache
+    #[cfg(feature = "sqlite")]
+    #[derive(serde::Serialize, serde::Deserialize)]
+    struct Sidecar {
+        /// The checksum of the database, the window and the ID column the
+        /// genes were read with
+        key: String,
+        book: Book,
+    }
+
+    impl GeneBook {
+        /// As [`GeneBook::load_all`], going through the `sidecar` cache
+        #[cfg(feature = "sqlite")]
+        pub(super) fn load_with_sidecar(
+            filename: &str,
+            window: usize,
+            id_column: &str,
+            options: &BookOptions,
+            sidecar: &str,
+        ) -> Result<Loaded> {
+            let key = format!(
+                "{}:{}:{}",
+                crate::dbmaker::inputs::checksum(filename)?,
+                window,
+                id_column
+            );
+            let cached = std::fs::read(sidecar).ok().and_then(|content| {
+                let content = content
+                    .strip_prefix(SIDECAR_MAGIC)?
+                    .strip_prefix(VERSION.to_le_bytes().as_slice())?;
+                postcard::from_bytes::<Sidecar>(content)
+                    .ok()
+                    .filter(|s| s.key == key)
+            });
+            if let Some(Sidecar { book, .. }) = cached {
+                debug!("Reading the genes from {}", sidecar);
+                let genes = book.genes.into_iter().map(|g| (g.id.clone(), g)).collect();
+                return Ok((genes, book.species, book.aliases, book.coordinates));
+            }
+
+            let (genes, species, aliases, coordinates) =
+                Self::load_all(filename, window, id_column, options)?;
+            debug!("Writing the genes to {}", sidecar);
+            let sidecar_content = Sidecar {
+                key,
+                book: Book {
+                    genes: genes.values().cloned().collect(),
+                    species: species.clone(),
+                    aliases: aliases.clone(),
+                    coordinates,
+                },
+            };
+            let written = postcard::to_stdvec(&sidecar_content)
+                .map_err(Error::from)
+                .and_then(|content| {
+                    let mut out = std::fs::File::create(sidecar)?;
+                    out.write_all(SIDECAR_MAGIC)?;
+                    out.write_all(&VERSION.to_le_bytes())?;
+                    out.write_all(&content)?;
+                    Ok(())
+                });
+            if let Err(e) = written {
+                warn!("failed to write the sidecar cache {}: {}", sidecar, e);
+            }
+            Ok((genes, species, aliases, coordinates))
+        }
+
+        /// Write an in-memory or cached book to `filename`, to be read back
+        /// with [`GeneBook::from_binary`] without going through SQLite
+        pub fn to_binary(&self, filename: &str) -> Result<()> {
+            let (genes, species, aliases, coordinates) = match self {
+                GeneBook::InMemory {
+                    genes,
+                    species,
+                    aliases,
+                    coordinates,
+                    ..
+                }
+                | GeneBook::Cached {
+                    genes,
+                    species,
+                    aliases,
+                    coordinates,
+                    ..
+                } => (genes, species, aliases, *coordinates),
+                #[cfg(feature = "sqlite")]
+                GeneBook::Inline { .. } => {
+                    return Err(errors::DataError::UnserializableBook("inline").into())
+                }
+            };
+            let mut genes = genes.values().cloned().collect::<Vec<_>>();
+            genes.sort_by(|a, b| a.id.cmp(&b.id));
+            let book = Book {
+                genes,
+                species: species.clone(),
+                aliases: aliases.clone(),
+                coordinates,
+            };
+
+            let mut out = std::io::BufWriter::new(
+                std::fs::File::create(filename)
+                    .with_context(|| anyhow!("while creating {}", filename))?,
+            );
+            out.write_all(MAGIC)?;
+            out.write_all(&VERSION.to_le_bytes())?;
+            out.write_all(&postcard::to_stdvec(&book)?)?;
+            out.flush()?;
+            Ok(())
+        }
+
+        /// Load in memory a book written with [`GeneBook::to_binary`]
+        pub fn from_binary(filename: &str) -> Result<Self> {
+            let mut content = Vec::new();
+            std::fs::File::open(filename)
+                .with_context(|| anyhow!("while opening {}", filename))?
+                .read_to_end(&mut content)?;
+            let book = content
+                .strip_prefix(MAGIC)
+                .and_then(|c| c.strip_prefix(VERSION.to_le_bytes().as_slice()))
+                .ok_or_else(|| errors::DataError::NotABinaryBook(filename.into()))?;
+            let book: Book = postcard::from_bytes(book)
+                .map_err(|_| errors::DataError::NotABinaryBook(filename.into()))?;
+
+            let genes = book
+                .genes
+                .into_iter()
+                .map(|g| (g.id.clone(), g))
+                .collect::<HashMap<_, _>>();
+            Ok(GeneBook::InMemory {
+                regions: Regions::new(&genes),
+                coordinates: book.coordinates,
+                window: genes
+                    .values()
+                    .map(|g| g.left_landscape.len().max(g.right_landscape.len()))
+                    .max()
+                    .unwrap_or_default(),
+                families: None,
+                genes,
+                species: book.species,
+                aliases: book.aliases,
+                normalizer: Normalizer::default(),
+                liftovers: HashMap::new(),
+            })
+        }
+    }
+}
+
+#[cfg(feature = "mmap")]
+pub use mapped::MappedBook;
+
+#[cfg(feature = "mmap")]
+mod mapped {
+    use super::*;
+    use std::io::Write;
+
+    /// Leads memory-mapped gene books, followed by the format version
+    const MAGIC: &[u8; 8] = b"SYNTMMAP";
+    const VERSION: u32 = 2;
+    /// magic, version, window, number of genes, number of slots
+    const HEADER: usize = 8 + 4 + 4 + 8 + 8;
+    /// family, start, stop, length, strand, data offset, landscapes offset,
+    /// left and right landscape lengths
+    const RECORD: usize = 8 * 4 + 8 + 8 + 8 + 4 + 4;
+    /// An empty slot of the hash table
+    const EMPTY: u64 = u64::MAX;
+
+    /// FNV-1a, as the hash of the index must not change across builds
+    fn hash(id: &str) -> u64 {
+        id.bytes().fold(0xcbf29ce484222325, |h, b| {
+            (h ^ b as u64).wrapping_mul(0x100000001b3)
+        })
+    }
+
+    fn strand_code(strand: Strand) -> u8 {
+        match strand {
+            Strand::Direct => 0,
+            Strand::Reverse => 1,
+            Strand::Unknown => 2,
+        }
+    }
+
+    fn put_str(out: &mut Vec<u8>, s: &str) {
+        out.extend((s.len() as u32).to_le_bytes());
+        out.extend(s.as_bytes());
+    }
+
+    impl GeneBook {
+        /// Write the genes of this book to `filename` as an index that
+        /// [`MappedBook`] maps in memory rather than loads, so that even
+        /// the largest books open instantly
+        pub fn to_mapped(&self, filename: &str) -> Result<()> {
+            let mut genes = self.all_genes()?;
+            genes.sort_by(|a, b| a.id.cmp(&b.id));
+            let window = genes
+                .iter()
+                .map(|g| g.left_landscape.len().max(g.right_landscape.len()))
+                .max()
+                .unwrap_or_default();
+
+            let slots = (2 * genes.len()).next_power_of_two();
+            let mut table = vec![EMPTY; slots];
+            for (i, g) in genes.iter().enumerate() {
+                let mut slot = hash(&g.id) as usize & (slots - 1);
+                while table[slot] != EMPTY {
+                    slot = (slot + 1) & (slots - 1);
+                }
+                table[slot] = i as u64;
+            }
+
+            let heap_start = (HEADER + 8 * slots + RECORD * genes.len()) as u64;
+            let mut records = Vec::with_capacity(RECORD * genes.len());
+            let mut heap = Vec::new();
+            for g in genes.iter() {
+                let data = heap_start + heap.len() as u64;
+                put_str(&mut heap, &g.id);
+                put_str(&mut heap, &g.species);
+                put_str(&mut heap, &g.chr);
+                match g.family_name.as_ref() {
+                    Some(name) => {
+                        heap.push(1);
+                        put_str(&mut heap, name);
+                    }
+                    None => heap.push(0),
+                }
+                let mut attributes = g.attributes.iter().collect::<Vec<_>>();
+                attributes.sort();
+                heap.extend((attributes.len() as u32).to_le_bytes());
+                for (k, v) in attributes {
+                    put_str(&mut heap, k);
+                    put_str(&mut heap, v);
+                }
+
+                let landscapes = heap_start + heap.len() as u64;
+                for t in g.left_landscape.iter().chain(g.right_landscape.iter()) {
+                    heap.extend((t.family as u64).to_le_bytes());
+                    heap.push(strand_code(t.strand));
+                    heap.push(
+                        t.pos.is_some() as u8
+                            | (t.id.is_some() as u8) << 1
+                            | ((t.copies > 1) as u8) << 2,
+                    );
+                    if let Some(pos) = t.pos {
+                        heap.extend((pos as u64).to_le_bytes());
+                    }
+                    if let Some(id) = t.id.as_ref() {
+                        put_str(&mut heap, id);
+                    }
+                    if t.copies > 1 {
+                        heap.extend((t.copies as u64).to_le_bytes());
+                    }
+                }
+
+                for x in [g.family, g.pos, g.end, g.length] {
+                    records.extend((x as u64).to_le_bytes());
+                }
+                records.extend((strand_code(g.strand) as u64).to_le_bytes());
+                records.extend(data.to_le_bytes());
+                records.extend(landscapes.to_le_bytes());
+                records.extend((g.left_landscape.len() as u32).to_le_bytes());
+                records.extend((g.right_landscape.len() as u32).to_le_bytes());
+            }
+
+            let mut out = std::io::BufWriter::new(
+                std::fs::File::create(filename)
+                    .with_context(|| anyhow!("while creating {}", filename))?,
+            );
+            out.write_all(MAGIC)?;
+            out.write_all(&VERSION.to_le_bytes())?;
+            out.write_all(&(window as u32).to_le_bytes())?;
+            out.write_all(&(genes.len() as u64).to_le_bytes())?;
+            out.write_all(&(slots as u64).to_le_bytes())?;
+            for slot in table {
+                out.write_all(&slot.to_le_bytes())?;
+            }
+            out.write_all(&records)?;
+            out.write_all(&heap)?;
+            out.flush()?;
+            Ok(())
+        }
+    }
+
+    /// A read-only gene book, memory-mapping an index written by
+    /// [`GeneBook::to_mapped`]; genes are looked up by their stored ID
+    /// through a hash table and decoded on demand, so that it opens
+    /// instantly and only keeps in memory the pages it touches
+    pub struct MappedBook {
+        filename: String,
+        map: memmap2::Mmap,
+        window: usize,
+        count: usize,
+        slots: usize,
+    }
+
+    /// Reads the fields of a gene from the mapped file
+    struct Reader<'a> {
+        data: &'a [u8],
+        at: usize,
+    }
+    impl<'a> Reader<'a> {
+        fn bytes(&mut self, n: usize) -> Option<&'a [u8]> {
+            let r = self.data.get(self.at..self.at.checked_add(n)?)?;
+            self.at += n;
+            Some(r)
+        }
+
+        fn u8(&mut self) -> Option<u8> {
+            self.bytes(1).map(|b| b[0])
+        }
+
+        fn u32(&mut self) -> Option<u32> {
+            self.bytes(4)
+                .map(|b| u32::from_le_bytes(b.try_into().unwrap()))
+        }
+
+        fn u64(&mut self) -> Option<u64> {
+            self.bytes(8)
+                .map(|b| u64::from_le_bytes(b.try_into().unwrap()))
+        }
+
+        fn usize(&mut self) -> Option<usize> {
+            self.u64().and_then(|x| x.try_into().ok())
+        }
+
+        fn str(&mut self) -> Option<&'a str> {
+            let len = self.u32()? as usize;
+            std::str::from_utf8(self.bytes(len)?).ok()
+        }
+
+        fn strand(&mut self) -> Option<Strand> {
+            match self.u8()? {
+                0 => Some(Strand::Direct),
+                1 => Some(Strand::Reverse),
+                2 => Some(Strand::Unknown),
+                _ => None,
+            }
+        }
+    }
+
+    impl MappedBook {
+        /// Map the index stored in `filename`
+        pub fn open(filename: &str) -> Result<Self> {
+            let file = std::fs::File::open(filename)
+                .with_context(|| anyhow!("while opening {}", filename))?;
+            // SAFETY: the index is never written to once built; should it be
+            // truncated while mapped anyway, reads are bound-checked
+            let map = unsafe { memmap2::Mmap::map(&file) }
+                .with_context(|| anyhow!("while mapping {}", filename))?;
+            let invalid = || errors::DataError::NotAMappedBook(filename.into());
+
+            let mut header = Reader { data: &map, at: 0 };
+            if header.bytes(MAGIC.len()) != Some(MAGIC.as_slice()) || header.u32() != Some(VERSION)
+            {
+                return Err(invalid().into());
+            }
+            let window = header.u32().ok_or_else(invalid)? as usize;
+            let count = header.usize().ok_or_else(invalid)?;
+            let slots = header.usize().ok_or_else(invalid)?;
+            let size = slots
+                .checked_mul(8)
+                .and_then(|t| count.checked_mul(RECORD)?.checked_add(t))
+                .and_then(|s| s.checked_add(HEADER));
+            if !slots.is_power_of_two() || size.is_none_or(|s| s > map.len()) {
+                return Err(invalid().into());
+            }
+
+            Ok(MappedBook {
+                filename: filename.to_owned(),
+                map,
+                window,
+                count,
+                slots,
+            })
+        }
+
+        /// The number of genes on each side of the landscapes
+        pub fn window(&self) -> usize {
+            self.window
+        }
+
+        pub fn len(&self) -> usize {
+            self.count
+        }
+
+        pub fn is_empty(&self) -> bool {
+            self.count == 0
+        }
+
+        fn corrupted(&self) -> Error {
+            errors::DataError::NotAMappedBook(self.filename.clone()).into()
+        }
+
+        fn reader(&self, at: usize) -> Reader<'_> {
+            Reader {
+                data: &self.map,
+                at,
+            }
+        }
+
+        /// The rank of the record of `id`, if it is stored
+        fn find(&self, id: &str) -> Result<Option<usize>> {
+            let mut slot = hash(id) as usize & (self.slots - 1);
+            for _ in 0..self.slots {
+                let record = self
+                    .reader(HEADER + 8 * slot)
+                    .u64()
+                    .ok_or_else(|| self.corrupted())?;
+                if record == EMPTY {
+                    return Ok(None);
+                }
+                let record = usize::try_from(record)
+                    .ok()
+                    .filter(|r| *r < self.count)
+                    .ok_or_else(|| self.corrupted())?;
+                let data = self
+                    .reader(self.record(record) + 8 * 5)
+                    .usize()
+                    .ok_or_else(|| self.corrupted())?;
+                if self.reader(data).str() == Some(id) {
+                    return Ok(Some(record));
+                }
+                slot = (slot + 1) & (self.slots - 1);
+            }
+            Ok(None)
+        }
+
+        /// The offset of the `i`-th record
+        fn record(&self, i: usize) -> usize {
+            HEADER + 8 * self.slots + RECORD * i
+        }
+
+        fn decode(&self, i: usize) -> Option<Gene> {
+            let mut record = self.reader(self.record(i));
+            let family = record.usize()?;
+            let pos = record.usize()?;
+            let end = record.usize()?;
+            let length = record.usize()?;
+            let strand = record.strand()?;
+            record.bytes(7)?;
+            let mut data = self.reader(record.usize()?);
+            let mut landscapes = self.reader(record.usize()?);
+            let left = record.u32()? as usize;
+            let right = record.u32()? as usize;
+
+            let id = data.str()?.to_owned();
+            let species = data.str()?.to_owned();
+            let chr = data.str()?.to_owned();
+            let family_name = match data.u8()? {
+                0 => None,
+                _ => Some(data.str()?.to_owned()),
+            };
+            let attributes = (0..data.u32()?)
+                .map(|_| Some((data.str()?.to_owned(), data.str()?.to_owned())))
+                .collect::<Option<HashMap<_, _>>>()?;
+
+            let mut tails = (0..left + right)
+                .map(|_| {
+                    let family = landscapes.usize()?;
+                    let strand = landscapes.strand()?;
+                    let flags = landscapes.u8()?;
+                    let pos = if flags & 1 != 0 {
+                        Some(landscapes.usize()?)
+                    } else {
+                        None
+                    };
+                    let id = if flags & 2 != 0 {
+                        Some(landscapes.str()?.to_owned())
+                    } else {
+                        None
+                    };
+                    let copies = if flags & 4 != 0 {
+                        landscapes.usize()?
+                    } else {
+                        1
+                    };
+                    Some(TailGene {
+                        family,
+                        strand,
+                        id,
+                        pos,
+                        copies,
+                    })
+                })
+                .collect::<Option<Vec<_>>>()?;
+            let right_landscape = tails.split_off(left);
+
+            Some(Gene {
+                id,
+                species,
+                family,
+                family_name,
+                chr,
+                pos,
+                end,
+                length,
+                strand,
+                left_landscape: tails,
+                right_landscape,
+                attributes,
+            })
+        }
+
+        pub fn contains(&self, id: &str) -> Result<bool> {
+            Ok(self.find(id)?.is_some())
+        }
+
+        pub fn get(&self, id: &str) -> Result<Gene> {
+            let i = self
+                .find(id)?
+                .ok_or_else(|| errors::DataError::UnknownId(id.to_owned()))?;
+            self.decode(i).ok_or_else(|| self.corrupted())
+        }
+
+        pub fn get_many<'a, I: IntoIterator<Item = &'a str>>(&self, ids: I) -> Result<Vec<Gene>> {
+            ids.into_iter().map(|id| self.get(id)).collect()
+        }
+    }
+}
+
+#[cfg(all(test, feature = "sqlite"))]
+mod tests {
+    use super::*;
+
+    fn tail(
+        family: FamilyID,
+        strand: Strand,
+        copies: usize,
+        rich: Option<(usize, &str)>,
+    ) -> TailGene {
+        TailGene {
+            family,
+            strand,
+            id: rich.map(|(_, id)| id.to_owned()),
+            pos: rich.map(|(pos, _)| pos),
+            copies,
+        }
+    }
+
+    fn roundtrip(landscape: &[TailGene]) -> Vec<TailGene> {
+        let encoded = landscape
+            .iter()
+            .map(|t| t.encode())
+            .collect::<Vec<_>>()
+            .join(".");
+        GeneBook::parse_landscape(&encoded).unwrap()
+    }
+
+    /// All the fields of the entries of `landscape`, which are only compared
+    /// by family otherwise
+    fn fields(landscape: &[TailGene]) -> Vec<String> {
+        landscape
+            .iter()
+            .map(|t| {
+                format!(
+                    "{}{}*{}:{:?}:{:?}",
+                    t.strand, t.family, t.copies, t.pos, t.id
+                )
+            })
+            .collect()
+    }
+
+    #[test]
+    fn landscape_roundtrip() {
+        let plain = vec![
+            tail(3, Strand::Direct, 1, None),
+            tail(12, Strand::Reverse, 1, None),
+        ];
+        assert_eq!(fields(&roundtrip(&plain)), fields(&plain));
+        assert!(roundtrip(&[]).is_empty());
+    }
+
+    #[test]
+    fn collapsed_landscape_roundtrip() {
+        let collapsed = TailGene::collapse(vec![
+            tail(3, Strand::Direct, 1, None),
+            tail(3, Strand::Direct, 1, None),
+            tail(3, Strand::Direct, 1, None),
+            tail(7, Strand::Reverse, 1, None),
+        ]);
+        assert_eq!(fields(&collapsed), ["+3*3:None:None", "-7*1:None:None"]);
+        assert_eq!(fields(&roundtrip(&collapsed)), fields(&collapsed));
+    }
+
+    #[test]
+    fn rich_landscape_roundtrip() {
+        let rich = vec![
+            tail(3, Strand::Direct, 1, Some((100, "ENSG01.2"))),
+            tail(4, Strand::Reverse, 2, Some((250, "odd%2Eid"))),
+            tail(5, Strand::Direct, 1, Some((400, "a:b"))),
+        ];
+        assert_eq!(fields(&roundtrip(&rich)), fields(&rich));
+    }
+
+    #[test]
+    fn mixed_strand_landscape_roundtrip() {
+        let mixed = vec![
+            tail(3, Strand::Unknown, 1, Some((100, "g1"))),
+            tail(3, Strand::Reverse, 1, None),
+            tail(8, Strand::Unknown, 1, None),
+        ];
+        assert_eq!(fields(&roundtrip(&mixed)), fields(&mixed));
+    }
+
+    #[test]
+    fn collapsed_mixed_strands() {
+        let collapsed = TailGene::collapse(vec![
+            tail(3, Strand::Direct, 1, Some((100, "g1"))),
+            tail(3, Strand::Reverse, 1, Some((200, "g2"))),
+            tail(8, Strand::Unknown, 1, None),
+        ]);
+        assert_eq!(collapsed[0].strand, Strand::Unknown);
+        assert_eq!(fields(&roundtrip(&collapsed)), fields(&collapsed));
+    }
+
+    #[test]
+    fn invalid_landscapes() {
+        for landscape in ["+x", "+3*", "+3:abc:g1", "+3..-4"] {
+            assert!(
+                GeneBook::parse_landscape(landscape).is_err(),
+                "{} was accepted",
+                landscape
+            );
+        }
+    }
 }
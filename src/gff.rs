@@ -4,7 +4,7 @@ use std::io::prelude::*;
 use std::io::{BufReader, Lines};
 use thiserror::Error;
 
-use crate::{Phase, Strand};
+use crate::{output::RecordSink, Coordinates, Phase, Record, Strand};
 
 #[derive(Debug, Error)]
 pub enum GffError {
@@ -16,7 +16,7 @@ pub enum GffError {
 }
 
 /// A key to a GFF3 record attribute, as defined in http://gmod.org/wiki/GFF3
-#[derive(Eq, Hash, Clone, Debug)]
+#[derive(PartialEq, Eq, Hash, Clone, Debug)]
 pub enum Key {
     ID,
     Name,
@@ -30,24 +30,6 @@ pub enum Key {
     OntologyTerm,
     K(String),
 }
-impl std::cmp::PartialEq for Key {
-    fn eq(&self, other: &Self) -> bool {
-        match (self, other) {
-            (Key::ID, Key::ID) => true,
-            (Key::Name, Key::Name) => true,
-            (Key::Alias, Key::Alias) => true,
-            (Key::Parent, Key::Parent) => true,
-            (Key::Target, Key::Target) => true,
-            (Key::Gap, Key::Gap) => true,
-            (Key::DerivesFrom, Key::DerivesFrom) => true,
-            (Key::Note, Key::Note) => true,
-            (Key::Dbxref, Key::Dbxref) => true,
-            (Key::OntologyTerm, Key::OntologyTerm) => true,
-            (Key::K(k1), Key::K(k2)) => k1 == k2,
-            _ => false,
-        }
-    }
-}
 impl From<&str> for Key {
     fn from(s: &str) -> Self {
         match s.to_lowercase().as_ref() {
@@ -66,6 +48,43 @@ impl From<&str> for Key {
     }
 }
 
+impl std::fmt::Display for Key {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Key::ID => write!(f, "ID"),
+            Key::Name => write!(f, "Name"),
+            Key::Alias => write!(f, "Alias"),
+            Key::Parent => write!(f, "Parent"),
+            Key::Target => write!(f, "Target"),
+            Key::Gap => write!(f, "Gap"),
+            Key::DerivesFrom => write!(f, "Derives_from"),
+            Key::Note => write!(f, "Note"),
+            Key::Dbxref => write!(f, "Dbxref"),
+            Key::OntologyTerm => write!(f, "Ontology_term"),
+            Key::K(k) => write!(f, "{}", k),
+        }
+    }
+}
+
+/// Percent-encode the characters of `value` in `reserved`
+fn percent_encode(value: &str, reserved: &[char]) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if reserved.contains(&c) {
+                format!("%{:02X}", c as u32)
+            } else {
+                c.to_string()
+            }
+        })
+        .collect()
+}
+
+/// Escape the reserved characters of a GFF3 attribute value
+pub(crate) fn escape(value: &str) -> String {
+    percent_encode(value, &[';', '=', '&', ',', '%', '\t', '\n'])
+}
+
 type Attributes = HashMap<Key, Vec<String>>;
 #[derive(Debug)]
 pub struct GffRecord {
@@ -86,7 +105,7 @@ impl GffRecord {
     pub fn id(&self) -> Option<&str> {
         self.attributes
             .get(&Key::ID)
-            .and_then(|x| x.get(0))
+            .and_then(|x| x.first())
             .map(|x| x.as_str())
     }
     pub fn source(&self) -> Option<&String> {
@@ -113,9 +132,14 @@ impl GffRecord {
     pub fn attributes(&self) -> &Attributes {
         &self.attributes
     }
+
+    /// The comma-joined values of the attribute `key`, if present
+    pub fn attribute(&self, key: &str) -> Option<String> {
+        self.attributes.get(&Key::from(key)).map(|v| v.join(","))
+    }
     /// If the record has a Parent attribute, return its first value
     pub fn parent(&self) -> Option<&String> {
-        self.parents().and_then(|v| v.get(0))
+        self.parents().and_then(|v| v.first())
     }
     /// If the record has a Parent attribute, return all its values
     pub fn parents(&self) -> Option<&Vec<String>> {
@@ -123,7 +147,7 @@ impl GffRecord {
     }
     /// If the record has a Target attribute, return its first value
     pub fn target(&self) -> Option<&String> {
-        self.targets().and_then(|v| v.get(0))
+        self.targets().and_then(|v| v.first())
     }
     /// If the record has a Target attribute, return all its values
     pub fn targets(&self) -> Option<&Vec<String>> {
@@ -204,7 +228,7 @@ impl<T: Read> Iterator for GffReader<T> {
                         }
                         Ok((
                             Key::from(s[0]),
-                            s[1].to_string().split(',').map(|x| x.to_string()).collect(),
+                            s[1].split(',').map(|x| x.to_string()).collect(),
                         ))
                     })
                     .collect::<Result<Attributes, GffError>>()?,
@@ -218,3 +242,105 @@ impl<T: Read> Iterator for GffReader<T> {
             .map(|l| make_record(&l))
     }
 }
+
+/// Writes records as GFF3 lines; records read from other formats become
+/// `gene` features, identified by their ID if they have one
+pub struct GffWriter<W> {
+    out: W,
+    header: bool,
+}
+impl<W: Write> GffWriter<W> {
+    pub fn new(out: W) -> GffWriter<W> {
+        GffWriter { out, header: false }
+    }
+}
+impl<W: Write> RecordSink for GffWriter<W> {
+    fn write_record(&mut self, record: &Record) -> anyhow::Result<()> {
+        if !self.header {
+            writeln!(self.out, "##gff-version 3")?;
+            self.header = true;
+        }
+        let (start, end) = record.interval().bounds(Coordinates::OneBased);
+        let or_dot = |x: Option<String>| x.unwrap_or_else(|| ".".to_owned());
+        let (source, class, score, strand, phase, attributes) = match record {
+            Record::Gff(r) => {
+                // Values are kept as read, hence already escaped
+                let mut attributes = r
+                    .attributes
+                    .iter()
+                    .map(|(k, v)| (k != &Key::ID, k.to_string(), v.join(",")))
+                    .collect::<Vec<_>>();
+                attributes.sort();
+                (
+                    r.source.clone(),
+                    r.class.clone(),
+                    r.score.map(|s| s.to_string()),
+                    r.strand,
+                    r.phase,
+                    attributes
+                        .into_iter()
+                        .map(|(_, k, v)| format!("{k}={v}"))
+                        .collect::<Vec<_>>(),
+                )
+            }
+            _ => (
+                None,
+                Some("gene".to_owned()),
+                None,
+                Some(record.strand()),
+                None,
+                // `.` stands for the lack of name in BED files; other IDs are
+                // not escaped yet, unlike GFF3 ones
+                record
+                    .id()
+                    .filter(|id| *id != ".")
+                    .map(|id| vec![format!("ID={}", escape(id))])
+                    .unwrap_or_default(),
+            ),
+        };
+        writeln!(
+            self.out,
+            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+            record.chr(),
+            or_dot(source),
+            or_dot(class),
+            start,
+            end,
+            or_dot(score),
+            strand.map(char::from).unwrap_or('.'),
+            or_dot(phase.map(|p| p.to_string())),
+            if attributes.is_empty() {
+                ".".to_owned()
+            } else {
+                attributes.join(";")
+            }
+        )?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bed::BedReader;
+
+    #[test]
+    fn escaped_ids() {
+        let mut out = Vec::new();
+        let mut writer = GffWriter::new(&mut out);
+        let bed = "chr1\t0\t100\tg%1;a=b\t0\t+\nchr1\t200\t300\t.\t0\t-\n";
+        for record in BedReader::new(bed.as_bytes()) {
+            writer.write_record(&Record::Bed(record.unwrap())).unwrap();
+        }
+        let gff = String::from_utf8(out).unwrap();
+        let lines = gff.lines().collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [
+                "##gff-version 3",
+                "chr1\t.\tgene\t1\t100\t.\t+\t.\tID=g%251%3Ba%3Db",
+                "chr1\t.\tgene\t201\t300\t.\t-\t.\t.",
+            ]
+        );
+    }
+}
@@ -0,0 +1,202 @@
+//! Opening of annotation files, handling transparent decompression and
+//! detection of their format.
+use anyhow::*;
+use colored::Colorize;
+use flate2::bufread::MultiGzDecoder;
+use log::*;
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Cursor, Read},
+};
+
+use crate::{bed, chrom, errors::FileError, errors::ParseError, gff, Record};
+#[cfg(feature = "sqlite")]
+use crate::{table, table::TableLayout};
+
+/// The filename standing for the standard input
+pub(crate) const STDIN: &str = "-";
+
+/// The number of leading non-comment lines inspected to guess the format of a file
+const SNIFFED_LINES: usize = 5;
+
+/// The annotation formats that can be read and written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    /// GFF3, 1-based
+    Gff,
+    /// BED, 0-based
+    Bed,
+    /// ChromTable, 1-based
+    Chrom,
+}
+impl Format {
+    pub(crate) fn from_extension(filename: &str) -> Option<Format> {
+        let filename = filename.strip_suffix(".gz").unwrap_or(filename);
+        if filename.ends_with("gff") || filename.ends_with("gff3") {
+            Some(Format::Gff)
+        } else if filename.ends_with("bed") {
+            Some(Format::Bed)
+        } else if filename.ends_with("chrom") || filename.ends_with("tsv") {
+            Some(Format::Chrom)
+        } else {
+            None
+        }
+    }
+
+    /// Guess the format from the first lines of a file
+    pub(crate) fn sniff(lines: &[String]) -> Option<Format> {
+        if lines.iter().any(|l| l.starts_with("##gff-version")) {
+            return Some(Format::Gff);
+        }
+
+        let is_number = |x: &str| x.parse::<usize>().is_ok();
+        let is_strand = |x: &str| matches!(x, "+" | "-" | "." | "1" | "-1" | "+1");
+        let guesses = lines
+            .iter()
+            .filter(|l| !l.starts_with('#') && !l.trim().is_empty())
+            .map(|l| {
+                let tabbed = l.split('\t').collect::<Vec<_>>();
+                if tabbed.len() >= 9 && is_number(tabbed[3]) && is_number(tabbed[4]) {
+                    return Some(Format::Gff);
+                }
+                // A BED5+ line may have a `.` name where a ChromTable has its strand
+                if tabbed.len() == 5
+                    && is_number(tabbed[1])
+                    && is_number(tabbed[2])
+                    && is_strand(tabbed[3])
+                    && !(tabbed[3] == "." && is_number(tabbed[4]))
+                {
+                    return Some(Format::Chrom);
+                }
+                let s = l.split_whitespace().collect::<Vec<_>>();
+                if s.len() >= 3 && is_number(s[1]) && is_number(s[2]) {
+                    return Some(Format::Bed);
+                }
+                None
+            })
+            .collect::<Vec<_>>();
+
+        let first = *guesses.first()?;
+        guesses
+            .iter()
+            .all(|g| *g == first)
+            .then_some(first)
+            .flatten()
+    }
+}
+
+/// Open a file, or the standard input if `filename` is `-`, transparently
+/// decompressing it if it is gzipped
+pub(crate) fn open(filename: &str) -> Result<Box<dyn BufRead>> {
+    let mut f: Box<dyn BufRead> = if filename == STDIN {
+        Box::new(std::io::stdin().lock())
+    } else {
+        let f = File::open(filename).map_err(|e| FileError::CannotOpen {
+            source: e,
+            filename: filename.to_owned(),
+        })?;
+        Box::new(BufReader::new(f))
+    };
+    // Check for the gzip magic number without consuming the stream
+    let is_gz = f.fill_buf()?.starts_with(&[0x1f, 0x8b]);
+
+    Ok(if is_gz {
+        Box::new(BufReader::new(MultiGzDecoder::new(f)))
+    } else {
+        f
+    })
+}
+
+/// Read the first lines of `reader` to guess its format, and return a reader
+/// replaying them followed by the rest of the stream.
+fn sniff<R: BufRead + 'static>(mut reader: R) -> Result<(Option<Format>, Box<dyn Read>)> {
+    let mut head = Vec::new();
+    let mut lines = Vec::new();
+    while lines
+        .iter()
+        .filter(|l: &&String| !l.starts_with('#'))
+        .count()
+        < SNIFFED_LINES
+    {
+        let mut line = String::new();
+        if reader.read_line(&mut line)? == 0 {
+            break;
+        }
+        head.extend_from_slice(line.as_bytes());
+        lines.push(line.trim_end_matches(['\r', '\n']).to_owned());
+    }
+
+    Ok((
+        Format::sniff(&lines),
+        Box::new(Cursor::new(head).chain(reader)),
+    ))
+}
+
+pub(crate) type Records = Box<dyn Iterator<Item = Result<Record, ParseError>>>;
+
+/// Open an annotation file, or the standard input if `filename` is `-`, and
+/// return an iterator over its records; the format is guessed from the
+/// content of the file, the extension being only used to settle ambiguous
+/// cases.
+pub(crate) fn records(filename: &str) -> Result<(Format, Records)> {
+    let (sniffed, reader) = sniff(open(filename)?)?;
+    let hinted = Format::from_extension(filename);
+    let format = match (sniffed, hinted) {
+        (Some(sniffed), Some(hinted)) => {
+            if sniffed != hinted {
+                warn!(
+                    "{} looks like {:?} despite its extension",
+                    filename.yellow().bold(),
+                    sniffed
+                );
+            }
+            sniffed
+        }
+        (Some(format), None) | (None, Some(format)) => format,
+        (None, None) => bail!(
+            "unable to process {}: unknown filetype",
+            filename.yellow().bold()
+        ),
+    };
+    debug!("Reading {} as {:?}", filename, format);
+    Ok((format, parse(reader, format)))
+}
+
+/// Open an annotation file known to be in `format` and return an iterator
+/// over its records
+pub(crate) fn records_as(filename: &str, format: Format) -> Result<Records> {
+    debug!("Reading {} as {:?}", filename, format);
+    Ok(parse(open(filename)?, format))
+}
+
+fn parse<R: Read + 'static>(reader: R, format: Format) -> Records {
+    match format {
+        Format::Gff => Box::new(
+            gff::GffReader::new(reader).map(|r| r.map(|r| r.into()).map_err(ParseError::GffError)),
+        ),
+        Format::Bed => Box::new(
+            bed::BedReader::new(reader).map(|r| r.map(|r| r.into()).map_err(ParseError::BedError)),
+        ),
+        Format::Chrom => Box::new(
+            chrom::ChromReader::new(reader)
+                .map(|r| r.map(|r| r.into()).map_err(ParseError::ChromError)),
+        ),
+    }
+}
+
+/// Open an annotation file as `dbmaker` does, i.e. transparently
+/// decompressing it and guessing its format, and return an iterator over its
+/// records
+pub fn open_annotations(filename: &str) -> Result<impl Iterator<Item = Result<Record>>> {
+    Ok(records(filename)?.1.map(|r| r.map_err(Error::from)))
+}
+
+/// Open a delimited gene table with the given layout and return an iterator
+/// over its records
+#[cfg(feature = "sqlite")]
+pub(crate) fn table_records(filename: &str, layout: &TableLayout) -> Result<Records> {
+    Ok(Box::new(
+        table::TableReader::new(open(filename)?, layout.clone())
+            .map(|r| r.map(|r| r.into()).map_err(ParseError::TableError)),
+    ))
+}
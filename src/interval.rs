@@ -0,0 +1,380 @@
+//! Stretches of chromosomes, stored 0-based and half-open whatever the
+//! convention they were read in, so that they can be compared safely.
+use anyhow::Result;
+use std::cmp::Ordering;
+use std::collections::HashMap;
+
+use crate::{
+    genebook::{Gene, GeneBook},
+    Coordinates, Record, Strand,
+};
+
+/// The bases `start..end` of `chr`, 0-based and half-open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Interval {
+    pub chr: String,
+    pub start: usize,
+    pub end: usize,
+    pub strand: Strand,
+}
+impl Interval {
+    /// The interval from `start` to `end`, expressed in `coordinates`
+    pub fn new(
+        chr: &str,
+        start: usize,
+        end: usize,
+        strand: Strand,
+        coordinates: Coordinates,
+    ) -> Self {
+        let (start, end) = coordinates.convert(start, end, Coordinates::ZeroBased);
+        Interval {
+            chr: chr.to_owned(),
+            start,
+            end,
+            strand,
+        }
+    }
+
+    /// The start and end of the interval, expressed in `coordinates`
+    pub fn bounds(&self, coordinates: Coordinates) -> (usize, usize) {
+        Coordinates::ZeroBased.convert(self.start, self.end, coordinates)
+    }
+
+    /// The number of bases in the interval
+    pub fn len(&self) -> usize {
+        self.end.saturating_sub(self.start)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Whether both intervals share at least a base, whatever their strand
+    pub fn overlaps(&self, other: &Interval) -> bool {
+        self.chr == other.chr && self.start < other.end && other.start < self.end
+    }
+
+    /// Whether all the bases of `other` are in this interval, whatever
+    /// their strand
+    pub fn contains(&self, other: &Interval) -> bool {
+        self.chr == other.chr && self.start <= other.start && other.end <= self.end
+    }
+
+    /// The number of bases between both intervals, 0 if they overlap or are
+    /// adjacent, or `None` if they do not lie on the same chromosome
+    pub fn distance_to(&self, other: &Interval) -> Option<usize> {
+        if self.chr != other.chr {
+            return None;
+        }
+        Some(if self.start <= other.start {
+            other.start.saturating_sub(self.end)
+        } else {
+            self.start.saturating_sub(other.end)
+        })
+    }
+
+    /// The interval spanning both intervals if they overlap or are adjacent,
+    /// on their strand if they share it
+    pub fn merge(&self, other: &Interval) -> Option<Interval> {
+        (self.distance_to(other)? == 0).then(|| Interval {
+            chr: self.chr.clone(),
+            start: self.start.min(other.start),
+            end: self.end.max(other.end),
+            strand: if self.strand == other.strand {
+                self.strand
+            } else {
+                Strand::Unknown
+            },
+        })
+    }
+}
+
+/// The intervals of a chromosome sorted by start, laid out as an implicit
+/// binary tree, each node knowing the furthest end in its subtree
+struct Chromosome<T> {
+    entries: Vec<(Interval, T)>,
+    /// The furthest end of the subtree rooted at each entry
+    max_ends: Vec<usize>,
+    /// The level of the root
+    depth: usize,
+}
+impl<T> Chromosome<T> {
+    fn new(mut entries: Vec<(Interval, T)>) -> Self {
+        entries.sort_by_key(|(i, _)| (i.start, i.end));
+        let n = entries.len();
+        let mut max_ends = entries.iter().map(|(i, _)| i.end).collect::<Vec<_>>();
+        if n == 0 {
+            return Chromosome {
+                entries,
+                max_ends,
+                depth: 0,
+            };
+        }
+
+        // Leaves lie at even ranks; the last one stands for the missing
+        // right children of the last nodes of each level
+        let mut last_i = (n - 1) & !1;
+        let mut last = max_ends[last_i];
+        let mut k = 1;
+        while 1 << k <= n {
+            let x = 1 << (k - 1);
+            for i in ((x << 1) - 1..n).step_by(x << 2) {
+                let left = max_ends[i - x];
+                let right = if i + x < n { max_ends[i + x] } else { last };
+                max_ends[i] = max_ends[i].max(left).max(right);
+            }
+            last_i = if (last_i >> k) & 1 != 0 {
+                last_i - x
+            } else {
+                last_i + x
+            };
+            if last_i < n {
+                last = last.max(max_ends[last_i]);
+            }
+            k += 1;
+        }
+        Chromosome {
+            entries,
+            max_ends,
+            depth: k - 1,
+        }
+    }
+
+    fn overlapping(&self, start: usize, end: usize) -> Vec<&(Interval, T)> {
+        let n = self.entries.len();
+        let mut r = Vec::new();
+        if n == 0 {
+            return r;
+        }
+        let overlaps = |i: usize| self.entries[i].0.end > start;
+        // (node, level, whether its left subtree was processed)
+        let mut stack = vec![((1 << self.depth) - 1, self.depth, false)];
+        while let Some((x, k, visited)) = stack.pop() {
+            if k <= 3 {
+                // Scan small subtrees linearly
+                let i0 = x >> k << k;
+                let i1 = (i0 + (1 << (k + 1)) - 1).min(n);
+                for i in (i0..i1).take_while(|i| self.entries[*i].0.start < end) {
+                    if overlaps(i) {
+                        r.push(&self.entries[i]);
+                    }
+                }
+            } else if !visited {
+                let left = x - (1 << (k - 1));
+                stack.push((x, k, true));
+                if left >= n || self.max_ends[left] > start {
+                    stack.push((left, k - 1, false));
+                }
+            } else if x < n && self.entries[x].0.start < end {
+                if overlaps(x) {
+                    r.push(&self.entries[x]);
+                }
+                stack.push((x + (1 << (k - 1)), k - 1, false));
+            }
+        }
+        r
+    }
+}
+
+/// An index of intervals and their payloads, e.g. genes, answering which of
+/// them overlap a region in logarithmic time
+pub struct IntervalTree<T> {
+    chrs: HashMap<String, Chromosome<T>>,
+}
+impl<T> IntervalTree<T> {
+    pub fn new(entries: impl IntoIterator<Item = (Interval, T)>) -> Self {
+        let mut chrs = HashMap::<String, Vec<(Interval, T)>>::new();
+        for (interval, value) in entries {
+            chrs.entry(interval.chr.clone())
+                .or_default()
+                .push((interval, value));
+        }
+        IntervalTree {
+            chrs: chrs
+                .into_iter()
+                .map(|(chr, entries)| (chr, Chromosome::new(entries)))
+                .collect(),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.chrs.values().map(|c| c.entries.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// The entries overlapping `interval`, ordered by start, whatever their
+    /// strand
+    pub fn overlapping(&self, interval: &Interval) -> Vec<&(Interval, T)> {
+        let mut r = self
+            .chrs
+            .get(&interval.chr)
+            .map(|c| c.overlapping(interval.start, interval.end))
+            .unwrap_or_default();
+        r.sort_by_key(|(i, _)| (i.start, i.end));
+        r
+    }
+}
+impl IntervalTree<Record> {
+    /// Index the records of e.g. [`crate::open_annotations`]
+    pub fn from_records(records: impl IntoIterator<Item = Result<Record>>) -> Result<Self> {
+        Ok(IntervalTree::new(
+            records
+                .into_iter()
+                .map(|r| r.map(|r| (r.interval(), r)))
+                .collect::<Result<Vec<_>>>()?,
+        ))
+    }
+}
+impl IntervalTree<Gene> {
+    /// Index the genes of `species` in `book`
+    pub fn from_species(book: &GeneBook, species: &str) -> Result<Self> {
+        let coordinates = book.coordinates();
+        Ok(IntervalTree::new(
+            book.genes_of_species(species)?
+                .into_iter()
+                .map(|g| (g.interval(coordinates), g)),
+        ))
+    }
+}
+
+/// `digits` without their leading zeros
+fn significant(digits: &[u8]) -> &[u8] {
+    let zeros = digits.iter().take_while(|c| **c == b'0').count();
+    &digits[zeros..]
+}
+
+/// Compare chromosome names in natural order, i.e. runs of digits by their
+/// value, so that `chr2` comes before `chr10`
+pub fn natural_cmp(a: &str, b: &str) -> Ordering {
+    let (mut x, mut y) = (a.as_bytes(), b.as_bytes());
+    loop {
+        match (x.first(), y.first()) {
+            (None, None) => break,
+            (None, Some(_)) => return Ordering::Less,
+            (Some(_), None) => return Ordering::Greater,
+            (Some(c), Some(d)) if c.is_ascii_digit() && d.is_ascii_digit() => {
+                let n = x.iter().take_while(|c| c.is_ascii_digit()).count();
+                let m = y.iter().take_while(|c| c.is_ascii_digit()).count();
+                // Compare numbers by value, without overflowing on long runs
+                let (u, v) = (significant(&x[..n]), significant(&y[..m]));
+                let r = u.len().cmp(&v.len()).then_with(|| u.cmp(v));
+                if r != Ordering::Equal {
+                    return r;
+                }
+                (x, y) = (&x[n..], &y[m..]);
+            }
+            (Some(c), Some(d)) => {
+                if c != d {
+                    return c.cmp(d);
+                }
+                (x, y) = (&x[1..], &y[1..]);
+            }
+        }
+    }
+    // e.g. `chr01` and `chr1`
+    a.cmp(b)
+}
+
+/// A base of a chromosome, ordered by chromosome in natural order, then by
+/// position
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct GenomicPosition {
+    pub chr: String,
+    pub pos: usize,
+}
+impl GenomicPosition {
+    pub fn new(chr: &str, pos: usize) -> Self {
+        GenomicPosition {
+            chr: chr.to_owned(),
+            pos,
+        }
+    }
+
+    /// The number of bases from this position to `other`, or `None` if they
+    /// do not lie on the same chromosome
+    pub fn distance(&self, other: &GenomicPosition) -> Option<usize> {
+        (self.chr == other.chr).then(|| self.pos.abs_diff(other.pos))
+    }
+}
+impl Ord for GenomicPosition {
+    fn cmp(&self, other: &Self) -> Ordering {
+        natural_cmp(&self.chr, &other.chr).then(self.pos.cmp(&other.pos))
+    }
+}
+impl PartialOrd for GenomicPosition {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn interval(chr: &str, start: usize, end: usize) -> Interval {
+        Interval::new(chr, start, end, Strand::Unknown, Coordinates::ZeroBased)
+    }
+
+    #[test]
+    fn overlaps() {
+        let tree = IntervalTree::new([
+            (interval("chr1", 10, 20), 'a'),
+            (interval("chr1", 0, 5), 'b'),
+            (interval("chr1", 15, 100), 'c'),
+            (interval("chr2", 10, 20), 'd'),
+        ]);
+        assert_eq!(tree.len(), 4);
+        let found = |chr, start, end| {
+            tree.overlapping(&interval(chr, start, end))
+                .into_iter()
+                .map(|(_, x)| *x)
+                .collect::<String>()
+        };
+        assert_eq!(found("chr1", 0, 200), "bac");
+        assert_eq!(found("chr1", 18, 19), "ac");
+        // Intervals are half-open
+        assert_eq!(found("chr1", 5, 10), "");
+        assert_eq!(found("chr1", 20, 21), "c");
+        assert_eq!(found("chr2", 0, 11), "d");
+        assert_eq!(found("chr3", 0, 100), "");
+    }
+
+    #[test]
+    fn overlaps_as_a_scan() {
+        // A small linear congruential generator, to stay deterministic
+        let mut seed = 42u64;
+        let mut random = |n: u64| {
+            seed = seed.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((seed >> 33) % n) as usize
+        };
+        for n in [1, 2, 3, 7, 8, 9, 16, 17, 31, 33, 100, 257] {
+            let entries = (0..n)
+                .map(|k| {
+                    let start = random(1000);
+                    (interval("chr1", start, start + 1 + random(150)), k)
+                })
+                .collect::<Vec<_>>();
+            let tree = IntervalTree::new(entries.clone());
+            for _ in 0..50 {
+                let start = random(1100);
+                let query = interval("chr1", start, start + 1 + random(100));
+                let mut expected = entries
+                    .iter()
+                    .filter(|(i, _)| i.overlaps(&query))
+                    .map(|(_, k)| *k)
+                    .collect::<Vec<_>>();
+                let mut found = tree
+                    .overlapping(&query)
+                    .into_iter()
+                    .map(|(_, k)| *k)
+                    .collect::<Vec<_>>();
+                expected.sort();
+                found.sort();
+                assert_eq!(found, expected, "{} intervals, {:?}", n, query);
+            }
+        }
+    }
+}
@@ -2,14 +2,31 @@ use std::write;
 
 use errors::ParseError;
 
+pub mod agp;
+pub mod aliases;
+pub mod analysis;
 mod bed;
+pub mod chain;
 mod chrom;
+#[cfg(feature = "sqlite")]
 pub mod dbmaker;
 mod errors;
+#[cfg(feature = "sqlite")]
+pub mod export;
 pub mod genebook;
 mod gff;
+mod input;
+pub mod interval;
+pub mod maf;
+mod newick;
+pub mod output;
+pub mod synteny;
+pub mod table;
 
-#[derive(Debug, Copy, Clone)]
+pub use input::{open_annotations, Format};
+pub use interval::{GenomicPosition, Interval, IntervalTree};
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
 pub enum Phase {
     Sync,
     OneShifted,
@@ -48,25 +65,112 @@ impl From<Phase> for usize {
         }
     }
 }
+impl std::fmt::Display for Phase {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", usize::from(*self))
+    }
+}
+impl Phase {
+    /// The phase of a CDS segment starting `bp` bases after one of this
+    /// phase, e.g. of the next exon of a spliced CDS if `bp` is the length
+    /// of this one
+    pub fn advance(self, bp: usize) -> Phase {
+        Phase::try_from((usize::from(self) + 3 - bp % 3) % 3).unwrap()
+    }
+
+    /// The phases of the successive segments of a spliced CDS, from their
+    /// lengths and the phase of the first one
+    pub fn spliced(first: Phase, lengths: impl IntoIterator<Item = usize>) -> Vec<Phase> {
+        lengths
+            .into_iter()
+            .scan(first, |phase, length| {
+                let current = *phase;
+                *phase = current.advance(length);
+                Some(current)
+            })
+            .collect()
+    }
+}
 
-#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+/// The coordinate conventions used by the annotation formats
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binary", derive(serde::Serialize, serde::Deserialize))]
+pub enum Coordinates {
+    /// 1-based, closed intervals, as used by GFF3
+    #[default]
+    OneBased,
+    /// 0-based, half-open intervals, as used by BED
+    ZeroBased,
+}
+impl Coordinates {
+    /// Convert the interval `[start, end]` from this convention to `to`
+    pub fn convert(self, start: usize, end: usize, to: Coordinates) -> (usize, usize) {
+        match (self, to) {
+            (Coordinates::OneBased, Coordinates::ZeroBased) => (start.saturating_sub(1), end),
+            (Coordinates::ZeroBased, Coordinates::OneBased) => (start + 1, end),
+            _ => (start, end),
+        }
+    }
+}
+impl std::fmt::Display for Coordinates {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Coordinates::OneBased => write!(f, "1-based"),
+            Coordinates::ZeroBased => write!(f, "0-based"),
+        }
+    }
+}
+impl TryFrom<&str> for Coordinates {
+    type Error = ParseError;
+
+    fn try_from(s: &str) -> Result<Self, Self::Error> {
+        match s {
+            "1-based" => Ok(Coordinates::OneBased),
+            "0-based" => Ok(Coordinates::ZeroBased),
+            _ => Err(ParseError::InvalidCoordinates(s.to_string())),
+        }
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "binary", derive(serde::Serialize, serde::Deserialize))]
 pub enum Strand {
     Direct,
     Reverse,
+    #[default]
     Unknown,
 }
 impl Strand {
     pub fn reverse(&mut self) {
+        *self = self.opposite();
+    }
+
+    /// The other strand; unknown strands stay unknown
+    pub fn opposite(self) -> Strand {
         match self {
-            Strand::Direct => *self = Strand::Reverse,
-            Strand::Reverse => *self = Strand::Direct,
-            Strand::Unknown => {}
+            Strand::Direct => Strand::Reverse,
+            Strand::Reverse => Strand::Direct,
+            Strand::Unknown => Strand::Unknown,
         }
     }
-}
-impl std::default::Default for Strand {
-    fn default() -> Strand {
-        Strand::Unknown
+
+    pub fn is_forward(self) -> bool {
+        self == Strand::Direct
+    }
+
+    pub fn is_reverse(self) -> bool {
+        self == Strand::Reverse
+    }
+
+    /// The absolute strand of a feature lying on `other` relatively to a
+    /// parent on this strand, e.g. the reverse strand for a direct feature
+    /// nested in a reverse parent
+    pub fn combine(self, other: Strand) -> Strand {
+        match self {
+            Strand::Direct => other,
+            Strand::Reverse => other.opposite(),
+            Strand::Unknown => Strand::Unknown,
+        }
     }
 }
 impl std::fmt::Display for Strand {
@@ -112,58 +216,95 @@ impl From<Strand> for String {
         match s {
             Strand::Direct => "+".into(),
             Strand::Reverse => "-".into(),
-            Strand::Unknown => "-".into(),
+            Strand::Unknown => ".".into(),
         }
     }
 }
 
-enum Record {
+/// A record of an annotation file, whatever its format; see
+/// [`open_annotations`]
+pub enum Record {
     Gff(gff::GffRecord),
     Bed(bed::BedRecord),
     Chrom(chrom::ChromRecord),
+    Table(table::TableRecord),
 }
 
 impl Record {
-    fn id(&self) -> Option<&str> {
+    pub fn id(&self) -> Option<&str> {
         match self {
             Record::Bed(r) => r.id(),
             Record::Gff(r) => r.id(),
             Record::Chrom(r) => Some(r.id()),
+            Record::Table(r) => Some(r.id()),
         }
     }
-    fn chr(&self) -> &str {
+    pub fn chr(&self) -> &str {
         match self {
             Record::Gff(r) => r.chr(),
             Record::Bed(r) => r.chr(),
             Record::Chrom(r) => r.chr(),
+            Record::Table(r) => r.chr(),
         }
     }
-    fn start(&self) -> usize {
+    pub fn start(&self) -> usize {
         match self {
             Record::Gff(r) => r.start(),
             Record::Bed(r) => r.start(),
             Record::Chrom(r) => r.start(),
+            Record::Table(r) => r.start(),
         }
     }
-    fn end(&self) -> usize {
+    pub fn end(&self) -> usize {
         match self {
             Record::Gff(r) => r.end(),
             Record::Bed(r) => r.end(),
             Record::Chrom(r) => r.end(),
+            Record::Table(r) => r.end(),
         }
     }
-    fn strand(&self) -> Strand {
+    pub fn strand(&self) -> Strand {
         match self {
-            Record::Gff(r) => r.strand().unwrap_or(Strand::Direct),
+            Record::Gff(r) => r.strand().unwrap_or(Strand::Unknown),
             Record::Bed(r) => r.strand(),
             Record::Chrom(r) => r.strand(),
+            Record::Table(r) => r.strand(),
+        }
+    }
+    /// The coordinate convention used by the underlying format
+    pub fn coordinates(&self) -> Coordinates {
+        match self {
+            Record::Gff(_) => Coordinates::OneBased,
+            Record::Bed(_) => Coordinates::ZeroBased,
+            Record::Chrom(_) => Coordinates::OneBased,
+            Record::Table(r) => r.coordinates(),
         }
     }
-    fn is_class(&self, class: &str) -> bool {
+    /// The stretch of chromosome covered by the record
+    pub fn interval(&self) -> Interval {
+        Interval::new(
+            self.chr(),
+            self.start(),
+            self.end(),
+            self.strand(),
+            self.coordinates(),
+        )
+    }
+    /// The value of the attribute `key`; only GFF records carry attributes
+    pub fn attribute(&self, key: &str) -> Option<String> {
+        match self {
+            Record::Gff(r) => r.attribute(key),
+            Record::Bed(_) | Record::Chrom(_) | Record::Table(_) => None,
+        }
+    }
+    /// Whether the record is of type `class`, e.g. `gene`; only GFF records
+    /// are typed, others are of all types
+    pub fn is_class(&self, class: &str) -> bool {
         match self {
             Record::Gff(r) => r.class().map(|c| c == class).unwrap_or(false),
             Record::Bed(_) => true,
             Record::Chrom(_) => true,
+            Record::Table(_) => true,
         }
     }
 }
@@ -183,3 +324,8 @@ impl From<chrom::ChromRecord> for Record {
         Record::Chrom(r)
     }
 }
+impl From<table::TableRecord> for Record {
+    fn from(r: table::TableRecord) -> Self {
+        Record::Table(r)
+    }
+}
@@ -0,0 +1,294 @@
+//! A parser for the MAF multiple alignment format, as produced by e.g.
+//! Multiz or Cactus (`hal2maf`):
+//! ```text
+//! ##maf version=1
+//! a score=23262.0
+//! s hg18.chr7    27578828 38 + 158545518 AAA-GGGAATGTTAACCAAATGA
+//! s panTro1.chr6 28741140 38 + 161576975 AAA-GGGAATGTTAACCAAATGA
+//! ```
+//! Only `a` and `s` lines are interpreted; `i`, `e` and `q` lines are skipped.
+
+use std::io::{BufRead, BufReader, Lines, Read};
+
+use thiserror::Error;
+
+use crate::Strand;
+
+#[derive(Debug, Error)]
+pub enum MafError {
+    #[error("MAF sequence line with missing fields: {0}")]
+    RecordTooShort(String),
+
+    #[error("invalid numeric field in MAF line: {0}")]
+    InvalidNumber(String),
+
+    #[error("unrecognized strand format: {0}")]
+    UnknownStrand(String),
+
+    #[error("aligned region past the end of its source sequence: {0}")]
+    OutOfBounds(String),
+
+    #[error("sequence line outside of an alignment block: {0}")]
+    OrphanSequence(String),
+}
+
+/// A single aligned sequence (`s` line) within an alignment block.
+#[derive(Debug, Clone)]
+pub struct MafComponent {
+    src: String,
+    start: usize,
+    size: usize,
+    strand: Strand,
+    src_size: usize,
+    text: String,
+}
+impl MafComponent {
+    /// The full source name, typically `species.chr`
+    pub fn src(&self) -> &str {
+        &self.src
+    }
+
+    /// The species part of the source name, i.e. everything before the first `.`
+    pub fn species(&self) -> &str {
+        self.src
+            .split_once('.')
+            .map(|(s, _)| s)
+            .unwrap_or(&self.src)
+    }
+
+    /// The chromosome part of the source name, i.e. everything after the first `.`
+    pub fn chr(&self) -> &str {
+        self.src
+            .split_once('.')
+            .map(|(_, c)| c)
+            .unwrap_or(&self.src)
+    }
+
+    /// The 0-based start of the aligned region, relative to `strand`
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    /// The number of non-gap characters in the aligned region
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    /// The full length of the source sequence
+    pub fn src_size(&self) -> usize {
+        self.src_size
+    }
+
+    /// The aligned sequence, gaps included
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// The 0-based, half-open coordinates of the aligned region on the
+    /// forward strand of the source sequence
+    pub fn forward_span(&self) -> (usize, usize) {
+        match self.strand {
+            Strand::Reverse => (
+                self.src_size - self.start - self.size,
+                self.src_size - self.start,
+            ),
+            _ => (self.start, self.start + self.size),
+        }
+    }
+}
+
+/// An alignment block (`a` paragraph).
+#[derive(Debug, Clone, Default)]
+pub struct MafBlock {
+    score: Option<f64>,
+    components: Vec<MafComponent>,
+}
+impl MafBlock {
+    pub fn score(&self) -> Option<f64> {
+        self.score
+    }
+
+    pub fn components(&self) -> &[MafComponent] {
+        &self.components
+    }
+
+    /// Return the first component belonging to the given species
+    pub fn component(&self, species: &str) -> Option<&MafComponent> {
+        self.components.iter().find(|c| c.species() == species)
+    }
+
+    /// Iterate over the distinct species present in this block
+    pub fn species(&self) -> impl Iterator<Item = &str> {
+        let mut seen = Vec::new();
+        self.components.iter().filter_map(move |c| {
+            if seen.contains(&c.species()) {
+                None
+            } else {
+                seen.push(c.species());
+                Some(c.species())
+            }
+        })
+    }
+}
+
+pub struct MafReader<T> {
+    buffer_lines: Lines<BufReader<T>>,
+    // The header of the next block, when blocks are not separated by blank lines
+    pending: Option<MafBlock>,
+}
+impl<T: Read> MafReader<T> {
+    pub fn new(file: T) -> MafReader<T> {
+        MafReader {
+            buffer_lines: BufReader::new(file).lines(),
+            pending: None,
+        }
+    }
+}
+impl<T: Read> Iterator for MafReader<T> {
+    type Item = Result<MafBlock, MafError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        fn parse_header(line: &str) -> MafBlock {
+            MafBlock {
+                score: line
+                    .split_whitespace()
+                    .skip(1)
+                    .filter_map(|kv| kv.split_once('='))
+                    .find(|(k, _)| *k == "score")
+                    .and_then(|(_, v)| v.parse().ok()),
+                components: Vec::new(),
+            }
+        }
+
+        fn parse_component(line: &str) -> Result<MafComponent, MafError> {
+            let mut s = line.split_whitespace().skip(1);
+            let mut field = || {
+                s.next()
+                    .ok_or_else(|| MafError::RecordTooShort(line.to_owned()))
+            };
+            let number = |x: &str| {
+                x.parse::<usize>()
+                    .map_err(|_| MafError::InvalidNumber(line.to_owned()))
+            };
+
+            let component = MafComponent {
+                src: field()?.to_owned(),
+                start: number(field()?)?,
+                size: number(field()?)?,
+                strand: field()?
+                    .try_into()
+                    .map_err(|_| MafError::UnknownStrand(line.to_owned()))?,
+                src_size: number(field()?)?,
+                text: field()?.to_owned(),
+            };
+            if component
+                .start
+                .checked_add(component.size)
+                .is_none_or(|end| end > component.src_size)
+            {
+                return Err(MafError::OutOfBounds(line.to_owned()));
+            }
+            Ok(component)
+        }
+
+        let mut block = self.pending.take();
+        for line in self.buffer_lines.by_ref().map(|l| l.unwrap()) {
+            let line = line.trim_end();
+            if line.starts_with('#') {
+                continue;
+            }
+            if line.is_empty() {
+                if block.is_some() {
+                    break;
+                } else {
+                    continue;
+                }
+            }
+
+            match line.split_whitespace().next() {
+                Some("a") => {
+                    if block.is_some() {
+                        self.pending = Some(parse_header(line));
+                        break;
+                    }
+                    block = Some(parse_header(line));
+                }
+                Some("s") => match block.as_mut() {
+                    Some(block) => match parse_component(line) {
+                        Ok(c) => block.components.push(c),
+                        Err(e) => return Some(Err(e)),
+                    },
+                    None => return Some(Err(MafError::OrphanSequence(line.to_owned()))),
+                },
+                _ => {}
+            }
+        }
+
+        block.map(Ok)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MAF: &str = "##maf version=1\n\
+                       # a comment\n\
+                       a score=23262.0\n\
+                       s hg18.chr7    27578828 38 + 158545518 AAA-GGGAATGTTAACCAAATGA\n\
+                       i hg18.chr7    N 0 C 0\n\
+                       s panTro1.chr6 28741140 38 - 161576975 AAA-GGGAATGTTAACCAAATGA\n\
+                       \n\
+                       a\n\
+                       s hg18.chr7    27699739 6 + 158545518 TAAAGA\n\
+                       s hg18.chr7_random 10 6 + 100 TAAAGA\n\
+                       a score=-5\n\
+                       s mm4.chr6 53310102 3 + 151104725 ACA\n";
+
+    #[test]
+    fn blocks() {
+        let blocks = MafReader::new(MAF.as_bytes())
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        assert_eq!(blocks.len(), 3);
+        assert_eq!(blocks[0].score(), Some(23262.));
+        assert_eq!(blocks[1].score(), None);
+        assert_eq!(blocks[2].score(), Some(-5.));
+
+        let chimp = blocks[0].component("panTro1").unwrap();
+        assert_eq!((chimp.species(), chimp.chr()), ("panTro1", "chr6"));
+        assert_eq!(chimp.strand(), Strand::Reverse);
+        assert_eq!(
+            chimp.forward_span(),
+            (161576975 - 28741140 - 38, 161576975 - 28741140)
+        );
+        assert_eq!(
+            blocks[0].components()[0].forward_span(),
+            (27578828, 27578866)
+        );
+        assert_eq!(blocks[0].components()[0].text().len(), 23);
+
+        // Blocks may follow each other without blank lines
+        assert_eq!(blocks[1].species().collect::<Vec<_>>(), ["hg18"]);
+        assert_eq!(blocks[1].components().len(), 2);
+        assert_eq!(blocks[2].components()[0].src(), "mm4.chr6");
+    }
+
+    #[test]
+    fn invalid_blocks() {
+        for (line, error) in [
+            ("a\ns hg18.chr7 10 6 + 100", "missing fields"),
+            ("a\ns hg18.chr7 x 6 + 100 TAAAGA", "invalid numeric"),
+            ("a\ns hg18.chr7 10 6 * 100 TAAAGA", "strand"),
+            ("a\ns hg18.chr7 95 6 + 100 TAAAGA", "past the end"),
+            ("s hg18.chr7 10 6 + 100 TAAAGA", "outside of an alignment"),
+        ] {
+            let e = MafReader::new(line.as_bytes()).next().unwrap().unwrap_err();
+            assert!(e.to_string().contains(error), "{}", e);
+        }
+    }
+}
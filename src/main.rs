@@ -0,0 +1,478 @@
+//! The `syntesuite` command-line tool, building gene databases and querying
+//! them without writing any Rust.
+use anyhow::*;
+use clap::{Parser, Subcommand};
+use colored::Colorize;
+use serde_json::json;
+use std::ops::Range;
+
+use syntesuite::{
+    dbmaker::{self, BuildOptions, FamilyFormat, Layout},
+    genebook::{FamilyID, Gene, GeneBook, GeneQuery, TailGene},
+    Coordinates, Format,
+};
+
+#[derive(Parser)]
+#[command(version, about = "Build and query syntesuite gene databases")]
+struct Cli {
+    #[command(subcommand)]
+    command: Command,
+    /// Report more of the progress on the standard error; may be repeated
+    #[arg(short, long, action = clap::ArgAction::Count, global = true)]
+    verbose: u8,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Build a database from family and annotation files
+    Build {
+        /// The database to create
+        #[arg(short, long)]
+        out: String,
+        /// The family files or directories
+        #[arg(short, long, required = true, num_args = 1..)]
+        families: Vec<String>,
+        /// The annotation files or directories
+        #[arg(short, long, required = true, num_args = 1..)]
+        annotations: Vec<String>,
+        /// The regex capturing the species from the annotation filenames in
+        /// a `species` group
+        #[arg(long, default_value = "(?<species>[^.]+)")]
+        species_pattern: String,
+        /// The type of the annotation records to keep
+        #[arg(long, default_value = "gene")]
+        id_type: String,
+        /// The regex capturing the gene IDs in an `id` group
+        #[arg(long, default_value = "(?<id>.*)")]
+        id_pattern: String,
+        /// The number of genes in the landscapes on each side
+        #[arg(short, long, default_value_t = 15)]
+        window: isize,
+        /// The format of the family files: plain, orthofinder, mcl,
+        /// eggnog:LEVEL (e.g. `eggnog:2759` or `eggnog:Eukaryota`) or trees
+        #[arg(long, default_value = "plain")]
+        family_format: String,
+        /// With tree families, split the trees rooted in duplication nodes
+        /// into one family per duplicated subtree
+        #[arg(long)]
+        split_duplications: bool,
+        /// The coordinate convention of the database: 1-based or 0-based
+        #[arg(long, default_value = "1-based")]
+        coordinates: String,
+        /// How the genes are laid out in the database: flat or normalized
+        #[arg(long, default_value = "flat")]
+        layout: String,
+        /// Also store the ID and start of the genes of the landscapes
+        #[arg(long)]
+        rich_landscapes: bool,
+        /// Collapse tandem arrays into single landscape entries
+        #[arg(long)]
+        collapse_tandems: bool,
+        /// How many annotation files to parse in parallel, 0 for all cores
+        #[arg(short, long, default_value_t = 0)]
+        threads: usize,
+    },
+    /// Print genes and their landscapes
+    Get {
+        /// The database to query
+        db: String,
+        /// The IDs of the genes
+        #[arg(required = true)]
+        genes: Vec<String>,
+        /// The number of genes to show on each side, by default all those
+        /// stored
+        #[arg(short, long)]
+        window: Option<usize>,
+    },
+    /// Print the genes matching a set of filters
+    Query {
+        /// The database to query
+        db: String,
+        /// Only keep the genes of this species
+        #[arg(short, long)]
+        species: Option<String>,
+        /// Only keep the genes of this chromosome
+        #[arg(short, long)]
+        chr: Option<String>,
+        /// Only keep the genes overlapping this region, e.g. `1m-2.5m`;
+        /// `k`, `m` and `g` suffixes are understood
+        #[arg(short, long)]
+        range: Option<String>,
+        /// Only keep the genes of this family
+        #[arg(short, long)]
+        family: Option<FamilyID>,
+        /// The output format: tsv or json, i.e. JSON lines
+        #[arg(long, default_value = "tsv")]
+        format: String,
+    },
+    /// List the species of a database with their number of genes
+    Species {
+        /// The database to query
+        db: String,
+    },
+    /// Print per-species and per-family summary statistics of a database
+    Stats {
+        /// The database to query
+        db: String,
+        /// Print the statistics as a JSON object
+        #[arg(long)]
+        json: bool,
+    },
+    /// Convert an annotation file between GFF3, BED and ChromTable
+    Convert {
+        /// The annotation file to convert, or `-` to read the standard input
+        input: String,
+        output: String,
+        /// The format of the input, guessed if not set: gff, bed or chrom
+        #[arg(long)]
+        from: Option<String>,
+        /// The format of the output, guessed from its extension if not set:
+        /// gff, bed or chrom
+        #[arg(long)]
+        to: Option<String>,
+    },
+}
+
+fn format(name: &str) -> Result<Format> {
+    match name {
+        "gff" | "gff3" => Ok(Format::Gff),
+        "bed" => Ok(Format::Bed),
+        "chrom" => Ok(Format::Chrom),
+        _ => bail!("unknown annotation format: {}", name.yellow().bold()),
+    }
+}
+
+fn family_format(name: &str, split_duplications: bool) -> Result<FamilyFormat> {
+    match name.split_once(':') {
+        Some(("eggnog", level)) if !level.is_empty() => Ok(FamilyFormat::EggNog {
+            level: level.to_owned(),
+        }),
+        Some(_) => bail!("unknown family format: {}", name.yellow().bold()),
+        None => match name {
+            "plain" => Ok(FamilyFormat::Plain),
+            "orthofinder" => Ok(FamilyFormat::OrthoFinder),
+            "mcl" => Ok(FamilyFormat::Mcl),
+            "eggnog" => bail!(
+                "the eggNOG taxonomic level must be set, e.g. {}",
+                "eggnog:2759".yellow().bold()
+            ),
+            "trees" => Ok(FamilyFormat::Trees { split_duplications }),
+            _ => bail!("unknown family format: {}", name.yellow().bold()),
+        },
+    }
+}
+
+/// Print the log records to the standard error
+struct Logger {
+    level: log::LevelFilter,
+}
+impl log::Log for Logger {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= self.level
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let level = match record.level() {
+                log::Level::Error => "ERROR".red().bold(),
+                log::Level::Warn => "WARN".yellow().bold(),
+                log::Level::Info => "INFO".green(),
+                log::Level::Debug => "DEBUG".blue(),
+                log::Level::Trace => "TRACE".dimmed(),
+            };
+            eprintln!("{} {}", level, record.args());
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+/// Install the stderr logger, showing warnings and errors by default and
+/// more with each `verbose` level
+fn init_logger(verbose: u8) -> Result<()> {
+    let level = match verbose {
+        0 => log::LevelFilter::Warn,
+        1 => log::LevelFilter::Info,
+        2 => log::LevelFilter::Debug,
+        _ => log::LevelFilter::Trace,
+    };
+    log::set_logger(Box::leak(Box::new(Logger { level })))
+        .map_err(|e| anyhow!("failed to set up logging: {}", e))?;
+    log::set_max_level(level);
+    Ok(())
+}
+
+/// Parse a genomic length such as `1500`, `20k` or `1.5m`
+fn length(s: &str) -> Result<usize> {
+    let s = s.trim().to_lowercase();
+    let (number, unit) = match s.chars().last() {
+        Some('k') => (&s[..s.len() - 1], 1e3),
+        Some('m') => (&s[..s.len() - 1], 1e6),
+        Some('g') => (&s[..s.len() - 1], 1e9),
+        _ => (s.as_str(), 1.),
+    };
+    let number = number
+        .parse::<f64>()
+        .ok()
+        .filter(|x| *x >= 0.)
+        .with_context(|| format!("invalid length: {}", s.yellow().bold()))?;
+    Ok((number * unit).round() as usize)
+}
+
+/// Parse a region such as `1m-2m`
+fn range(s: &str) -> Result<Range<usize>> {
+    let (start, end) = s
+        .split_once('-')
+        .with_context(|| format!("expected START-END, found {}", s.yellow().bold()))?;
+    let range = length(start)?..length(end)?;
+    ensure!(
+        range.start < range.end,
+        "empty region: {}",
+        s.yellow().bold()
+    );
+    Ok(range)
+}
+
+/// The window the landscapes of `db` were built with
+fn stored_window(db: &str) -> Result<usize> {
+    Ok(dbmaker::metadata(db)?
+        .get("window")
+        .and_then(|w| w.parse().ok())
+        .unwrap_or_default())
+}
+
+/// The form of a gene in a printed landscape
+fn tail(t: &TailGene) -> String {
+    let mut r = format!("{}{}", t.strand, t.family);
+    if let Some(id) = t.id.as_ref() {
+        r = format!("{r}:{id}");
+    }
+    if t.copies > 1 {
+        r = format!("{r}×{}", t.copies);
+    }
+    r
+}
+
+fn main() -> Result<()> {
+    let cli = Cli::parse();
+    init_logger(cli.verbose)?;
+    match cli.command {
+        Command::Build {
+            out,
+            families,
+            annotations,
+            species_pattern,
+            id_type,
+            id_pattern,
+            window,
+            family_format: family_format_name,
+            split_duplications,
+            coordinates,
+            layout,
+            rich_landscapes,
+            collapse_tandems,
+            threads,
+        } => {
+            let options = BuildOptions {
+                family_format: family_format(&family_format_name, split_duplications)?,
+                coordinates: Coordinates::try_from(coordinates.as_str())?,
+                layout: Layout::try_from(layout.as_str())?,
+                rich_landscapes,
+                collapse_tandems,
+                threads,
+                ..Default::default()
+            };
+            let summary = dbmaker::db_from_files(
+                &families,
+                &annotations,
+                &out,
+                &species_pattern,
+                &id_type,
+                &id_pattern,
+                window,
+                &options,
+            )?;
+            let mut species = summary.genes.iter().collect::<Vec<_>>();
+            species.sort();
+            for (species, genes) in species {
+                println!("{}\t{}", species, genes);
+            }
+        }
+        Command::Get { db, genes, window } => {
+            let window = match window {
+                Some(window) => window,
+                None => stored_window(&db)?,
+            };
+            let book = GeneBook::inline(&db, window, "id")?;
+            for id in genes {
+                let g = book.get(&id)?;
+                println!(
+                    "{} {} {}:{}-{} {} family {}",
+                    g.id.bold(),
+                    g.species,
+                    g.chr,
+                    g.pos,
+                    g.end,
+                    g.strand,
+                    g.family_name()
+                        .map(str::to_owned)
+                        .unwrap_or_else(|| g.family.to_string())
+                );
+                let left = g.left_landscape.iter().map(tail).collect::<Vec<_>>();
+                let right = g.right_landscape.iter().map(tail).collect::<Vec<_>>();
+                println!(
+                    "  {} {} {}",
+                    left.join(" "),
+                    format!("[{}{}]", g.strand, g.family).bold(),
+                    right.join(" ")
+                );
+            }
+        }
+        Command::Query {
+            db,
+            species,
+            chr,
+            range: region,
+            family,
+            format,
+        } => {
+            let query = GeneQuery {
+                species,
+                chr,
+                range: region.as_deref().map(range).transpose()?,
+                family,
+            };
+            let book = GeneBook::inline(&db, stored_window(&db)?, "id")?;
+            let genes = book.query(&query)?;
+            let landscape = |l: &[TailGene]| {
+                l.iter()
+                    .map(|t| {
+                        let mut tail = json!({"family": t.family, "strand": t.strand.to_string()});
+                        if let (Some(id), Some(pos)) = (t.id.as_ref(), t.pos) {
+                            tail["id"] = json!(id);
+                            tail["start"] = json!(pos);
+                        }
+                        if t.copies > 1 {
+                            tail["copies"] = json!(t.copies);
+                        }
+                        tail
+                    })
+                    .collect::<Vec<_>>()
+            };
+            let name = |g: &Gene| g.family_name().unwrap_or_default().to_owned();
+            match format.as_str() {
+                "tsv" => {
+                    println!("species\tchr\tid\tfamily\tfamily_name\tstart\tstop\tstrand");
+                    for g in genes {
+                        println!(
+                            "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                            g.species,
+                            g.chr,
+                            g.id,
+                            g.family,
+                            name(&g),
+                            g.pos,
+                            g.end,
+                            g.strand
+                        );
+                    }
+                }
+                "json" => {
+                    for g in genes {
+                        println!(
+                            "{}",
+                            json!({
+                                "species": g.species,
+                                "chr": g.chr,
+                                "id": g.id,
+                                "family": g.family,
+                                "family_name": g.family_name(),
+                                "start": g.pos,
+                                "stop": g.end,
+                                "strand": g.strand.to_string(),
+                                "left_landscape": landscape(&g.left_landscape),
+                                "right_landscape": landscape(&g.right_landscape),
+                            })
+                        );
+                    }
+                }
+                _ => bail!("unknown output format: {}", format.yellow().bold()),
+            }
+        }
+        Command::Species { db } => {
+            let stats = GeneBook::inline(&db, stored_window(&db)?, "id")?.stats()?;
+            let mut species = stats.genes.into_iter().collect::<Vec<_>>();
+            species.sort();
+            for (species, genes) in species {
+                println!("{}\t{}", species, genes);
+            }
+        }
+        Command::Stats { db, json } => {
+            let stats = GeneBook::inline(&db, stored_window(&db)?, "id")?.stats()?;
+            let mut species = stats.genes.keys().collect::<Vec<_>>();
+            species.sort();
+            let chromosomes = |sp: &str| stats.chromosomes.keys().filter(|(s, _)| s == sp).count();
+            let families = |sp: &str| stats.species_families.get(sp).copied().unwrap_or_default();
+            if json {
+                println!(
+                    "{}",
+                    json!({
+                        "genes": stats.genes.values().sum::<usize>(),
+                        "chromosomes": stats.chromosomes.len(),
+                        "families": stats.families,
+                        "mean_landscape": stats.mean_landscape,
+                        "median_landscape": stats.median_landscape,
+                        "species": species
+                            .iter()
+                            .map(|sp| json!({
+                                "name": sp,
+                                "genes": stats.genes[*sp],
+                                "chromosomes": chromosomes(sp),
+                                "families": families(sp),
+                            }))
+                            .collect::<Vec<_>>(),
+                        "family_sizes": stats
+                            .family_sizes
+                            .iter()
+                            .map(|(size, count)| json!({"size": size, "families": count}))
+                            .collect::<Vec<_>>(),
+                    })
+                );
+            } else {
+                println!("species\tgenes\tchromosomes\tfamilies");
+                for sp in species.iter() {
+                    println!(
+                        "{}\t{}\t{}\t{}",
+                        sp,
+                        stats.genes[*sp],
+                        chromosomes(sp),
+                        families(sp)
+                    );
+                }
+                println!();
+                println!("genes\t{}", stats.genes.values().sum::<usize>());
+                println!("chromosomes\t{}", stats.chromosomes.len());
+                println!("families\t{}", stats.families);
+                println!("mean landscape\t{:.2}", stats.mean_landscape);
+                println!("median landscape\t{:.2}", stats.median_landscape);
+                println!();
+                println!("family size\tfamilies");
+                for (size, count) in stats.family_sizes {
+                    println!("{}\t{}", size, count);
+                }
+            }
+        }
+        Command::Convert {
+            input,
+            output,
+            from,
+            to,
+        } => {
+            let from = from.as_deref().map(format).transpose()?;
+            let to = to.as_deref().map(format).transpose()?;
+            let count = syntesuite::output::convert(&input, &output, from, to)?;
+            eprintln!("{} records converted", count);
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,180 @@
+//! A minimal parser for Newick trees, with support for NHX annotations:
+//! ```text
+//! ((a:0.1,b:0.2)n1[&&NHX:D=N],c)root[&&NHX:D=Y];
+//! ```
+use std::collections::HashMap;
+
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum NewickError {
+    #[error("unexpected end of tree")]
+    UnexpectedEnd,
+
+    #[error("unexpected character `{0}` at position {1}")]
+    UnexpectedChar(char, usize),
+
+    #[error("invalid branch length at position {0}")]
+    InvalidLength(usize),
+}
+
+#[derive(Debug, Default)]
+pub struct Node {
+    pub name: Option<String>,
+    pub length: Option<f64>,
+    pub nhx: HashMap<String, String>,
+    pub children: Vec<Node>,
+}
+impl Node {
+    pub fn is_leaf(&self) -> bool {
+        self.children.is_empty()
+    }
+
+    /// Whether this node is annotated as a duplication, i.e. carries `D=Y`
+    #[allow(dead_code)]
+    pub fn is_duplication(&self) -> bool {
+        self.nhx
+            .get("D")
+            .map(|d| d == "Y" || d == "T" || d == "1")
+            .unwrap_or(false)
+    }
+
+    /// The names of all the leaves below this node
+    pub fn leaves(&self) -> Vec<&str> {
+        let mut r = Vec::new();
+        let mut todo = vec![self];
+        while let Some(n) = todo.pop() {
+            if n.is_leaf() {
+                r.extend(n.name.as_deref());
+            } else {
+                todo.extend(n.children.iter().rev());
+            }
+        }
+        r
+    }
+}
+
+struct Parser<'a> {
+    s: &'a [u8],
+    i: usize,
+}
+impl Parser<'_> {
+    fn peek(&self) -> Option<char> {
+        self.s.get(self.i).map(|c| *c as char)
+    }
+
+    fn skip_ws(&mut self) {
+        while self.peek().map(|c| c.is_whitespace()).unwrap_or(false) {
+            self.i += 1;
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), NewickError> {
+        self.skip_ws();
+        match self.peek() {
+            Some(x) if x == c => {
+                self.i += 1;
+                Ok(())
+            }
+            Some(x) => Err(NewickError::UnexpectedChar(x, self.i)),
+            None => Err(NewickError::UnexpectedEnd),
+        }
+    }
+
+    fn token(&mut self) -> String {
+        self.skip_ws();
+        if self.peek() == Some('\'') {
+            self.i += 1;
+            let start = self.i;
+            while self.peek().map(|c| c != '\'').unwrap_or(false) {
+                self.i += 1;
+            }
+            let r = String::from_utf8_lossy(&self.s[start..self.i]).to_string();
+            self.i += 1;
+            r
+        } else {
+            let start = self.i;
+            while self
+                .peek()
+                .map(|c| !"(),:;[".contains(c) && !c.is_whitespace())
+                .unwrap_or(false)
+            {
+                self.i += 1;
+            }
+            String::from_utf8_lossy(&self.s[start..self.i]).to_string()
+        }
+    }
+
+    fn node(&mut self) -> Result<Node, NewickError> {
+        let mut node = Node::default();
+        self.skip_ws();
+        if self.peek() == Some('(') {
+            self.i += 1;
+            loop {
+                node.children.push(self.node()?);
+                self.skip_ws();
+                match self.peek() {
+                    Some(',') => self.i += 1,
+                    Some(')') => {
+                        self.i += 1;
+                        break;
+                    }
+                    Some(c) => return Err(NewickError::UnexpectedChar(c, self.i)),
+                    None => return Err(NewickError::UnexpectedEnd),
+                }
+            }
+        }
+
+        let name = self.token();
+        if !name.is_empty() {
+            node.name = Some(name);
+        }
+        self.skip_ws();
+        if self.peek() == Some(':') {
+            self.i += 1;
+            let start = self.i;
+            let length = self.token();
+            node.length = Some(
+                length
+                    .parse()
+                    .map_err(|_| NewickError::InvalidLength(start))?,
+            );
+        }
+        self.skip_ws();
+        if self.peek() == Some('[') {
+            let start = self.i + 1;
+            while self.peek().map(|c| c != ']').unwrap_or(false) {
+                self.i += 1;
+            }
+            let comment = String::from_utf8_lossy(&self.s[start..self.i]).to_string();
+            self.expect(']')?;
+            if let Some(nhx) = comment.strip_prefix("&&NHX") {
+                node.nhx = nhx
+                    .split(':')
+                    .filter_map(|kv| kv.split_once('='))
+                    .map(|(k, v)| (k.to_owned(), v.to_owned()))
+                    .collect();
+            }
+        }
+
+        Ok(node)
+    }
+}
+
+/// Parse all the `;`-terminated trees contained in `s`
+pub fn parse(s: &str) -> Result<Vec<Node>, NewickError> {
+    let mut parser = Parser {
+        s: s.as_bytes(),
+        i: 0,
+    };
+    let mut r = Vec::new();
+    loop {
+        parser.skip_ws();
+        if parser.peek().is_none() {
+            break;
+        }
+        r.push(parser.node()?);
+        parser.expect(';')?;
+    }
+    Ok(r)
+}
@@ -0,0 +1,76 @@
+//! Writing of annotation files, whatever the format the records were read
+//! from.
+use anyhow::*;
+use flate2::{write::GzEncoder, Compression};
+use std::{
+    fs::File,
+    io::{BufWriter, Write},
+};
+
+pub use crate::bed::BedWriter;
+pub use crate::chrom::ChromWriter;
+pub use crate::gff::GffWriter;
+use crate::{errors::FileError, input, Format, Record};
+
+/// A destination for annotation records, converting them to its own format
+/// and coordinate convention
+pub trait RecordSink {
+    fn write_record(&mut self, record: &Record) -> Result<()>;
+}
+
+/// Write `records` to `out` in `format`, returning how many were written
+fn write_records<W: Write>(
+    records: impl Iterator<Item = Result<Record>>,
+    format: Format,
+    out: W,
+) -> Result<usize> {
+    let mut sink: Box<dyn RecordSink> = match format {
+        Format::Gff => Box::new(GffWriter::new(out)),
+        Format::Bed => Box::new(BedWriter::new(out)),
+        Format::Chrom => Box::new(ChromWriter::new(out)),
+    };
+    let mut count = 0;
+    for record in records {
+        sink.write_record(&record?)?;
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Stream the records of `input` to `output`, converting them from the
+/// format `from` to the format `to`, and return how many were converted.
+///
+/// Coordinates and strands are converted to the convention of the target
+/// format. `input` may be `-` to read from the standard input. If not
+/// specified, `from` is guessed as by [`crate::open_annotations`], and `to`
+/// from the extension of `output`, which is gzipped if it ends with `.gz`.
+pub fn convert(
+    input: &str,
+    output: &str,
+    from: Option<Format>,
+    to: Option<Format>,
+) -> Result<usize> {
+    let to = to
+        .or_else(|| Format::from_extension(output))
+        .ok_or_else(|| FileError::InvalidFilename(output.to_owned()))
+        .with_context(|| "unable to guess the output format")?;
+    let records = match from {
+        Some(from) => input::records_as(input, from)?,
+        None => input::records(input)?.1,
+    }
+    .map(|r| r.map_err(Error::from));
+
+    let file = File::create(output).with_context(|| anyhow!("while creating {}", output))?;
+    let count = if output.ends_with(".gz") {
+        let mut out = GzEncoder::new(BufWriter::new(file), Compression::default());
+        let count = write_records(records, to, &mut out)?;
+        out.finish()?.flush()?;
+        count
+    } else {
+        let mut out = BufWriter::new(file);
+        let count = write_records(records, to, &mut out)?;
+        out.flush()?;
+        count
+    };
+    Ok(count)
+}
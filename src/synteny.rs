@@ -0,0 +1,905 @@
+//! Comparisons of the genomic neighbourhoods of genes, beyond the simple
+//! overlap of [`crate::genebook::synteny_score`], and of whole genomes.
+
+use anyhow::Result;
+use std::collections::HashMap;
+
+use crate::genebook::{synteny_score_with, FamilyID, Gene, GeneBook, SyntenyMetric, TailGene};
+use crate::Strand;
+
+/// Whether [`align`] aligns whole landscapes or their best-matching parts
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum AlignmentMode {
+    /// Needleman–Wunsch, aligning both landscapes end to end
+    #[default]
+    Global,
+    /// Smith–Waterman, aligning only their best-scoring segments
+    Local,
+}
+
+/// The scores used by [`align`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct AlignmentScoring {
+    /// Two genes of the same family
+    pub matching: f64,
+    /// Two genes of different families
+    pub mismatch: f64,
+    /// A gene aligned with nothing
+    pub gap: f64,
+    /// Added to `matching` if both genes lie on the same, known, strand
+    pub strand_bonus: f64,
+}
+impl Default for AlignmentScoring {
+    fn default() -> Self {
+        AlignmentScoring {
+            matching: 1.,
+            mismatch: -1.,
+            gap: -1.,
+            strand_bonus: 0.,
+        }
+    }
+}
+impl AlignmentScoring {
+    fn pair(&self, a: &TailGene, b: &TailGene) -> f64 {
+        if a.family != b.family {
+            self.mismatch
+        } else if a.strand == b.strand && a.strand != Strand::Unknown {
+            self.matching + self.strand_bonus
+        } else {
+            self.matching
+        }
+    }
+}
+
+/// The alignment of two landscapes
+#[derive(Debug, Clone, PartialEq)]
+pub struct Alignment {
+    /// The aligned positions in both landscapes, in order; `None` stands
+    /// for a gap
+    pub pairs: Vec<(Option<usize>, Option<usize>)>,
+    pub score: f64,
+}
+impl Alignment {
+    /// The positions of the genes of the same family aligned together
+    pub fn matches<'a>(
+        &'a self,
+        a: &'a [TailGene],
+        b: &'a [TailGene],
+    ) -> impl Iterator<Item = (usize, usize)> + 'a {
+        self.pairs.iter().filter_map(move |p| match *p {
+            (Some(i), Some(j)) if a[i].family == b[j].family => Some((i, j)),
+            _ => None,
+        })
+    }
+}
+
+#[derive(Clone, Copy)]
+enum Step {
+    Stop,
+    Diagonal,
+    Up,
+    Left,
+}
+
+/// Align the landscapes `a` and `b`, e.g. as collected from
+/// [`crate::genebook::Gene::landscape`]
+pub fn align(
+    a: &[TailGene],
+    b: &[TailGene],
+    mode: AlignmentMode,
+    scoring: &AlignmentScoring,
+) -> Alignment {
+    let (n, m) = (a.len(), b.len());
+    let local = mode == AlignmentMode::Local;
+    let mut scores = vec![vec![0.; m + 1]; n + 1];
+    let mut steps = vec![vec![Step::Stop; m + 1]; n + 1];
+    if !local {
+        for i in 1..=n {
+            scores[i][0] = i as f64 * scoring.gap;
+            steps[i][0] = Step::Up;
+        }
+        for j in 1..=m {
+            scores[0][j] = j as f64 * scoring.gap;
+            steps[0][j] = Step::Left;
+        }
+    }
+
+    let mut best = (0., 0, 0);
+    for i in 1..=n {
+        for j in 1..=m {
+            let mut cell = (
+                scores[i - 1][j - 1] + scoring.pair(&a[i - 1], &b[j - 1]),
+                Step::Diagonal,
+            );
+            for candidate in [
+                (scores[i - 1][j] + scoring.gap, Step::Up),
+                (scores[i][j - 1] + scoring.gap, Step::Left),
+            ] {
+                if candidate.0 > cell.0 {
+                    cell = candidate;
+                }
+            }
+            if local && cell.0 <= 0. {
+                cell = (0., Step::Stop);
+            }
+            (scores[i][j], steps[i][j]) = cell;
+            if cell.0 > best.0 {
+                best = (cell.0, i, j);
+            }
+        }
+    }
+
+    let (score, mut i, mut j) = if local { best } else { (scores[n][m], n, m) };
+    let mut pairs = Vec::new();
+    loop {
+        match steps[i][j] {
+            Step::Stop => break,
+            Step::Diagonal => {
+                i -= 1;
+                j -= 1;
+                pairs.push((Some(i), Some(j)));
+            }
+            Step::Up => {
+                i -= 1;
+                pairs.push((Some(i), None));
+            }
+            Step::Left => {
+                j -= 1;
+                pairs.push((None, Some(j)));
+            }
+        }
+    }
+    pairs.reverse();
+
+    Alignment { pairs, score }
+}
+
+/// The settings of [`syntenic_blocks`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BlockOptions {
+    /// How many genes may lie between two consecutive anchors of a block,
+    /// in either genome
+    pub max_gap: usize,
+    /// How many anchors a block must have to be reported
+    pub min_anchors: usize,
+}
+impl Default for BlockOptions {
+    fn default() -> Self {
+        BlockOptions {
+            max_gap: 10,
+            min_anchors: 5,
+        }
+    }
+}
+
+/// A run of genes whose families appear in the same order in two genomes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntenicBlock {
+    pub chr_a: String,
+    pub start_a: usize,
+    pub end_a: usize,
+    pub chr_b: String,
+    pub start_b: usize,
+    pub end_b: usize,
+    /// [`Strand::Reverse`] if the block is inverted in the second genome
+    pub orientation: Strand,
+    /// The IDs of the pairs of genes of the same family anchoring the
+    /// block, in order along the first genome
+    pub anchors: Vec<(String, String)>,
+    /// The score of the chain of anchors, as set by [`ChainOptions`]
+    pub score: i64,
+}
+
+/// A pair of genes of the same family, with their rank along their
+/// chromosomes
+struct Anchor<'a> {
+    a: &'a Gene,
+    b: &'a Gene,
+    rank_a: usize,
+    rank_b: usize,
+}
+
+/// Detect the syntenic blocks between `species_a` and `species_b`, i.e. the
+/// maximal chains of anchors found in the same order, or in reverse order
+/// for inverted blocks, on a chromosome of each, ordered by position in
+/// `species_a`
+pub fn syntenic_blocks(
+    book: &GeneBook,
+    species_a: &str,
+    species_b: &str,
+    options: &BlockOptions,
+) -> Result<Vec<SyntenicBlock>> {
+    collinear_chains(
+        book,
+        species_a,
+        species_b,
+        &ChainOptions {
+            match_score: 1,
+            gap_penalty: 0,
+            max_gap: options.max_gap,
+            min_anchors: options.min_anchors,
+        },
+    )
+}
+
+/// The settings of [`collinear_chains`], defaulting to those of MCScanX
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChainOptions {
+    /// The score of each anchor of a chain
+    pub match_score: i64,
+    /// The score of each gene skipped between two consecutive anchors, in
+    /// the genome where most are skipped; usually negative
+    pub gap_penalty: i64,
+    /// How many genes may lie between two consecutive anchors of a chain,
+    /// in either genome
+    pub max_gap: usize,
+    /// How many anchors a chain must have to be reported
+    pub min_anchors: usize,
+}
+impl Default for ChainOptions {
+    fn default() -> Self {
+        ChainOptions {
+            match_score: 50,
+            gap_penalty: -1,
+            max_gap: 25,
+            min_anchors: 5,
+        }
+    }
+}
+
+/// Chain the pairs of homologous genes of `species_a` and `species_b` into
+/// collinear blocks as DAGchainer does, i.e. extracting in turn the chains
+/// of anchors of highest score, each anchor adding the match score and
+/// each gap between them the gap penalty; blocks are ordered by position in
+/// `species_a`
+pub fn collinear_chains(
+    book: &GeneBook,
+    species_a: &str,
+    species_b: &str,
+    options: &ChainOptions,
+) -> Result<Vec<SyntenicBlock>> {
+    let genes_a = book.genes_of_species(species_a)?;
+    let genes_b = book.genes_of_species(species_b)?;
+    let ranks_a = ranks(&genes_a);
+    let ranks_b = ranks(&genes_b);
+
+    let mut by_family = HashMap::<FamilyID, Vec<usize>>::new();
+    for (j, g) in genes_b.iter().enumerate() {
+        by_family.entry(g.family).or_default().push(j);
+    }
+    let mut anchors = HashMap::<(&str, &str), Vec<Anchor>>::new();
+    for (i, a) in genes_a.iter().enumerate() {
+        for &j in by_family.get(&a.family).into_iter().flatten() {
+            let b = &genes_b[j];
+            anchors
+                .entry((a.chr.as_str(), b.chr.as_str()))
+                .or_default()
+                .push(Anchor {
+                    a,
+                    b,
+                    rank_a: ranks_a[i],
+                    rank_b: ranks_b[j],
+                });
+        }
+    }
+
+    let mut blocks = Vec::new();
+    for mut anchors in anchors.into_values() {
+        anchors.sort_by_key(|x| (x.rank_a, x.rank_b));
+        blocks.extend(chain(&anchors, options));
+    }
+    blocks.sort_by(|x, y| (&x.chr_a, x.start_a).cmp(&(&y.chr_a, y.start_a)));
+    Ok(blocks)
+}
+
+/// The rank of each gene along its chromosome, `genes` being sorted by
+/// chromosome and position
+fn ranks(genes: &[Gene]) -> Vec<usize> {
+    let mut r = Vec::with_capacity(genes.len());
+    for (i, g) in genes.iter().enumerate() {
+        r.push(match i.checked_sub(1).map(|p| &genes[p]) {
+            Some(previous) if previous.chr == g.chr => r[i - 1] + 1,
+            _ => 0,
+        });
+    }
+    r
+}
+
+/// Chain `anchors`, sorted by rank in the first genome, into blocks
+fn chain(anchors: &[Anchor], options: &ChainOptions) -> Vec<SyntenicBlock> {
+    let reach = options.max_gap + 1;
+    // The score of the best chain ending on each anchor, and the anchor
+    // before it, in each orientation
+    let orientations = [Strand::Direct, Strand::Reverse];
+    let mut predecessors = Vec::new();
+    let mut totals = Vec::new();
+    let mut ends = Vec::new();
+    for (o, &orientation) in orientations.iter().enumerate() {
+        let mut scores = vec![options.match_score; anchors.len()];
+        let mut previous = vec![None; anchors.len()];
+        for k in 0..anchors.len() {
+            for l in (0..k).rev() {
+                if anchors[k].rank_a - anchors[l].rank_a > reach {
+                    break;
+                }
+                if anchors[k].rank_a == anchors[l].rank_a {
+                    continue;
+                }
+                let step = if orientation == Strand::Direct {
+                    anchors[k].rank_b.checked_sub(anchors[l].rank_b)
+                } else {
+                    anchors[l].rank_b.checked_sub(anchors[k].rank_b)
+                };
+                let Some(step) = step.filter(|s| (1..=reach).contains(s)) else {
+                    continue;
+                };
+                let skipped = (anchors[k].rank_a - anchors[l].rank_a).max(step) - 1;
+                let score = scores[l] + options.match_score + options.gap_penalty * skipped as i64;
+                if score > scores[k] {
+                    scores[k] = score;
+                    previous[k] = Some(l);
+                }
+            }
+        }
+        ends.extend(scores.iter().enumerate().map(|(k, &n)| (n, o, k)));
+        predecessors.push(previous);
+        totals.push(scores);
+    }
+    ends.sort_by_key(|x| std::cmp::Reverse(x.0));
+
+    // Extract the best chains first, each anchor belonging to one block at
+    // most
+    let mut used = vec![false; anchors.len()];
+    let mut blocks = Vec::new();
+    for (score, o, end) in ends {
+        let previous = &predecessors[o];
+        let mut members = Vec::new();
+        let mut k = Some(end);
+        while let Some(current) = k.filter(|&c| !used[c]) {
+            members.push(current);
+            k = previous[current];
+        }
+        if members.len() < options.min_anchors.max(1) {
+            continue;
+        }
+        members.reverse();
+        for &m in &members {
+            used[m] = true;
+        }
+        // The chain may have been cut short by anchors already used
+        let score = score - totals[o][members[0]] + options.match_score;
+        let genes = members.iter().map(|&m| &anchors[m]).collect::<Vec<_>>();
+        blocks.push(SyntenicBlock {
+            chr_a: genes[0].a.chr.clone(),
+            start_a: genes.iter().map(|x| x.a.pos).min().unwrap_or_default(),
+            end_a: genes.iter().map(|x| x.a.end).max().unwrap_or_default(),
+            chr_b: genes[0].b.chr.clone(),
+            start_b: genes.iter().map(|x| x.b.pos).min().unwrap_or_default(),
+            end_b: genes.iter().map(|x| x.b.end).max().unwrap_or_default(),
+            orientation: orientations[o],
+            anchors: genes
+                .iter()
+                .map(|x| (x.a.id.clone(), x.b.id.clone()))
+                .collect(),
+            score,
+        });
+    }
+    blocks
+}
+
+/// A pair of homologous genes on a dot plot
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Dot {
+    /// The rank of the gene along the genome of the first species
+    pub x: usize,
+    /// The rank of the gene along the genome of the second species
+    pub y: usize,
+    pub family: FamilyID,
+}
+
+/// The content of a dot plot between two species, whose genomes are laid
+/// out as their chromosomes put end to end, in lexicographic order
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DotPlot {
+    pub dots: Vec<Dot>,
+    /// The chromosomes of the first species, with the rank of their first
+    /// gene along its genome
+    pub chromosomes_a: Vec<(String, usize)>,
+    /// The chromosomes of the second species, with the rank of their first
+    /// gene along its genome
+    pub chromosomes_b: Vec<(String, usize)>,
+}
+
+/// The dots of all the pairs of genes of `species_a` and `species_b`
+/// sharing a family, ordered by `x` then `y`
+pub fn dot_plot(book: &GeneBook, species_a: &str, species_b: &str) -> Result<DotPlot> {
+    let genes_a = book.genes_of_species(species_a)?;
+    let genes_b = book.genes_of_species(species_b)?;
+
+    let mut by_family = HashMap::<FamilyID, Vec<usize>>::new();
+    for (y, g) in genes_b.iter().enumerate() {
+        by_family.entry(g.family).or_default().push(y);
+    }
+    let dots = genes_a
+        .iter()
+        .enumerate()
+        .flat_map(|(x, a)| {
+            by_family
+                .get(&a.family)
+                .into_iter()
+                .flatten()
+                .map(move |&y| Dot {
+                    x,
+                    y,
+                    family: a.family,
+                })
+        })
+        .collect();
+
+    Ok(DotPlot {
+        dots,
+        chromosomes_a: boundaries(&genes_a),
+        chromosomes_b: boundaries(&genes_b),
+    })
+}
+
+/// The chromosomes of `genes`, sorted by chromosome and position, with the
+/// rank of their first gene
+fn boundaries(genes: &[Gene]) -> Vec<(String, usize)> {
+    let mut r = Vec::<(String, usize)>::new();
+    for (i, g) in genes.iter().enumerate() {
+        if r.last().is_none_or(|(chr, _)| *chr != g.chr) {
+            r.push((g.chr.clone(), i));
+        }
+    }
+    r
+}
+
+/// A run of genes of the same family lying close to each other on a
+/// chromosome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TandemArray {
+    pub chr: String,
+    pub start: usize,
+    pub end: usize,
+    pub family: FamilyID,
+    /// The IDs of the copies, in order along the chromosome
+    pub genes: Vec<String>,
+}
+impl TandemArray {
+    /// The number of copies in the array
+    pub fn copy_number(&self) -> usize {
+        self.genes.len()
+    }
+}
+
+/// Detect the tandem arrays of `species`, i.e. the runs of at least two
+/// genes of the same family where at most `max_gap` genes lie between two
+/// consecutive copies, ordered by position
+pub fn tandem_arrays(book: &GeneBook, species: &str, max_gap: usize) -> Result<Vec<TandemArray>> {
+    let genes = book.genes_of_species(species)?;
+    let ranks = ranks(&genes);
+
+    // The genes of the array currently open for each family of the
+    // chromosome being scanned
+    let mut open = HashMap::<FamilyID, Vec<usize>>::new();
+    let mut arrays = Vec::new();
+    let mut close = |members: Vec<usize>| {
+        if members.len() >= 2 {
+            arrays.push(members);
+        }
+    };
+    for (i, g) in genes.iter().enumerate() {
+        if ranks[i] == 0 {
+            open.drain().for_each(|(_, members)| close(members));
+        }
+        let members = open.entry(g.family).or_default();
+        if members
+            .last()
+            .is_some_and(|&last| ranks[i] - ranks[last] > max_gap + 1)
+        {
+            close(std::mem::take(members));
+        }
+        members.push(i);
+    }
+    open.into_values().for_each(close);
+
+    arrays.sort_by_key(|members| members[0]);
+    Ok(arrays
+        .into_iter()
+        .map(|members| TandemArray {
+            chr: genes[members[0]].chr.clone(),
+            start: members
+                .iter()
+                .map(|&m| genes[m].pos)
+                .min()
+                .unwrap_or_default(),
+            end: members
+                .iter()
+                .map(|&m| genes[m].end)
+                .max()
+                .unwrap_or_default(),
+            family: genes[members[0]].family,
+            genes: members.iter().map(|&m| genes[m].id.clone()).collect(),
+        })
+        .collect())
+}
+
+/// The settings of [`syntenic_clusters`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ClusterOptions {
+    /// How similar the neighborhoods of two genes must be to link them
+    pub min_score: f64,
+    /// How the neighborhoods are compared
+    pub metric: SyntenyMetric,
+    /// How many species a cluster must span to be reported
+    pub min_species: usize,
+}
+impl Default for ClusterOptions {
+    fn default() -> Self {
+        ClusterOptions {
+            min_score: 0.5,
+            metric: SyntenyMetric::default(),
+            min_species: 3,
+        }
+    }
+}
+
+/// Genes of a family whose neighborhoods are similar across several
+/// species, i.e. a syntenic orthogroup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyntenicCluster {
+    pub family: FamilyID,
+    /// The IDs of the genes of the cluster, ordered by species then ID
+    pub genes: Vec<String>,
+    /// The species spanned by the cluster, in lexicographic order
+    pub species: Vec<String>,
+}
+
+/// Split the families of `book` into clusters of genes linked, directly or
+/// not, by pairs of genes from different species whose neighborhoods are
+/// similar enough, ordered by family then by first gene
+pub fn syntenic_clusters(
+    book: &GeneBook,
+    options: &ClusterOptions,
+) -> Result<Vec<SyntenicCluster>> {
+    let mut by_family = HashMap::<FamilyID, Vec<Gene>>::new();
+    for species in book.species() {
+        for g in book.genes_of_species(&species)? {
+            by_family.entry(g.family).or_default().push(g);
+        }
+    }
+
+    let mut clusters = Vec::new();
+    for (family, genes) in by_family {
+        // Union-find over the genes of the family
+        let mut parent = (0..genes.len()).collect::<Vec<_>>();
+        fn root(parent: &mut [usize], mut i: usize) -> usize {
+            while parent[i] != i {
+                parent[i] = parent[parent[i]];
+                i = parent[i];
+            }
+            i
+        }
+        for i in 0..genes.len() {
+            for j in i + 1..genes.len() {
+                if genes[i].species != genes[j].species
+                    && synteny_score_with(&genes[i], &genes[j], options.metric) >= options.min_score
+                {
+                    let (ri, rj) = (root(&mut parent, i), root(&mut parent, j));
+                    parent[ri] = rj;
+                }
+            }
+        }
+
+        let mut components = HashMap::<usize, Vec<&Gene>>::new();
+        for (i, g) in genes.iter().enumerate() {
+            components.entry(root(&mut parent, i)).or_default().push(g);
+        }
+        for mut members in components.into_values() {
+            let mut species = members
+                .iter()
+                .map(|g| g.species.clone())
+                .collect::<Vec<_>>();
+            species.sort();
+            species.dedup();
+            if species.len() < options.min_species.max(1) {
+                continue;
+            }
+            members.sort_by(|a, b| (&a.species, &a.id).cmp(&(&b.species, &b.id)));
+            clusters.push(SyntenicCluster {
+                family,
+                genes: members.iter().map(|g| g.id.clone()).collect(),
+                species,
+            });
+        }
+    }
+    clusters.sort_by(|a, b| (a.family, &a.genes).cmp(&(b.family, &b.genes)));
+    Ok(clusters)
+}
+
+/// The settings of [`ortholog_groups`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct OrthologyOptions {
+    /// How similar the neighborhoods of two genes must be to put them in the
+    /// same group
+    pub min_score: f64,
+    /// How the neighborhoods are compared
+    pub metric: SyntenyMetric,
+}
+impl Default for OrthologyOptions {
+    fn default() -> Self {
+        OrthologyOptions {
+            min_score: 0.3,
+            metric: SyntenyMetric::default(),
+        }
+    }
+}
+
+/// A group of genes of a family deemed orthologous, holding at most one
+/// gene per species
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubFamily {
+    pub family: FamilyID,
+    /// The IDs of the genes of the group, ordered by species then ID
+    pub genes: Vec<String>,
+}
+
+/// Partition the genes of `family` into groups of orthologs, merging in
+/// turn the groups holding the pairs of genes with the most similar
+/// neighborhoods, as long as they do not share any species; groups are
+/// ordered by decreasing size, then by first gene
+pub fn ortholog_groups(
+    book: &GeneBook,
+    family: FamilyID,
+    options: &OrthologyOptions,
+) -> Result<Vec<SubFamily>> {
+    let genes = book.genes_of_family(family)?;
+
+    let mut pairs = Vec::new();
+    for i in 0..genes.len() {
+        for j in i + 1..genes.len() {
+            if genes[i].species != genes[j].species {
+                let score = synteny_score_with(&genes[i], &genes[j], options.metric);
+                if score >= options.min_score {
+                    pairs.push((score, i, j));
+                }
+            }
+        }
+    }
+    pairs.sort_by(|x, y| y.0.total_cmp(&x.0).then((x.1, x.2).cmp(&(y.1, y.2))));
+
+    // The group of each gene, and the members of each group
+    let mut group = (0..genes.len()).collect::<Vec<_>>();
+    let mut members = (0..genes.len()).map(|i| vec![i]).collect::<Vec<_>>();
+    for (_, i, j) in pairs {
+        let (gi, gj) = (group[i], group[j]);
+        if gi == gj
+            || members[gi].iter().any(|&m| {
+                members[gj]
+                    .iter()
+                    .any(|&n| genes[m].species == genes[n].species)
+            })
+        {
+            continue;
+        }
+        for m in std::mem::take(&mut members[gj]) {
+            group[m] = gi;
+            members[gi].push(m);
+        }
+    }
+
+    let mut groups = members
+        .into_iter()
+        .filter(|m| !m.is_empty())
+        .map(|mut m| {
+            m.sort_by(|&a, &b| {
+                (&genes[a].species, &genes[a].id).cmp(&(&genes[b].species, &genes[b].id))
+            });
+            SubFamily {
+                family,
+                genes: m.into_iter().map(|i| genes[i].id.clone()).collect(),
+            }
+        })
+        .collect::<Vec<_>>();
+    groups.sort_by(|a, b| {
+        b.genes
+            .len()
+            .cmp(&a.genes.len())
+            .then(a.genes.cmp(&b.genes))
+    });
+    Ok(groups)
+}
+
+/// Split each family of `book` holding several genes of a species into
+/// groups of orthologs, as [`ortholog_groups`] does
+pub fn refine_families(book: &GeneBook, options: &OrthologyOptions) -> Result<Vec<SubFamily>> {
+    let mut copies = HashMap::<(FamilyID, String), usize>::new();
+    for species in book.species() {
+        for g in book.genes_of_species(&species)? {
+            *copies.entry((g.family, species.clone())).or_default() += 1;
+        }
+    }
+    let mut families = copies
+        .into_iter()
+        .filter(|(_, n)| *n > 1)
+        .map(|((family, _), _)| family)
+        .collect::<Vec<_>>();
+    families.sort();
+    families.dedup();
+
+    let mut r = Vec::new();
+    for family in families {
+        r.extend(ortholog_groups(book, family, options)?);
+    }
+    Ok(r)
+}
+
+/// A gene of a painted genome
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaintedGene {
+    pub species: String,
+    pub id: String,
+    pub chr: String,
+    pub pos: usize,
+    pub end: usize,
+    /// The index in [`Painting::chromosomes`] of the reference chromosome
+    /// the gene is assigned to, if any of its neighbors has a homolog there
+    pub color: Option<usize>,
+}
+
+/// The genomes of a book painted after the chromosomes of a reference
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Painting {
+    /// The chromosomes of the reference species in natural order, each
+    /// identified by its index, e.g. to pick its color
+    pub chromosomes: Vec<String>,
+    /// The genes of the other species, ordered by species then position
+    pub genes: Vec<PaintedGene>,
+}
+
+/// Paint the genomes of all the species of `book` but `reference` after the
+/// chromosomes of `reference`, each gene being assigned the reference
+/// chromosome where most of the homologs of its landscape, itself included,
+/// lie; ties go to the first chromosome
+pub fn paint(book: &GeneBook, reference: &str) -> Result<Painting> {
+    let genes = book.genes_of_species(reference)?;
+    let mut chromosomes = genes.iter().map(|g| g.chr.clone()).collect::<Vec<_>>();
+    chromosomes.dedup();
+    let index = chromosomes
+        .iter()
+        .enumerate()
+        .map(|(i, chr)| (chr.as_str(), i))
+        .collect::<HashMap<_, _>>();
+    // The reference chromosomes holding each family, with multiplicity
+    let mut homologs = HashMap::<FamilyID, Vec<usize>>::new();
+    for g in genes.iter() {
+        homologs
+            .entry(g.family)
+            .or_default()
+            .push(index[g.chr.as_str()]);
+    }
+
+    let mut painted = Vec::new();
+    let mut species = book.species();
+    species.sort();
+    for species in species.iter().filter(|s| *s != reference) {
+        for g in book.genes_of_species(species)? {
+            let mut votes = vec![0; chromosomes.len()];
+            for t in g.landscape() {
+                for &c in homologs.get(&t.family).into_iter().flatten() {
+                    votes[c] += 1;
+                }
+            }
+            let color = votes
+                .iter()
+                .enumerate()
+                .filter(|(_, n)| **n > 0)
+                .max_by(|(i, n), (j, m)| n.cmp(m).then(j.cmp(i)))
+                .map(|(c, _)| c);
+            painted.push(PaintedGene {
+                species: g.species,
+                id: g.id,
+                chr: g.chr,
+                pos: g.pos,
+                end: g.end,
+                color,
+            });
+        }
+    }
+
+    Ok(Painting {
+        chromosomes,
+        genes: painted,
+    })
+}
+
+/// The settings of [`conservation_matrix`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ConservationOptions {
+    /// How similar the neighborhoods of two homologs must be for a gene to
+    /// be deemed syntenic
+    pub min_score: f64,
+    /// How the neighborhoods are compared
+    pub metric: SyntenyMetric,
+}
+impl Default for ConservationOptions {
+    fn default() -> Self {
+        ConservationOptions {
+            min_score: 0.5,
+            metric: SyntenyMetric::default(),
+        }
+    }
+}
+
+/// For each pair of species, the fraction of the genes of the first one
+/// having a homolog in the second one with a similar neighborhood
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ConservationMatrix {
+    /// The species, in lexicographic order
+    pub species: Vec<String>,
+    /// The fraction of the genes of `species[i]` syntenic in `species[j]`
+    /// in `values[i][j]`, the diagonal being set to 1
+    pub values: Vec<Vec<f64>>,
+}
+impl ConservationMatrix {
+    /// Write the matrix as tab-separated values, with the species as header
+    /// line and as first column
+    pub fn write_tsv<W: std::io::Write>(&self, mut out: W) -> Result<()> {
+        writeln!(out, "\t{}", self.species.join("\t"))?;
+        for (species, values) in self.species.iter().zip(self.values.iter()) {
+            let values = values
+                .iter()
+                .map(|v| format!("{:.4}", v))
+                .collect::<Vec<_>>();
+            writeln!(out, "{}\t{}", species, values.join("\t"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Compute the syntenic conservation between all the pairs of species of
+/// `book`
+pub fn conservation_matrix(
+    book: &GeneBook,
+    options: &ConservationOptions,
+) -> Result<ConservationMatrix> {
+    let mut species = book.species();
+    species.sort();
+    let genomes = species
+        .iter()
+        .map(|s| book.genes_of_species(s))
+        .collect::<Result<Vec<_>>>()?;
+    let families = genomes
+        .iter()
+        .map(|genes| {
+            let mut r = HashMap::<FamilyID, Vec<&Gene>>::new();
+            for g in genes.iter() {
+                r.entry(g.family).or_default().push(g);
+            }
+            r
+        })
+        .collect::<Vec<_>>();
+
+    let mut values = vec![vec![1.; species.len()]; species.len()];
+    for (i, genes) in genomes.iter().enumerate() {
+        for (j, homologs) in families.iter().enumerate() {
+            if i == j || genes.is_empty() {
+                continue;
+            }
+            let syntenic = genes
+                .iter()
+                .filter(|g| {
+                    homologs
+                        .get(&g.family)
+                        .into_iter()
+                        .flatten()
+                        .any(|h| synteny_score_with(g, h, options.metric) >= options.min_score)
+                })
+                .count();
+            values[i][j] = syntenic as f64 / genes.len() as f64;
+        }
+    }
+
+    Ok(ConservationMatrix { species, values })
+}
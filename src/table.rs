@@ -0,0 +1,237 @@
+//! A reader for arbitrary delimited gene tables, whose layout is described
+//! by the user.
+use std::io::{BufRead, BufReader, Lines, Read};
+
+use thiserror::Error;
+
+use crate::{Coordinates, Strand};
+
+#[derive(Debug, Error)]
+pub enum TableError {
+    #[error("table entry with missing fields: {0}")]
+    RecordTooShort(String),
+
+    #[error("invalid coordinate in table entry: {0}")]
+    InvalidCoordinate(String),
+
+    #[error("unrecognized strand format: {0}")]
+    UnknownStrand(String),
+}
+
+/// The layout of a delimited gene table; columns are 0-indexed
+#[derive(Debug, Clone)]
+pub struct TableLayout {
+    pub delimiter: char,
+    /// Whether the first non-empty line that is not a `#` comment is a
+    /// header to skip
+    pub header: bool,
+    /// Whether the header is commented out, as in `#chr start end id`: the
+    /// first non-empty line is then the header, even if it starts with a
+    /// `#`; only used along with `header`
+    pub commented_header: bool,
+    pub chr: usize,
+    pub start: usize,
+    pub end: usize,
+    pub id: usize,
+    /// If unset, all genes are assumed to be on the direct strand
+    pub strand: Option<usize>,
+    pub coordinates: Coordinates,
+}
+impl Default for TableLayout {
+    fn default() -> Self {
+        TableLayout {
+            delimiter: '\t',
+            header: false,
+            commented_header: false,
+            chr: 0,
+            start: 1,
+            end: 2,
+            id: 3,
+            strand: None,
+            coordinates: Coordinates::OneBased,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct TableRecord {
+    chr: String,
+    start: usize,
+    end: usize,
+    id: String,
+    strand: Strand,
+    coordinates: Coordinates,
+}
+impl TableRecord {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    pub fn chr(&self) -> &str {
+        &self.chr
+    }
+
+    pub fn start(&self) -> usize {
+        self.start
+    }
+
+    pub fn end(&self) -> usize {
+        self.end
+    }
+
+    pub fn strand(&self) -> Strand {
+        self.strand
+    }
+
+    pub fn coordinates(&self) -> Coordinates {
+        self.coordinates
+    }
+}
+
+pub struct TableReader<T> {
+    buffer_lines: Lines<BufReader<T>>,
+    layout: TableLayout,
+    header_skipped: bool,
+}
+impl<T: Read> TableReader<T> {
+    pub fn new(file: T, layout: TableLayout) -> TableReader<T> {
+        TableReader {
+            buffer_lines: BufReader::new(file).lines(),
+            header_skipped: !layout.header,
+            layout,
+        }
+    }
+}
+impl<T: Read> Iterator for TableReader<T> {
+    type Item = Result<TableRecord, TableError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        fn make_record(line: &str, layout: &TableLayout) -> Result<TableRecord, TableError> {
+            let s = line
+                .split(layout.delimiter)
+                .map(|x| x.trim())
+                .collect::<Vec<_>>();
+            let field = |i: usize| {
+                s.get(i)
+                    .copied()
+                    .ok_or_else(|| TableError::RecordTooShort(line.to_owned()))
+            };
+            let number = |i: usize| {
+                field(i)?
+                    .parse::<usize>()
+                    .map_err(|_| TableError::InvalidCoordinate(line.to_owned()))
+            };
+
+            Ok(TableRecord {
+                chr: field(layout.chr)?.to_owned(),
+                start: number(layout.start)?,
+                end: number(layout.end)?,
+                id: field(layout.id)?.to_owned(),
+                strand: layout
+                    .strand
+                    .map(|i| {
+                        field(i)?
+                            .try_into()
+                            .map_err(|_| TableError::UnknownStrand(line.to_owned()))
+                    })
+                    .transpose()?
+                    .unwrap_or(Strand::Direct),
+                coordinates: layout.coordinates,
+            })
+        }
+
+        if !self.header_skipped {
+            self.header_skipped = true;
+            let commented = self.layout.commented_header;
+            self.buffer_lines
+                .by_ref()
+                .map(|l| l.unwrap())
+                .find(|line| (commented || !line.starts_with('#')) && !line.trim().is_empty())?;
+        }
+        let line = self
+            .buffer_lines
+            .by_ref()
+            .map(|l| l.unwrap())
+            .find(|line| !line.starts_with('#') && !line.trim().is_empty())?;
+        Some(make_record(&line, &self.layout))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ids(content: &str, layout: TableLayout) -> Vec<String> {
+        TableReader::new(content.as_bytes(), layout)
+            .map(|r| r.unwrap().id().to_owned())
+            .collect()
+    }
+
+    #[test]
+    fn records() {
+        let layout = TableLayout {
+            delimiter: ',',
+            id: 0,
+            chr: 1,
+            start: 2,
+            end: 3,
+            strand: Some(4),
+            coordinates: Coordinates::ZeroBased,
+            ..Default::default()
+        };
+        let records = TableReader::new("g1, chr2, 10, 20, -\n".as_bytes(), layout)
+            .collect::<Result<Vec<_>, _>>()
+            .unwrap();
+        let r = &records[0];
+        assert_eq!(
+            (r.id(), r.chr(), r.start(), r.end()),
+            ("g1", "chr2", 10, 20)
+        );
+        assert_eq!(r.strand(), Strand::Reverse);
+        assert_eq!(r.coordinates(), Coordinates::ZeroBased);
+    }
+
+    #[test]
+    fn headers() {
+        let content = "# exported from somewhere\n\
+                       \n\
+                       chr\tstart\tend\tid\n\
+                       chr1\t1\t10\tg1\n\
+                       # a comment\n\
+                       chr1\t20\t30\tg2\n";
+        let header = TableLayout {
+            header: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(content, header), ["g1", "g2"]);
+
+        let commented = "#chr\tstart\tend\tid\n\
+                         chr1\t1\t10\tg1\n";
+        let header = TableLayout {
+            header: true,
+            commented_header: true,
+            ..Default::default()
+        };
+        assert_eq!(ids(commented, header), ["g1"]);
+        assert_eq!(ids(commented, TableLayout::default()), ["g1"]);
+    }
+
+    #[test]
+    fn invalid_records() {
+        let layout = TableLayout {
+            strand: Some(4),
+            ..Default::default()
+        };
+        for (line, expected) in [
+            ("chr1\t1\t10", "RecordTooShort"),
+            ("chr1\tx\t10\tg1\t+", "InvalidCoordinate"),
+            ("chr1\t1\t10\tg1\t*", "UnknownStrand"),
+        ] {
+            let error = TableReader::new(line.as_bytes(), layout.clone())
+                .next()
+                .unwrap()
+                .unwrap_err();
+            assert!(format!("{:?}", error).starts_with(expected), "{}", line);
+        }
+    }
+}
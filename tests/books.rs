@@ -0,0 +1,368 @@
+//! Round-trips of gene books through the database: building, saving and
+//! updating species.
+#![cfg(feature = "sqlite")]
+mod common;
+
+use common::{Fixture, SPECIES_PATTERN, WINDOW};
+use syntesuite::dbmaker::{self, BuildOptions};
+use syntesuite::genebook::{BookOptions, GeneBook, IdNormalization};
+use syntesuite::Strand;
+
+/// A comparable description of all the genes of a book
+fn summary(book: &GeneBook) -> Vec<String> {
+    let mut genes = book
+        .all_genes()
+        .unwrap()
+        .into_iter()
+        .map(|g| {
+            let landscape = |l: &[syntesuite::genebook::TailGene]| {
+                l.iter()
+                    .map(|t| {
+                        format!(
+                            "{}{}*{}:{:?}:{:?}",
+                            t.strand, t.family, t.copies, t.pos, t.id
+                        )
+                    })
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            };
+            format!(
+                "{} {} {} {}-{} {} {} [{}] [{}]",
+                g.id,
+                g.species,
+                g.chr,
+                g.pos,
+                g.end,
+                g.strand,
+                g.family,
+                landscape(&g.left_landscape),
+                landscape(&g.right_landscape)
+            )
+        })
+        .collect::<Vec<_>>();
+    genes.sort();
+    genes
+}
+
+fn variants() -> Vec<BuildOptions> {
+    vec![
+        BuildOptions::default(),
+        BuildOptions {
+            collapse_tandems: true,
+            rich_landscapes: true,
+            ..Default::default()
+        },
+    ]
+}
+
+#[test]
+fn save_and_reload() {
+    let fixture = Fixture::new("save");
+    for options in variants() {
+        let db = fixture.build("save.sqlite", &["spA", "spB", "spC"], &options);
+        let mut book = GeneBook::in_memory(&db, WINDOW, "id").unwrap();
+        book.get_mut("a2").unwrap().family = 6;
+        let expected = summary(&book);
+
+        let saved = fixture.path("saved.sqlite");
+        std::fs::copy(&db, &saved).unwrap();
+        book.save(&saved).unwrap();
+        assert_eq!(
+            summary(&GeneBook::in_memory(&saved, WINDOW, "id").unwrap()),
+            expected
+        );
+        assert_eq!(
+            summary(&GeneBook::inline(&saved, WINDOW, "id").unwrap()),
+            expected
+        );
+    }
+}
+
+#[test]
+fn cached_books_are_not_saved() {
+    let fixture = Fixture::new("cached");
+    let db = fixture.build("cached.sqlite", &["spA", "spB"], &BuildOptions::default());
+    let book = GeneBook::cached(&db, WINDOW, "id", &["spA"]).unwrap();
+    assert!(book.save(&db).is_err());
+    assert_eq!(
+        GeneBook::inline(&db, WINDOW, "id").unwrap().species().len(),
+        2
+    );
+}
+
+#[test]
+fn add_species() {
+    let fixture = Fixture::new("add");
+    for options in variants() {
+        let expected = fixture.build("all.sqlite", &["spA", "spB", "spC"], &options);
+        let db = fixture.build("added.sqlite", &["spA", "spB"], &options);
+        dbmaker::add_species(
+            &db,
+            &fixture.path("spC.gff3"),
+            &fixture.families(),
+            SPECIES_PATTERN,
+            "gene",
+            "(?<id>.*)",
+            WINDOW as isize,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+            summary(&GeneBook::inline(&expected, WINDOW, "id").unwrap())
+        );
+    }
+}
+
+#[test]
+fn add_species_with_another_window() {
+    let fixture = Fixture::new("window");
+    let db = fixture.build("window.sqlite", &["spA", "spB"], &BuildOptions::default());
+    let before = summary(&GeneBook::inline(&db, WINDOW, "id").unwrap());
+    assert!(dbmaker::add_species(
+        &db,
+        &fixture.path("spC.gff3"),
+        &fixture.families(),
+        SPECIES_PATTERN,
+        "gene",
+        "(?<id>.*)",
+        WINDOW as isize + 1,
+        &BuildOptions::default(),
+    )
+    .is_err());
+    assert_eq!(
+        summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+        before
+    );
+}
+
+#[test]
+fn replace_species() {
+    let fixture = Fixture::new("replace");
+    for options in variants() {
+        let db = fixture.build("replaced.sqlite", &["spA", "spB", "spC"], &options);
+        // Drop the tandem copy and flip a gene
+        fixture.annotations(
+            "spC",
+            &[
+                ("c1", '-'),
+                ("c2", '+'),
+                ("c3", '+'),
+                ("c4", '+'),
+                ("c5", '+'),
+                ("c6", '-'),
+            ],
+        );
+        let expected = fixture.build("expected.sqlite", &["spA", "spB", "spC"], &options);
+        dbmaker::replace_species(
+            &db,
+            &fixture.path("spC.gff3"),
+            &fixture.families(),
+            SPECIES_PATTERN,
+            "gene",
+            "(?<id>.*)",
+            WINDOW as isize,
+            &options,
+        )
+        .unwrap();
+        assert_eq!(
+            summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+            summary(&GeneBook::inline(&expected, WINDOW, "id").unwrap())
+        );
+        // Restore the original annotations for the next variant
+        fixture.annotations(
+            "spC",
+            &[
+                ("c1", '-'),
+                ("c2", '+'),
+                ("c3", '+'),
+                ("c3b", '-'),
+                ("c4", '.'),
+                ("c5", '+'),
+                ("c6", '-'),
+            ],
+        );
+    }
+}
+
+#[test]
+fn remove_and_add_species() {
+    let fixture = Fixture::new("remove");
+    let options = BuildOptions::default();
+    let db = fixture.build("removed.sqlite", &["spA", "spB", "spC"], &options);
+    let before = summary(&GeneBook::inline(&db, WINDOW, "id").unwrap());
+    dbmaker::remove_species(&db, "spC").unwrap();
+    assert_eq!(
+        GeneBook::inline(&db, WINDOW, "id").unwrap().species().len(),
+        2
+    );
+    dbmaker::add_species(
+        &db,
+        &fixture.path("spC.gff3"),
+        &fixture.families(),
+        SPECIES_PATTERN,
+        "gene",
+        "(?<id>.*)",
+        WINDOW as isize,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+        before
+    );
+}
+
+#[test]
+fn unknown_strands() {
+    let fixture = Fixture::new("strands");
+    for options in variants() {
+        let db = fixture.build("strands.sqlite", &["spA", "spB", "spC"], &options);
+        let in_memory = GeneBook::in_memory(&db, WINDOW, "id").unwrap();
+        let inline = GeneBook::inline(&db, WINDOW, "id").unwrap();
+        for book in [&in_memory, &inline] {
+            assert_eq!(book.get("c4").unwrap().strand, Strand::Unknown);
+            assert_eq!(book.get("c3").unwrap().strand, Strand::Direct);
+            assert_eq!(book.get("c6").unwrap().strand, Strand::Reverse);
+        }
+        let c5 = in_memory.get("c5").unwrap();
+        assert_eq!(c5.left_landscape.last().unwrap().strand, Strand::Unknown);
+    }
+}
+
+#[test]
+fn per_species_assemblies() {
+    let fixture = Fixture::new("agp");
+    // Place the first 420 bases of the chr1 of spA reversed on chrX, so that
+    // a1 (100-150) fits, a4 (400-450) straddles the placement and a5
+    // (500-550) lies outside of it
+    let agp = fixture.path("spA.agp");
+    std::fs::write(&agp, "chrX\t1\t420\t1\tW\tchr1\t1\t420\t-\n").unwrap();
+    let options = BuildOptions {
+        agps: [("spA".to_owned(), vec![agp])].into_iter().collect(),
+        ..Default::default()
+    };
+    let db = fixture.build("agp.sqlite", &["spA", "spB"], &options);
+    let book = GeneBook::in_memory(&db, WINDOW, "id").unwrap();
+
+    let a1 = book.get("a1").unwrap();
+    assert_eq!((a1.chr.as_str(), a1.pos, a1.end), ("chrX", 271, 321));
+    assert_eq!(a1.strand, Strand::Reverse);
+    assert!(book.get("a4").is_err());
+    assert!(book.get("a5").is_err());
+
+    let b1 = book.get("b1").unwrap();
+    assert_eq!((b1.chr.as_str(), b1.pos, b1.end), ("chr1", 100, 150));
+}
+
+#[test]
+fn fresh_families_after_removal() {
+    let fixture = Fixture::new("fresh");
+    let options = BuildOptions::default();
+    let families = fixture.path("families");
+    std::fs::write(format!("{families}/f7.txt"), "c7").unwrap();
+    fixture.annotations("spC", &[("c1", '-'), ("c2", '+'), ("c7", '+')]);
+    let db = fixture.build("fresh.sqlite", &["spA", "spB", "spC"], &options);
+    let f7 = GeneBook::inline(&db, WINDOW, "id")
+        .unwrap()
+        .get("c7")
+        .unwrap()
+        .family;
+
+    // The family of c7 is left without genes, but keeps its ID
+    dbmaker::remove_species(&db, "spC").unwrap();
+    std::fs::remove_file(format!("{families}/f7.txt")).unwrap();
+    std::fs::write(format!("{families}/g7.txt"), "c7").unwrap();
+    dbmaker::add_species(
+        &db,
+        &fixture.path("spC.gff3"),
+        &fixture.families(),
+        SPECIES_PATTERN,
+        "gene",
+        "(?<id>.*)",
+        WINDOW as isize,
+        &options,
+    )
+    .unwrap();
+    let c7 = GeneBook::inline(&db, WINDOW, "id")
+        .unwrap()
+        .get("c7")
+        .unwrap();
+    assert!(c7.family > f7);
+    assert_eq!(c7.family_name.as_deref(), Some("g7"));
+}
+
+#[test]
+fn remove_missing_species() {
+    let fixture = Fixture::new("missing");
+    let db = fixture.build("missing.sqlite", &["spA", "spB"], &BuildOptions::default());
+    let before = summary(&GeneBook::inline(&db, WINDOW, "id").unwrap());
+    assert!(dbmaker::remove_species(&db, "spC").is_err());
+    assert_eq!(
+        summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+        before
+    );
+}
+
+#[test]
+fn update_saved_books() {
+    let fixture = Fixture::new("update-saved");
+    let options = BuildOptions::default();
+    let db = fixture.build("built.sqlite", &["spA", "spB", "spC"], &options);
+    let book = GeneBook::in_memory(&db, WINDOW, "id").unwrap();
+    book.save(&db).unwrap();
+    let conn = rusqlite::Connection::open(&db).unwrap();
+    let pattern: String = conn
+        .query_row(
+            "SELECT value FROM meta WHERE key='species_pattern'",
+            [],
+            |r| r.get(0),
+        )
+        .unwrap();
+    assert_eq!(pattern, SPECIES_PATTERN);
+    drop(conn);
+
+    dbmaker::remove_species(&db, "spC").unwrap();
+    dbmaker::add_species(
+        &db,
+        &fixture.path("spC.gff3"),
+        &fixture.families(),
+        SPECIES_PATTERN,
+        "gene",
+        "(?<id>.*)",
+        WINDOW as isize,
+        &options,
+    )
+    .unwrap();
+    assert_eq!(
+        summary(&GeneBook::inline(&db, WINDOW, "id").unwrap()),
+        summary(&book)
+    );
+}
+
+#[test]
+fn normalization_collisions() {
+    let fixture = Fixture::new("collisions");
+    std::fs::write(fixture.path("families/f7.txt"), "x7.2 X7.1 x7.3").unwrap();
+    fixture.annotations(
+        "spA",
+        &[("a1", '+'), ("x7.2", '+'), ("X7.1", '-'), ("x7.3", '+')],
+    );
+    let db = fixture.build("collisions.sqlite", &["spA"], &BuildOptions::default());
+    let options = BookOptions {
+        normalization: IdNormalization {
+            lowercase: true,
+            strip_version: true,
+            strip: None,
+        },
+        ..Default::default()
+    };
+    for book in [
+        GeneBook::in_memory_with(&db, WINDOW, "id", &options).unwrap(),
+        GeneBook::cached_with(&db, WINDOW, "id", &["X7", "a1"], &options).unwrap(),
+        GeneBook::inline_with(&db, WINDOW, "id", &options).unwrap(),
+    ] {
+        assert_eq!(book.get("x7").unwrap().id, "X7.1");
+        assert_eq!(book.get("X7.3").unwrap().id, "X7.1");
+    }
+}
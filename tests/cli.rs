@@ -0,0 +1,180 @@
+//! The `syntesuite` command-line tool.
+#![cfg(feature = "cli")]
+mod common;
+
+use std::process::{Command, Output};
+
+use common::{Fixture, SPECIES_PATTERN, WINDOW};
+
+fn syntesuite(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_syntesuite"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+/// The standard output of a successful run
+fn stdout(args: &[&str]) -> String {
+    let output = syntesuite(args);
+    assert!(
+        output.status.success(),
+        "{}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
+
+/// Build a database of `spA` and `spB` from the families in `families`
+fn build(fixture: &Fixture, families: &str, extra: &[&str]) -> (String, Output) {
+    let db = fixture.path("cli.sqlite");
+    let window = WINDOW.to_string();
+    let spa = fixture.path("spA.gff3");
+    let spb = fixture.path("spB.gff3");
+    let mut args = vec![
+        "build",
+        "-o",
+        &db,
+        "-f",
+        families,
+        "-a",
+        &spa,
+        &spb,
+        "--species-pattern",
+        SPECIES_PATTERN,
+        "-w",
+        &window,
+    ];
+    args.extend(extra);
+    let output = syntesuite(&args);
+    (db, output)
+}
+
+#[test]
+fn build_and_get() {
+    let fixture = Fixture::new("cli-build");
+    let (db, output) = build(&fixture, &fixture.path("families"), &[]);
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8_lossy(&output.stdout), "spA\t6\nspB\t6\n");
+
+    let get = stdout(&["get", &db, "a3"]);
+    let lines = get.lines().collect::<Vec<_>>();
+    assert!(lines[0].contains("a3") && lines[0].contains("spA chr1:300-350 -"));
+    assert_eq!(lines.len(), 2);
+    assert_eq!(stdout(&["species", &db]), "spA\t6\nspB\t6\n");
+    assert!(!syntesuite(&["get", &db, "z1"]).status.success());
+}
+
+#[test]
+fn logging() {
+    let fixture = Fixture::new("cli-log");
+    let (_, quiet) = build(&fixture, &fixture.path("families"), &[]);
+    assert!(quiet.stderr.is_empty());
+    let (_, verbose) = build(&fixture, &fixture.path("families"), &["-v"]);
+    let stderr = String::from_utf8_lossy(&verbose.stderr);
+    assert!(stderr.contains("INFO") && stderr.contains("spA.gff3"));
+}
+
+#[test]
+fn family_formats() {
+    let fixture = Fixture::new("cli-families");
+    let emapper = fixture.path("spAB.emapper.annotations");
+    let mut annotations = String::from("#query\tseed_ortholog\teggNOG_OGs\n");
+    for k in 1..=6 {
+        for sp in ["a", "b"] {
+            annotations.push_str(&format!("{sp}{k}\tx\tOG{k}@2759|Eukaryota\n"));
+        }
+    }
+    std::fs::write(&emapper, annotations).unwrap();
+    for format in ["eggnog:2759", "eggnog:Eukaryota"] {
+        let (db, output) = build(&fixture, &emapper, &["--family-format", format]);
+        assert!(output.status.success(), "{}", format);
+        assert!(stdout(&["get", &db, "b4"]).contains("family OG4"));
+    }
+    let (_, output) = build(&fixture, &emapper, &["--family-format", "eggnog"]);
+    assert!(!output.status.success());
+
+    let trees = fixture.path("trees");
+    std::fs::create_dir_all(&trees).unwrap();
+    for k in 1..=6 {
+        std::fs::write(format!("{trees}/t{k}.nwk"), format!("(a{k},b{k});")).unwrap();
+    }
+    let (db, output) = build(&fixture, &trees, &["--family-format", "trees"]);
+    assert!(output.status.success());
+    assert_eq!(stdout(&["species", &db]), "spA\t6\nspB\t6\n");
+
+    let (_, output) = build(&fixture, &trees, &["--family-format", "nope"]);
+    assert!(!output.status.success());
+}
+
+#[test]
+fn query() {
+    let fixture = Fixture::new("cli-query");
+    let (db, _) = build(&fixture, &fixture.path("families"), &[]);
+
+    // Genes are kept if they overlap the range, bounds included
+    assert_eq!(
+        stdout(&["query", &db, "-s", "spA", "-r", "250-450"]),
+        "species\tchr\tid\tfamily\tfamily_name\tstart\tstop\tstrand\n\
+         spA\tchr1\ta2\t2\tf2\t200\t250\t+\n\
+         spA\tchr1\ta3\t3\tf3\t300\t350\t-\n\
+         spA\tchr1\ta4\t4\tf4\t400\t450\t+\n"
+    );
+    assert_eq!(
+        stdout(&["query", &db, "-c", "chr1", "-r", "1k-2k"])
+            .lines()
+            .count(),
+        1
+    );
+
+    let genes = stdout(&["query", &db, "-f", "3", "--format", "json"])
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(genes.len(), 2);
+    assert_eq!(genes[1]["id"], "b3");
+    assert_eq!(genes[1]["family_name"], "f3");
+    assert_eq!(
+        genes[1]["left_landscape"],
+        serde_json::json!([{"family": 1, "strand": "+"}, {"family": 2, "strand": "-"}])
+    );
+
+    assert!(!syntesuite(&["query", &db, "--format", "xml"])
+        .status
+        .success());
+    assert!(!syntesuite(&["query", &db, "-r", "2k-1x"]).status.success());
+}
+
+#[test]
+fn stats() {
+    let fixture = Fixture::new("cli-stats");
+    let (db, _) = build(&fixture, &fixture.path("families"), &[]);
+
+    assert_eq!(
+        stdout(&["stats", &db]),
+        "species\tgenes\tchromosomes\tfamilies\n\
+         spA\t6\t1\t6\n\
+         spB\t6\t1\t6\n\
+         \n\
+         genes\t12\n\
+         chromosomes\t2\n\
+         families\t6\n\
+         mean landscape\t3.00\n\
+         median landscape\t3.00\n\
+         \n\
+         family size\tfamilies\n\
+         2\t6\n"
+    );
+
+    let stats =
+        serde_json::from_str::<serde_json::Value>(&stdout(&["stats", &db, "--json"])).unwrap();
+    assert_eq!(stats["genes"], 12);
+    assert_eq!(stats["mean_landscape"], 3.);
+    assert_eq!(
+        stats["species"][1],
+        serde_json::json!({"name": "spB", "genes": 6, "chromosomes": 1, "families": 6})
+    );
+    assert_eq!(
+        stats["family_sizes"],
+        serde_json::json!([{"size": 2, "families": 6}])
+    );
+}
@@ -0,0 +1,124 @@
+//! A scratch fixture shared by the integration tests.
+#![allow(dead_code)]
+use std::path::PathBuf;
+
+use syntesuite::dbmaker::{self, BuildOptions};
+
+pub const WINDOW: usize = 2;
+pub const SPECIES_PATTERN: &str = "(?<species>sp.)";
+
+/// A scratch directory holding the families and annotations of three
+/// species, `spC` having mixed strands and a tandem array
+pub struct Fixture {
+    dir: PathBuf,
+}
+impl Fixture {
+    pub fn new(name: &str) -> Self {
+        let dir = std::env::temp_dir().join(format!("syntesuite-{}-{}", std::process::id(), name));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("families")).unwrap();
+        for k in 1..=6 {
+            let mut members = format!("a{k} b{k} c{k}");
+            if k == 3 {
+                members.push_str(" c3b");
+            }
+            std::fs::write(dir.join("families").join(format!("f{k}.txt")), members).unwrap();
+        }
+        let fixture = Fixture { dir };
+        fixture.annotations(
+            "spA",
+            &[
+                ("a1", '+'),
+                ("a2", '+'),
+                ("a3", '-'),
+                ("a4", '+'),
+                ("a5", '+'),
+                ("a6", '-'),
+            ],
+        );
+        fixture.annotations(
+            "spB",
+            &[
+                ("b1", '+'),
+                ("b2", '-'),
+                ("b3", '+'),
+                ("b4", '+'),
+                ("b5", '-'),
+                ("b6", '+'),
+            ],
+        );
+        fixture.annotations(
+            "spC",
+            &[
+                ("c1", '-'),
+                ("c2", '+'),
+                ("c3", '+'),
+                ("c3b", '-'),
+                ("c4", '.'),
+                ("c5", '+'),
+                ("c6", '-'),
+            ],
+        );
+        fixture
+    }
+
+    /// Write the annotations of `species`, one gene every 100 bases
+    pub fn annotations(&self, species: &str, genes: &[(&str, char)]) -> String {
+        self.chromosomes(species, &[("chr1", genes)])
+    }
+
+    /// Write the annotations of `species` over several chromosomes, one gene
+    /// every 100 bases
+    pub fn chromosomes(&self, species: &str, chromosomes: &[(&str, &[(&str, char)])]) -> String {
+        let mut gff = String::from("##gff-version 3\n");
+        for (chr, genes) in chromosomes.iter() {
+            for (i, (id, strand)) in genes.iter().enumerate() {
+                let start = 100 * (i + 1);
+                gff.push_str(&format!(
+                    "{}\tx\tgene\t{}\t{}\t.\t{}\t.\tID={}\n",
+                    chr,
+                    start,
+                    start + 50,
+                    strand,
+                    id
+                ));
+            }
+        }
+        let path = self.path(&format!("{species}.gff3"));
+        std::fs::write(&path, gff).unwrap();
+        path
+    }
+
+    pub fn path(&self, name: &str) -> String {
+        self.dir.join(name).to_str().unwrap().to_owned()
+    }
+
+    pub fn families(&self) -> Vec<String> {
+        vec![self.path("families")]
+    }
+
+    pub fn build(&self, db: &str, species: &[&str], options: &BuildOptions) -> String {
+        let db = self.path(db);
+        let annotations = species
+            .iter()
+            .map(|s| self.path(&format!("{s}.gff3")))
+            .collect::<Vec<_>>();
+        dbmaker::db_from_files(
+            &self.families(),
+            &annotations,
+            &db,
+            SPECIES_PATTERN,
+            "gene",
+            "(?<id>.*)",
+            WINDOW as isize,
+            options,
+        )
+        .unwrap();
+        db
+    }
+}
+impl Drop for Fixture {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
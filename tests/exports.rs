@@ -0,0 +1,293 @@
+//! The files written for third-party synteny tools.
+#![cfg(feature = "sqlite")]
+mod common;
+
+use common::{Fixture, WINDOW};
+use syntesuite::dbmaker::{self, BuildOptions, DumpFormat};
+use syntesuite::export::{self, grimm::PermutationFormat};
+use syntesuite::genebook::{GeneBook, NeighborhoodFormat};
+use syntesuite::synteny::{BlockOptions, ChainOptions};
+
+fn written<F: FnOnce(&mut Vec<u8>)>(f: F) -> String {
+    let mut out = Vec::new();
+    f(&mut out);
+    String::from_utf8(out).unwrap()
+}
+
+#[test]
+fn mcscanx_collinearity() {
+    let fixture = Fixture::new("mcscanx");
+    let db = fixture.build(
+        "mcscanx.sqlite",
+        &["spA", "spB", "spC"],
+        &BuildOptions::default(),
+    );
+    let options = ChainOptions {
+        min_anchors: 3,
+        ..Default::default()
+    };
+    let collinearity =
+        written(|out| export::mcscanx::write_collinearity(&db, &options, out).unwrap());
+    let lines = collinearity.lines().collect::<Vec<_>>();
+    assert!(lines.contains(&"# MATCH_SIZE: 3"));
+    assert!(lines.contains(&"# Number of collinear genes: 18, Percentage: 94.74"));
+    let alignments = lines
+        .iter()
+        .copied()
+        .filter(|l| l.starts_with("## Alignment"))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        alignments,
+        [
+            "## Alignment 0: score=300.0 e_value=0 N=6 aachr1&abchr1 plus",
+            // Skipping c3 costs a gap
+            "## Alignment 1: score=299.0 e_value=0 N=6 aachr1&acchr1 plus",
+            "## Alignment 2: score=299.0 e_value=0 N=6 abchr1&acchr1 plus",
+        ]
+    );
+    assert!(lines.contains(&"  0-  2:\ta3\tb3\t      0"));
+    assert!(lines.contains(&"  1-  2:\ta3\tc3b\t      0"));
+}
+
+#[test]
+fn cached_neighborhoods() {
+    let fixture = Fixture::new("neighborhoods");
+    let db = fixture.build("neighborhoods.sqlite", &["spA"], &BuildOptions::default());
+    let all = ["a1", "a2", "a3", "a4", "a5", "a6"];
+    let book = GeneBook::cached(&db, WINDOW, "id", &all).unwrap();
+    let bed = written(|out| {
+        book.export_neighborhood("a3", NeighborhoodFormat::Bed, out)
+            .unwrap()
+    });
+    let ids = bed
+        .lines()
+        .map(|l| l.split('\t').nth(3).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(ids, ["a1|f1", "a2|f2", "a3|f3", "a4|f4", "a5|f5"]);
+
+    // Without a4, a5 and a6 would be taken as the right neighbors of a3
+    let partial = GeneBook::cached(&db, WINDOW, "id", &["a1", "a2", "a3", "a5", "a6"]).unwrap();
+    assert!(partial
+        .export_neighborhood("a3", NeighborhoodFormat::Bed, std::io::sink())
+        .is_err());
+}
+
+#[test]
+fn dumps() {
+    let fixture = Fixture::new("dumps");
+    let options = BuildOptions {
+        collapse_tandems: true,
+        ..Default::default()
+    };
+    let db = fixture.build("dumps.sqlite", &["spA", "spC"], &options);
+
+    let tsv = written(|out| dbmaker::export(&db, DumpFormat::Tsv, out).unwrap());
+    let lines = tsv.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 1 + 6 + 7);
+    assert!(lines[0].starts_with("species\tchr\tid\tfamily\tstart\tstop\tstrand\tleft_families"));
+    assert!(lines.contains(&"spC\tchr1\tc4\t4\t500\t550\t.\t2,3\t+.\t5,6\t+-"));
+
+    let jsonl = written(|out| dbmaker::export(&db, DumpFormat::JsonLines, out).unwrap());
+    let genes = jsonl
+        .lines()
+        .map(|l| serde_json::from_str::<serde_json::Value>(l).unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(genes.len(), 13);
+    let c2 = genes.iter().find(|g| g["id"] == "c2").unwrap();
+    assert_eq!(c2["species"], "spC");
+    assert_eq!(
+        c2["right_landscape"],
+        serde_json::json!([{"family": 3, "strand": ".", "copies": 2}, {"family": 4, "strand": "."}])
+    );
+}
+
+#[test]
+fn iadhore_inputs() {
+    let fixture = Fixture::new("iadhore");
+    // Both chromosomes of spA end up with the same safe name
+    fixture.chromosomes(
+        "spA",
+        &[
+            ("chr/1", &[("a1", '+'), ("a2", '+'), ("a3", '-')]),
+            ("chr_1", &[("a4", '+'), ("a5", '+'), ("a6", '-')]),
+        ],
+    );
+    let db = fixture.build("iadhore.sqlite", &["spA", "spC"], &BuildOptions::default());
+    let out = std::path::PathBuf::from(fixture.path("iadhore"));
+    std::fs::create_dir_all(&out).unwrap();
+    export::iadhore::export(&db, &out).unwrap();
+    let read = |path: &str| std::fs::read_to_string(out.join(path)).unwrap();
+
+    assert_eq!(
+        read("lists/spC/chr1.lst"),
+        "c1-\nc2+\nc3+\nc3b-\nc4+\nc5+\nc6-\n"
+    );
+    assert_eq!(read("lists/spA/chr_1_1.lst"), "a4+\na5+\na6-\n");
+    let families = read("families.tsv");
+    assert_eq!(families.lines().count(), 13);
+    assert!(families.lines().any(|l| l == "c3b\t3"));
+    let settings = read("settings.ini");
+    assert!(settings.starts_with(
+        "genome=spA\n\
+         chr_1 lists/spA/chr_1.lst\n\
+         chr_1_1 lists/spA/chr_1_1.lst\n\
+         \n\
+         genome=spC\n\
+         chr1 lists/spC/chr1.lst\n"
+    ));
+    assert!(settings.contains("blast_table=families.tsv\n"));
+}
+
+#[test]
+fn dagchainer_matches() {
+    let fixture = Fixture::new("dagchainer");
+    let db = fixture.build(
+        "dagchainer.sqlite",
+        &["spA", "spB", "spC"],
+        &BuildOptions::default(),
+    );
+
+    let matches = written(|out| export::dagchainer::export(&db, "spA", "spC", out).unwrap());
+    let lines = matches.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "chr1\ta1\t100\t150\tchr1\tc1\t100\t150\t1e-50");
+    assert!(lines.contains(&"chr1\ta3\t300\t350\tchr1\tc3b\t400\t450\t1e-50"));
+    assert!(lines.iter().all(|l| !l.contains("\tb")));
+
+    // Genes are never paired with themselves
+    let same = written(|out| export::dagchainer::export(&db, "spA", "spA", out).unwrap());
+    assert_eq!(same.lines().count(), 0);
+}
+
+#[test]
+fn jcvi_files() {
+    let fixture = Fixture::new("jcvi");
+    let db = fixture.build("jcvi.sqlite", &["spA", "spC"], &BuildOptions::default());
+
+    let bed = written(|out| export::jcvi::write_bed(&db, "spC", out).unwrap());
+    let lines = bed.lines().collect::<Vec<_>>();
+    assert_eq!(lines.len(), 7);
+    assert_eq!(lines[0], "chr1\t99\t150\tc1\t0\t-");
+    // Unknown strands are written as direct ones
+    assert_eq!(lines[4], "chr1\t499\t550\tc4\t0\t+");
+
+    let options = ChainOptions {
+        max_gap: 0,
+        min_anchors: 3,
+        ..Default::default()
+    };
+    let anchors =
+        written(|out| export::jcvi::write_anchors(&db, "spA", "spC", &options, out).unwrap());
+    assert_eq!(
+        anchors,
+        "###\n\
+         a1\tc1\t100\na2\tc2\t100\na3\tc3\t100\n\
+         ###\n\
+         a3\tc3b\t100\na4\tc4\t100\na5\tc5\t100\na6\tc6\t100\n"
+    );
+}
+
+#[test]
+fn circos_files() {
+    let fixture = Fixture::new("circos");
+    let db = fixture.build("circos.sqlite", &["spA", "spC"], &BuildOptions::default());
+    let export = |blocks| {
+        let (mut karyotype, mut links) = (Vec::new(), Vec::new());
+        export::circos::export(&db, &["spC", "spA"], blocks, &mut karyotype, &mut links).unwrap();
+        (
+            String::from_utf8(karyotype).unwrap(),
+            String::from_utf8(links).unwrap(),
+        )
+    };
+
+    // Species are colored in the order given
+    let (karyotype, links) = export(None);
+    assert_eq!(
+        karyotype,
+        "chr - spC_chr1 chr1 100 750 chr1\nchr - spA_chr1 chr1 100 650 chr2\n"
+    );
+    let links = links.lines().collect::<Vec<_>>();
+    assert_eq!(links.len(), 7);
+    assert!(links.contains(&"spA_chr1 300 350 spC_chr1 400 450"));
+
+    let (_, links) = export(Some(BlockOptions {
+        max_gap: 0,
+        min_anchors: 3,
+    }));
+    assert_eq!(
+        links,
+        "spC_chr1 100 350 spA_chr1 100 350\nspC_chr1 400 750 spA_chr1 300 650\n"
+    );
+}
+
+#[test]
+fn permutations() {
+    let fixture = Fixture::new("permutations");
+    let db = fixture.build(
+        "permutations.sqlite",
+        &["spA", "spC"],
+        &BuildOptions::default(),
+    );
+    let export =
+        |format| written(|out| export::grimm::export(&db, &["spA", "spC"], format, out).unwrap());
+
+    // f3, duplicated in spC, is left out
+    assert_eq!(
+        export(PermutationFormat::Grimm),
+        ">spA\n# chr1\n1 2 3 4 -5 $\n>spC\n# chr1\n-1 2 3 4 -5 $\n"
+    );
+    assert_eq!(
+        export(PermutationFormat::UniMoG),
+        ">spA\n1 2 3 4 -5 |\n>spC\n-1 2 3 4 -5 |\n"
+    );
+}
+
+#[test]
+fn ribbons() {
+    let fixture = Fixture::new("ribbons");
+    fixture.chromosomes(
+        "spA",
+        &[
+            ("chr10", &[("a4", '+'), ("a5", '+'), ("a6", '-')]),
+            ("chr2", &[("a1", '+'), ("a2", '+'), ("a3", '-')]),
+        ],
+    );
+    let db = fixture.build(
+        "ribbons.sqlite",
+        &["spA", "spB", "spC"],
+        &BuildOptions::default(),
+    );
+    let options = BlockOptions {
+        max_gap: 0,
+        min_anchors: 3,
+    };
+    let json =
+        written(|out| export::ribbons::export(&db, &["spA", "spB", "spC"], &options, out).unwrap());
+    let plot = serde_json::from_str::<serde_json::Value>(&json).unwrap();
+
+    // Chromosomes are sorted naturally
+    assert_eq!(
+        plot["genomes"][0],
+        serde_json::json!({"species": "spA", "chromosomes": [
+            {"name": "chr2", "start": 100, "end": 350},
+            {"name": "chr10", "start": 100, "end": 350},
+        ]})
+    );
+    let ribbons = plot["ribbons"].as_array().unwrap();
+    assert_eq!(ribbons.len(), 4);
+    assert_eq!(
+        ribbons[0],
+        serde_json::json!({
+            "source": {"species": "spA", "chr": "chr10", "start": 100, "end": 350},
+            "target": {"species": "spB", "chr": "chr1", "start": 400, "end": 650},
+            "orientation": "+",
+            "anchors": 3,
+        })
+    );
+    // Ribbons only link consecutive genomes
+    assert!(ribbons
+        .iter()
+        .all(|r| r["source"]["species"] != "spA" || r["target"]["species"] == "spB"));
+    assert_eq!(ribbons[3]["source"]["species"], "spB");
+    assert_eq!(ribbons[3]["target"]["start"], 400);
+}
@@ -0,0 +1,480 @@
+//! Comparisons of neighborhoods and genomes on small, hand-checked books.
+#![cfg(feature = "sqlite")]
+mod common;
+
+use common::{Fixture, WINDOW};
+use syntesuite::analysis::{self, DcjOptions, Duplicates};
+use syntesuite::dbmaker::BuildOptions;
+use syntesuite::genebook::{
+    synteny_score, synteny_score_with, FamilyID, GeneBook, SyntenyMetric, TailGene,
+};
+use syntesuite::synteny::{
+    self, AlignmentMode, AlignmentScoring, BlockOptions, ChainOptions, ClusterOptions,
+    ConservationOptions, OrthologyOptions, SubFamily,
+};
+use syntesuite::Strand;
+
+/// The in-memory book of the given species of `fixture`
+fn book(fixture: &Fixture, species: &[&str]) -> GeneBook {
+    let db = fixture.build("synteny.sqlite", species, &BuildOptions::default());
+    GeneBook::in_memory(&db, WINDOW, "id").unwrap()
+}
+
+/// A landscape of the given families, all on the direct strand
+fn landscape(families: &[FamilyID]) -> Vec<TailGene> {
+    families
+        .iter()
+        .map(|&family| TailGene {
+            family,
+            strand: Strand::Direct,
+            ..Default::default()
+        })
+        .collect()
+}
+
+#[test]
+fn synteny_scores() {
+    let fixture = Fixture::new("scores");
+    // spB shuffles the neighbors of b3 without changing their families
+    fixture.annotations(
+        "spB",
+        &[
+            ("b2", '+'),
+            ("b1", '+'),
+            ("b3", '+'),
+            ("b5", '+'),
+            ("b4", '+'),
+            ("b6", '+'),
+        ],
+    );
+    let book = book(&fixture, &["spA", "spB"]);
+    let get = |id: &str| book.get(id).unwrap();
+
+    let (a3, b3) = (get("a3"), get("b3"));
+    assert_eq!(synteny_score(&a3, &b3), 1.);
+    assert_eq!(synteny_score_with(&a3, &b3, SyntenyMetric::Jaccard), 1.);
+    assert_eq!(synteny_score_with(&a3, &b3, SyntenyMetric::Ordered), 0.6);
+
+    // a1 lies at the end of its chromosome, and b5 is a neighbor of b1
+    let (a1, b1) = (get("a1"), get("b1"));
+    assert_eq!(synteny_score(&a1, &b1), 1.);
+    assert_eq!(synteny_score_with(&a1, &b1, SyntenyMetric::Jaccard), 0.75);
+    assert_eq!(
+        synteny_score_with(&a1, &b1, SyntenyMetric::Ordered),
+        2. / 3.
+    );
+}
+
+#[test]
+fn alignments() {
+    let scoring = AlignmentScoring::default();
+
+    let (a, b) = (landscape(&[1, 2, 3]), landscape(&[1, 3]));
+    let global = synteny::align(&a, &b, AlignmentMode::Global, &scoring);
+    assert_eq!(global.score, 1.);
+    assert_eq!(
+        global.pairs,
+        [(Some(0), Some(0)), (Some(1), None), (Some(2), Some(1))]
+    );
+
+    let (a, b) = (landscape(&[5, 1, 2, 6]), landscape(&[7, 1, 2, 8]));
+    let global = synteny::align(&a, &b, AlignmentMode::Global, &scoring);
+    assert_eq!(global.score, 0.);
+    assert_eq!(global.pairs.len(), 4);
+    assert_eq!(global.matches(&a, &b).collect::<Vec<_>>(), [(1, 1), (2, 2)]);
+    let local = synteny::align(&a, &b, AlignmentMode::Local, &scoring);
+    assert_eq!(local.score, 2.);
+    assert_eq!(local.pairs, [(Some(1), Some(1)), (Some(2), Some(2))]);
+
+    // Only genes on the same known strand earn the bonus
+    let scoring = AlignmentScoring {
+        strand_bonus: 0.5,
+        ..Default::default()
+    };
+    let a = landscape(&[1, 2, 3]);
+    let mut b = landscape(&[1, 2, 3]);
+    b[1].strand = Strand::Reverse;
+    b[2].strand = Strand::Unknown;
+    let aligned = synteny::align(&a, &b, AlignmentMode::Global, &scoring);
+    assert_eq!(aligned.score, 3.5);
+}
+
+/// The fixture, the genes `b3` to `b5` of `spB` being inverted
+fn inverted(name: &str) -> Fixture {
+    let fixture = Fixture::new(name);
+    fixture.annotations(
+        "spB",
+        &[
+            ("b1", '+'),
+            ("b2", '-'),
+            ("b5", '+'),
+            ("b4", '-'),
+            ("b3", '-'),
+            ("b6", '+'),
+        ],
+    );
+    fixture
+}
+
+/// The anchors of `blocks`, as `a1:b1` pairs, with their orientation
+fn anchors(blocks: &[synteny::SyntenicBlock]) -> Vec<String> {
+    blocks
+        .iter()
+        .map(|b| {
+            let anchors = b
+                .anchors
+                .iter()
+                .map(|(a, b)| format!("{a}:{b}"))
+                .collect::<Vec<_>>();
+            format!("{} {}", b.orientation, anchors.join(" "))
+        })
+        .collect()
+}
+
+#[test]
+fn syntenic_blocks() {
+    let fixture = inverted("blocks");
+    let book = book(&fixture, &["spA", "spB"]);
+
+    let options = BlockOptions {
+        max_gap: 0,
+        min_anchors: 2,
+    };
+    let blocks = synteny::syntenic_blocks(&book, "spA", "spB", &options).unwrap();
+    assert_eq!(anchors(&blocks), ["+ a1:b1 a2:b2", "- a3:b3 a4:b4 a5:b5"]);
+    let inversion = &blocks[1];
+    assert_eq!(
+        (
+            inversion.start_a,
+            inversion.end_a,
+            inversion.start_b,
+            inversion.end_b
+        ),
+        (300, 550, 300, 550)
+    );
+    assert_eq!(inversion.score, 3);
+
+    // A longer reach lets a4 bridge the direct blocks, leaving too few
+    // anchors to the inversion
+    let options = BlockOptions {
+        max_gap: 1,
+        min_anchors: 2,
+    };
+    let blocks = synteny::syntenic_blocks(&book, "spA", "spB", &options).unwrap();
+    assert_eq!(anchors(&blocks), ["+ a1:b1 a2:b2 a4:b4 a6:b6"]);
+    let options = BlockOptions {
+        max_gap: 0,
+        min_anchors: 4,
+    };
+    assert!(synteny::syntenic_blocks(&book, "spA", "spB", &options)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn tandem_arrays() {
+    let fixture = Fixture::new("tandems");
+    let book = book(&fixture, &["spA", "spC"]);
+    assert!(synteny::tandem_arrays(&book, "spA", 0).unwrap().is_empty());
+    let arrays = synteny::tandem_arrays(&book, "spC", 0).unwrap();
+    assert_eq!(arrays.len(), 1);
+    assert_eq!(arrays[0].genes, ["c3", "c3b"]);
+    assert_eq!(arrays[0].copy_number(), 2);
+    assert_eq!(
+        (arrays[0].chr.as_str(), arrays[0].start, arrays[0].end),
+        ("chr1", 300, 450)
+    );
+
+    // Copies may be split by at most `max_gap` genes
+    fixture.annotations(
+        "spC",
+        &[("c3", '+'), ("c1", '+'), ("c2", '+'), ("c3b", '+')],
+    );
+    let book = self::book(&fixture, &["spC"]);
+    assert!(synteny::tandem_arrays(&book, "spC", 1).unwrap().is_empty());
+    let arrays = synteny::tandem_arrays(&book, "spC", 2).unwrap();
+    assert_eq!(arrays[0].genes, ["c3", "c3b"]);
+}
+
+/// The fixture, `spB` being `spA` with its genes `a3` to `a5` inverted
+fn rearranged(name: &str) -> Fixture {
+    let fixture = Fixture::new(name);
+    fixture.annotations(
+        "spB",
+        &[
+            ("b1", '+'),
+            ("b2", '+'),
+            ("b5", '-'),
+            ("b4", '-'),
+            ("b3", '+'),
+            ("b6", '-'),
+        ],
+    );
+    fixture
+}
+
+#[test]
+fn breakpoint_distances() {
+    let fixture = rearranged("breakpoints");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+    let distance = |a: &str, b: &str| analysis::breakpoint_distance(&book, a, b).unwrap();
+    assert_eq!(distance("spA", "spA"), 0);
+    // The adjacencies a3|a4 and a4|a5 survive the inversion
+    assert_eq!(distance("spA", "spB"), 2);
+    assert_eq!(distance("spB", "spA"), 2);
+    // c1 and c3 are flipped, the extra copy c3b being ignored
+    assert_eq!(distance("spA", "spC"), 3);
+
+    // Matching the copies of f3 in order pairs a3b with c3b
+    std::fs::write(fixture.path("families/f3.txt"), "a3 a3b b3 c3 c3b").unwrap();
+    fixture.annotations(
+        "spA",
+        &[
+            ("a1", '+'),
+            ("a2", '+'),
+            ("a3", '-'),
+            ("a3b", '+'),
+            ("a4", '+'),
+            ("a5", '+'),
+            ("a6", '-'),
+        ],
+    );
+    let book = self::book(&fixture, &["spA", "spC"]);
+    let distance =
+        |duplicates| analysis::breakpoint_distance_with(&book, "spA", "spC", duplicates).unwrap();
+    assert_eq!(distance(Duplicates::Exemplar), 3);
+    assert_eq!(distance(Duplicates::Matching), 4);
+}
+
+#[test]
+fn dcj_distances() {
+    let fixture = rearranged("dcj");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+    let distance = |a: &str, b: &str| analysis::dcj_distance(&book, a, b).unwrap();
+    assert_eq!(distance("spA", "spA"), 0);
+    assert_eq!(distance("spA", "spB"), 1);
+    assert_eq!(distance("spB", "spA"), 1);
+    // f3 is duplicated in spC, leaving only c1 flipped
+    assert_eq!(distance("spA", "spC"), 1);
+
+    // Only f1, f2 and f6, untouched by the inversion, are found in all
+    // species
+    fixture.annotations("spC", &[("c1", '+'), ("c2", '+'), ("c6", '-')]);
+    let book = self::book(&fixture, &["spA", "spB", "spC"]);
+    let universal = DcjOptions { universal: true };
+    assert_eq!(analysis::dcj_distance(&book, "spA", "spB").unwrap(), 1);
+    assert_eq!(
+        analysis::dcj_distance_with(&book, "spA", "spB", &universal).unwrap(),
+        0
+    );
+}
+
+#[test]
+fn ancestral_regions() {
+    let fixture = Fixture::new("ancestors");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+    let tree = "((spA,spB)anc,spC)root;";
+
+    // f3 is duplicated in spC; spA and the outgroup spC share the order of
+    // f2 to f6, but f1 lies next to f2 differently in all three species
+    let regions = analysis::ancestral_regions(&book, tree, Some("anc")).unwrap();
+    let regions = regions
+        .iter()
+        .map(|r| (r.genes.clone(), r.support.clone()))
+        .collect::<Vec<_>>();
+    assert_eq!(
+        regions,
+        [
+            (
+                vec![
+                    (2, Strand::Direct),
+                    (4, Strand::Direct),
+                    (5, Strand::Direct),
+                    (6, Strand::Reverse)
+                ],
+                vec![2, 2, 2]
+            ),
+            (vec![(1, Strand::Direct)], vec![]),
+        ]
+    );
+
+    assert!(analysis::ancestral_regions(&book, tree, Some("missing")).is_err());
+    assert!(analysis::ancestral_regions(&book, "((spA,spB)anc,spD)root;", None).is_err());
+}
+
+#[test]
+fn syntenic_clusters() {
+    let fixture = Fixture::new("clusters");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+
+    let clusters = synteny::syntenic_clusters(&book, &ClusterOptions::default()).unwrap();
+    assert_eq!(
+        clusters.iter().map(|c| c.family).collect::<Vec<_>>(),
+        [1, 2, 3, 4, 5, 6]
+    );
+    assert_eq!(clusters[2].genes, ["a3", "b3", "c3", "c3b"]);
+    assert_eq!(clusters[2].species, ["spA", "spB", "spC"]);
+
+    // The tandem array of spC disturbs the neighborhoods of c2 to c4
+    let strict = ClusterOptions {
+        min_score: 1.,
+        ..Default::default()
+    };
+    let clusters = synteny::syntenic_clusters(&book, &strict).unwrap();
+    assert_eq!(
+        clusters.iter().map(|c| c.family).collect::<Vec<_>>(),
+        [1, 5, 6]
+    );
+    let clusters = synteny::syntenic_clusters(
+        &book,
+        &ClusterOptions {
+            min_species: 2,
+            ..strict
+        },
+    )
+    .unwrap();
+    assert_eq!(clusters.len(), 6);
+    assert_eq!(clusters[2].genes, ["a3", "b3"]);
+}
+
+#[test]
+fn collinear_chains() {
+    let fixture = Fixture::new("chains");
+    let book = book(&fixture, &["spA", "spC"]);
+
+    // Skipping either copy of f3 costs a gap
+    let options = ChainOptions::default();
+    let chains = synteny::collinear_chains(&book, "spA", "spC", &options).unwrap();
+    assert_eq!(anchors(&chains), ["+ a1:c1 a2:c2 a3:c3b a4:c4 a5:c5 a6:c6"]);
+    assert_eq!(chains[0].score, 299);
+
+    let options = ChainOptions {
+        max_gap: 0,
+        min_anchors: 3,
+        ..Default::default()
+    };
+    let chains = synteny::collinear_chains(&book, "spA", "spC", &options).unwrap();
+    assert_eq!(
+        anchors(&chains),
+        ["+ a1:c1 a2:c2 a3:c3", "+ a3:c3b a4:c4 a5:c5 a6:c6"]
+    );
+    assert_eq!(
+        chains.iter().map(|c| c.score).collect::<Vec<_>>(),
+        [150, 200]
+    );
+
+    let options = ChainOptions {
+        min_anchors: 7,
+        ..Default::default()
+    };
+    assert!(synteny::collinear_chains(&book, "spA", "spC", &options)
+        .unwrap()
+        .is_empty());
+}
+
+#[test]
+fn refined_families() {
+    let fixture = Fixture::new("orthologs");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+    let genes = |groups: Vec<SubFamily>| {
+        groups
+            .into_iter()
+            .map(|g| (g.family, g.genes))
+            .collect::<Vec<_>>()
+    };
+
+    // Only f3 holds several genes of a species
+    let refined = synteny::refine_families(&book, &OrthologyOptions::default()).unwrap();
+    assert_eq!(
+        genes(refined),
+        [
+            (3, vec!["a3".to_owned(), "b3".into(), "c3".into()]),
+            (3, vec!["c3b".to_owned()]),
+        ]
+    );
+
+    let strict = OrthologyOptions {
+        min_score: 0.9,
+        ..Default::default()
+    };
+    assert_eq!(
+        genes(synteny::ortholog_groups(&book, 3, &strict).unwrap()),
+        [
+            (3, vec!["a3".to_owned(), "b3".into()]),
+            (3, vec!["c3".to_owned()]),
+            (3, vec!["c3b".to_owned()]),
+        ]
+    );
+    assert_eq!(
+        genes(synteny::ortholog_groups(&book, 1, &strict).unwrap()),
+        [(1, vec!["a1".to_owned(), "b1".into(), "c1".into()])]
+    );
+}
+
+#[test]
+fn painting() {
+    let fixture = Fixture::new("painting");
+    fixture.chromosomes(
+        "spA",
+        &[
+            ("chr1", &[("a1", '+'), ("a2", '+')]),
+            ("chr2", &[("a3", '-'), ("a4", '+')]),
+            ("chr3", &[("a5", '+'), ("a6", '-')]),
+        ],
+    );
+    let book = book(&fixture, &["spA", "spB"]);
+
+    let painting = synteny::paint(&book, "spA").unwrap();
+    assert_eq!(painting.chromosomes, ["chr1", "chr2", "chr3"]);
+    // Ties between chromosomes go to the first one
+    assert_eq!(
+        painting
+            .genes
+            .iter()
+            .map(|g| (g.id.as_str(), g.color))
+            .collect::<Vec<_>>(),
+        [
+            ("b1", Some(0)),
+            ("b2", Some(0)),
+            ("b3", Some(0)),
+            ("b4", Some(1)),
+            ("b5", Some(1)),
+            ("b6", Some(2)),
+        ]
+    );
+
+    let painting = synteny::paint(&book, "spB").unwrap();
+    assert_eq!(painting.chromosomes, ["chr1"]);
+    assert!(painting
+        .genes
+        .iter()
+        .all(|g| g.species == "spA" && g.color == Some(0)));
+}
+
+#[test]
+fn conservation_matrix() {
+    let fixture = Fixture::new("conservation");
+    let book = book(&fixture, &["spA", "spB", "spC"]);
+
+    let loose = synteny::conservation_matrix(&book, &ConservationOptions::default()).unwrap();
+    assert_eq!(loose.species, ["spA", "spB", "spC"]);
+    assert!(loose.values.iter().flatten().all(|v| *v == 1.));
+
+    // The landscapes of c2 to c4 only hold some of the families around a2
+    // to a4, not the other way around
+    let strict = ConservationOptions {
+        min_score: 1.,
+        ..Default::default()
+    };
+    let matrix = synteny::conservation_matrix(&book, &strict).unwrap();
+    assert_eq!(matrix.values, [[1., 1., 0.5], [1., 1., 0.5], [1., 1., 1.]]);
+    let mut tsv = Vec::new();
+    matrix.write_tsv(&mut tsv).unwrap();
+    assert_eq!(
+        String::from_utf8(tsv).unwrap(),
+        "\tspA\tspB\tspC\n\
+         spA\t1.0000\t1.0000\t0.5000\n\
+         spB\t1.0000\t1.0000\t0.5000\n\
+         spC\t1.0000\t1.0000\t1.0000\n"
+    );
+}